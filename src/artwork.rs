@@ -0,0 +1,341 @@
+use crate::auth::to_hex;
+use image::imageops::FilterType;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use url::Url;
+
+/// Folder of the output where artwork is written to.
+pub const ARTWORK_DIR: &str = "artwork";
+/// Shown when there's no poster at all; written next to the pages so it works offline.
+pub const NO_POSTER_FILE: &str = "no-poster.svg";
+/// Poster width used in listings and search results.
+pub const THUMB_WIDTH: u32 = 200;
+/// Poster width used on detail pages.
+pub const FULL_WIDTH: u32 = 600;
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// A poster resized for the generated site.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Poster {
+    /// Detail page size.
+    pub url: String,
+    /// Listing and search size.
+    pub thumb_url: String,
+    /// `srcset` listing both sizes; empty when the image couldn't be resized and is used as is.
+    pub srcset: String,
+}
+
+/// Artwork of a movie or series, as URLs of files in the output folder.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Artwork {
+    pub poster: Option<Poster>,
+    pub fanart: Option<String>,
+    /// Season posters of a series, by season number.
+    pub seasons: BTreeMap<u8, Poster>,
+}
+
+/// The first of `<folder>/<name>.<image extension>` that exists, trying names in order.
+fn find_image(folder: &Path, names: &[String]) -> Option<PathBuf> {
+    names.iter()
+        .flat_map(|name| IMAGE_EXTENSIONS.iter().map(move |ext| folder.join(format!("{}.{}", name, ext))))
+        .find(|path| path.is_file())
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// Writes through a temporary file so an interrupted run doesn't leave a broken image behind.
+fn write_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let part = path.with_extension("part");
+    std::fs::write(&part, data)?;
+    std::fs::rename(&part, path)
+}
+
+/// Writes local and OMDB artwork into `<output_dir>/artwork`, resizing posters to listing and
+/// detail page sizes. Downloaded posters are kept in a content-addressed cache between runs.
+pub struct ArtworkExporter {
+    cache_dir: PathBuf,
+    output_dir: PathBuf,
+    base_url: Option<Url>,
+}
+
+impl ArtworkExporter {
+    pub fn new(cache_dir: &Path, output_dir: &Path, base_url: &Option<Url>) -> Self {
+        ArtworkExporter { cache_dir: cache_dir.to_path_buf(), output_dir: output_dir.to_path_buf(), base_url: base_url.clone() }
+    }
+
+    fn url(&self, file_name: &str) -> String {
+        let path = format!("{}/{}", ARTWORK_DIR, file_name);
+        match &self.base_url {
+            Some(url) => url.join(&path).unwrap().to_string(),
+            None => path,
+        }
+    }
+
+    fn artwork_dir(&self) -> io::Result<PathBuf> {
+        let dir = self.output_dir.join(ARTWORK_DIR);
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// Copies `image` as is, e.g. fanart. Copies from earlier runs are reused.
+    fn copy(&self, image: &Path) -> Option<String> {
+        let copy = || -> io::Result<String> {
+            let metadata = std::fs::metadata(image)?;
+            let mtime = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            image.to_str().hash(&mut hasher);
+            metadata.len().hash(&mut hasher);
+            mtime.as_nanos().hash(&mut hasher);
+            let extension = image.extension().and_then(|e| e.to_str()).unwrap_or("jpg").to_lowercase();
+            let file_name = format!("{}.{}", hasher.finish(), extension);
+
+            let target = self.artwork_dir()?.join(&file_name);
+            if !target.exists() {
+                std::fs::copy(image, target)?;
+                log::info!(target: "cli", "Artwork discovered: {:?}", image);
+            }
+            Ok(file_name)
+        };
+        copy().map(|file_name| self.url(&file_name))
+            .map_err(|e| log::warn!(target: "cli", "Failed to copy artwork {:?}: {}", image, e))
+            .ok()
+    }
+
+    /// Thumbnail and detail page variants of a poster, named after its content.
+    /// Posters are never scaled up, and variants from earlier runs are reused.
+    fn poster_variants(&self, data: &[u8], extension: &str) -> io::Result<Poster> {
+        let hash = sha256_hex(data);
+        let dir = self.artwork_dir()?;
+        let width = image::ImageReader::new(io::Cursor::new(data)).with_guessed_format()?
+            .into_dimensions().map(|(width, _)| width);
+        let width = match width {
+            Ok(width) => width,
+            Err(e) => {
+                // Unknown format; better shown full size than not at all
+                log::warn!(target: "cli", "Could not read poster dimensions, using it as is: {}", e);
+                let file_name = format!("{}.{}", hash, extension);
+                if !dir.join(&file_name).exists() {
+                    write_file(&dir.join(&file_name), data)?;
+                }
+                let url = self.url(&file_name);
+                return Ok(Poster { url: url.clone(), thumb_url: url, srcset: String::new() });
+            }
+        };
+
+        let mut decoded = None;
+        let mut variants = Vec::new();
+        for target_width in [THUMB_WIDTH, FULL_WIDTH].map(|w| w.min(width)) {
+            let file_name = format!("{}-{}.jpg", hash, target_width);
+            if !dir.join(&file_name).exists() {
+                if decoded.is_none() {
+                    decoded = Some(image::load_from_memory(data).map_err(io::Error::other)?);
+                }
+                let resized = decoded.as_ref().unwrap().resize(target_width, u32::MAX, FilterType::Lanczos3).to_rgb8();
+                let mut jpeg = Vec::new();
+                resized.write_to(&mut io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg).map_err(io::Error::other)?;
+                write_file(&dir.join(&file_name), &jpeg)?;
+            }
+            variants.push((self.url(&file_name), target_width));
+        }
+        variants.dedup();
+        Ok(Poster {
+            url: variants.last().unwrap().0.clone(),
+            thumb_url: variants[0].0.clone(),
+            srcset: variants.iter().map(|(url, width)| format!("{} {}w", url, width)).collect::<Vec<_>>().join(", "),
+        })
+    }
+
+    fn local_poster(&self, image: &Path) -> Option<Poster> {
+        let extension = image.extension().and_then(|e| e.to_str()).unwrap_or("jpg").to_lowercase();
+        match std::fs::read(image).and_then(|data| self.poster_variants(&data, &extension)) {
+            Ok(poster) => {
+                log::info!(target: "cli", "Artwork discovered: {:?}", image);
+                Some(poster)
+            }
+            Err(e) => {
+                log::warn!(target: "cli", "Failed to export poster {:?}: {}", image, e);
+                None
+            }
+        }
+    }
+
+    /// Cache entry pointing from a poster URL to the downloaded file.
+    fn url_index_path(&self, url: &str) -> PathBuf {
+        self.cache_dir.join("urls").join(sha256_hex(url.as_bytes()))
+    }
+
+    /// A poster downloaded on an earlier run, with its file extension.
+    fn cached_download(&self, url: &str) -> Option<(Vec<u8>, String)> {
+        let file_name = std::fs::read_to_string(self.url_index_path(url)).ok()?;
+        let data = std::fs::read(self.cache_dir.join(file_name.trim())).ok()?;
+        let extension = Path::new(file_name.trim()).extension().and_then(|e| e.to_str()).unwrap_or("jpg").to_string();
+        Some((data, extension))
+    }
+
+    fn store_download(&self, url: &str, data: &[u8]) -> io::Result<String> {
+        let extension = Url::parse(url).ok()
+            .and_then(|u| Path::new(u.path()).extension().and_then(|e| e.to_str()).map(str::to_lowercase))
+            .filter(|e| IMAGE_EXTENSIONS.contains(&e.as_str()))
+            .unwrap_or_else(|| "jpg".to_string());
+        let file_name = format!("{}.{}", sha256_hex(data), extension);
+        std::fs::create_dir_all(self.cache_dir.join("urls"))?;
+        if !self.cache_dir.join(&file_name).exists() {
+            write_file(&self.cache_dir.join(&file_name), data)?;
+        }
+        write_file(&self.url_index_path(url), file_name.as_bytes())?;
+        Ok(extension)
+    }
+
+    /// Downloads a remote poster once and resizes it like a local one.
+    fn remote_poster(&self, url: &str) -> Option<Poster> {
+        if url == crate::movie::NO_POSTER_URL || Url::parse(url).is_err() {
+            return None;
+        }
+        let (data, extension) = match self.cached_download(url) {
+            Some(cached) => cached,
+            None => {
+                let download = || -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
+                    let data = crate::http::downloads().get(url, &[])?.error_for_status()?.bytes()?.to_vec();
+                    let extension = self.store_download(url, &data)?;
+                    Ok((data, extension))
+                };
+                match download() {
+                    Ok(downloaded) => {
+                        log::info!(target: "cli", "Downloaded poster {}", url);
+                        downloaded
+                    }
+                    Err(e) => {
+                        log::warn!(target: "cli", "Failed to download poster {}: {}", url, e);
+                        return None;
+                    }
+                }
+            }
+        };
+        self.poster_variants(&data, &extension)
+            .map_err(|e| log::warn!(target: "cli", "Failed to resize poster {}: {}", url, e))
+            .ok()
+    }
+
+    /// A poster from an image URL or local path, e.g. one set in an overrides file.
+    pub fn poster(&self, source: &str) -> Option<Poster> {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            self.remote_poster(source)
+        } else {
+            self.local_poster(Path::new(source))
+        }
+    }
+
+    /// `<name>-poster.jpg` and `<name>-fanart.jpg` next to the movie, or `poster.jpg`/`folder.jpg` and
+    /// `fanart.jpg` when the movie has its own folder below `library_folder`. Without a local poster,
+    /// `remote_poster` is downloaded.
+    pub fn movie_artwork(&self, video: &Path, library_folder: &Path, remote_poster: Option<&str>) -> Artwork {
+        let Some(folder) = video.parent() else { return Artwork::default() };
+        let stem = video.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        // A poster.jpg directly in the library folder isn't about any one movie
+        let own_folder = folder != library_folder;
+        let names = |kind: &str, generic: &[&str]| {
+            let mut names = vec![format!("{}-{}", stem, kind)];
+            if own_folder {
+                names.extend(generic.iter().map(|n| n.to_string()));
+            }
+            names
+        };
+        Artwork {
+            poster: find_image(folder, &names("poster", &["poster", "folder", "cover"])).and_then(|p| self.local_poster(&p))
+                .or_else(|| remote_poster.and_then(|url| self.remote_poster(url))),
+            fanart: find_image(folder, &names("fanart", &["fanart"])).and_then(|p| self.copy(&p)),
+            seasons: BTreeMap::new(),
+        }
+    }
+
+    /// `poster.jpg`/`folder.jpg`, `fanart.jpg` and `seasonNN-poster.jpg` (`season-specials-poster.jpg`
+    /// for season 0) in the series folder. Without a local poster, `remote_poster` is downloaded.
+    pub fn series_artwork(&self, series_folder: &Path, seasons: impl IntoIterator<Item = u8>, remote_poster: Option<&str>) -> Artwork {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let season_posters = seasons.into_iter().filter_map(|season| {
+            let name = if season == 0 { "season-specials-poster".to_string() } else { format!("season{:02}-poster", season) };
+            find_image(series_folder, &[name])
+                .and_then(|p| self.local_poster(&p))
+                .map(|poster| (season, poster))
+        }).collect();
+        Artwork {
+            poster: find_image(series_folder, &names(&["poster", "folder", "cover"])).and_then(|p| self.local_poster(&p))
+                .or_else(|| remote_poster.and_then(|url| self.remote_poster(url))),
+            fanart: find_image(series_folder, &names(&["fanart"])).and_then(|p| self.copy(&p)),
+            seasons: season_posters,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        image::RgbImage::new(width, height).write_to(&mut io::Cursor::new(&mut data), image::ImageFormat::Png).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_movie_artwork() {
+        let dir = tempfile::Builder::new().prefix("test_artwork").tempdir().unwrap();
+        let (library, output) = (dir.path().join("movies"), dir.path().join("pub"));
+        let exporter = ArtworkExporter::new(&dir.path().join("cache"), &output, &None);
+        let movie = library.join("Movie (2001)/Movie.2001.mkv");
+        let loose = library.join("Loose.1999.mkv");
+        std::fs::create_dir_all(movie.parent().unwrap()).unwrap();
+        std::fs::write(library.join("Movie (2001)/folder.png"), png(400, 600)).unwrap();
+        std::fs::write(library.join("Movie (2001)/fanart.jpg"), "fanart").unwrap();
+        std::fs::write(library.join("poster.jpg"), "not a poster").unwrap();
+
+        let artwork = exporter.movie_artwork(&movie, &library, None);
+        let poster = artwork.poster.unwrap();
+        assert!(poster.thumb_url.starts_with("artwork/") && poster.thumb_url.ends_with("-200.jpg"));
+        assert!(poster.url.ends_with("-400.jpg"), "never scaled up");
+        assert_eq!(poster.srcset, format!("{} 200w, {} 400w", poster.thumb_url, poster.url));
+        assert_eq!(image::image_dimensions(output.join(&poster.thumb_url)).unwrap(), (200, 300));
+        assert_eq!(std::fs::read_to_string(output.join(artwork.fanart.unwrap())).unwrap(), "fanart");
+
+        // The library folder's own poster.jpg isn't used for a loose movie, nor is a missing remote one
+        let artwork = exporter.movie_artwork(&loose, &library, Some(crate::movie::NO_POSTER_URL));
+        assert_eq!(artwork, Artwork::default());
+    }
+
+    #[test]
+    fn test_remote_poster_cache() {
+        let dir = tempfile::Builder::new().prefix("test_artwork").tempdir().unwrap();
+        let exporter = ArtworkExporter::new(&dir.path().join("cache"), &dir.path().join("pub"), &Some(Url::parse("http://nas/media/").unwrap()));
+        // Nothing listens here; the poster can only come from the cache
+        let url = "http://127.0.0.1:9/poster.png";
+        assert_eq!(exporter.remote_poster(url), None);
+        exporter.store_download(url, &png(100, 150)).unwrap();
+        let poster = exporter.remote_poster(url).unwrap();
+        assert!(poster.url.starts_with("http://nas/media/artwork/") && poster.url.ends_with("-100.jpg"));
+        assert_eq!(poster.url, poster.thumb_url);
+        assert_eq!(poster.srcset, format!("{} 100w", poster.url));
+    }
+
+    #[test]
+    fn test_series_artwork() {
+        let dir = tempfile::Builder::new().prefix("test_artwork").tempdir().unwrap();
+        let (series, output) = (dir.path().join("Show"), dir.path().join("pub"));
+        let exporter = ArtworkExporter::new(&dir.path().join("cache"), &output, &None);
+        std::fs::create_dir_all(&series).unwrap();
+        for image in ["poster.jpg", "season01-poster.jpg", "season-specials-poster.jpg"] {
+            std::fs::write(series.join(image), png(300, 450)).unwrap();
+        }
+        let artwork = exporter.series_artwork(&series, [0, 1, 2], None);
+        assert!(artwork.poster.is_some());
+        assert_eq!(artwork.fanart, None);
+        assert_eq!(artwork.seasons.keys().copied().collect::<Vec<_>>(), vec![0, 1]);
+    }
+}
@@ -1,13 +1,30 @@
 use rusqlite::{Connection, Result, params};
 use std::path::Path;
-use serde_json;
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::fs;
 
+use crate::matching::{normalize_title, MatchRecord};
+use crate::media::OmdbError;
+use crate::provider::SeasonMetadata;
 use crate::movie::MovieInfo;
+use crate::probe::MediaProbe;
 use crate::tv::{TvSeriesInfo, EpisodeTemplateData};
 
+/// How long a season listing is trusted to have all aired episodes; an episode missing from an
+/// older listing fetches it again.
+pub const SEASON_REFRESH_SECS: i64 = 24 * 60 * 60;
+
+/// How long an OMDB "not found" is trusted before the title is looked up again.
+pub const NOT_FOUND_RETRY_SECS: i64 = 7 * 24 * 60 * 60;
+
+pub fn now_secs() -> i64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64)
+}
+
+/// A connection to the cache database. Clones share the connection, e.g. with the OMDB quota.
+#[derive(Clone)]
 pub struct MediaCache {
-    conn: Connection,
+    conn: Arc<Mutex<Connection>>,
 }
 
 impl MediaCache {
@@ -55,14 +72,66 @@ impl MediaCache {
             [],
         )?;
 
-        Ok(MediaCache { conn })
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS tv_seasons (
+                series_name TEXT NOT NULL,
+                season INTEGER NOT NULL,
+                fetched_at INTEGER NOT NULL,
+                json_data TEXT NOT NULL,
+                PRIMARY KEY (series_name, season)
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS media_probes (
+                path TEXT PRIMARY KEY,
+                mtime INTEGER NOT NULL,
+                json_data TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS omdb_matches (
+                query TEXT PRIMARY KEY,
+                media_type TEXT NOT NULL,
+                confidence REAL NOT NULL,
+                imdb_id TEXT,
+                json_data TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS omdb_not_found (
+                query TEXT PRIMARY KEY,
+                checked_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS http_quota (
+                name TEXT PRIMARY KEY,
+                day INTEGER NOT NULL,
+                calls INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        Ok(MediaCache { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
     }
 
     /// Store a movie in the cache
     pub fn store_movie(&self, movie: &MovieInfo, path_hash: &str) -> Result<()> {
         let json_data = serde_json::to_string(movie).unwrap_or_default();
         
-        self.conn.execute(
+        self.conn().execute(
             "INSERT OR REPLACE INTO movies (name, year, path_hash, json_data) 
              VALUES (?1, ?2, ?3, ?4)",
             params![
@@ -78,7 +147,8 @@ impl MediaCache {
 
     /// Retrieve a movie from the cache by path hash
     pub fn get_movie_by_path_hash(&self, path_hash: &str) -> Result<Option<MovieInfo>> {
-        let mut stmt = self.conn.prepare(
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
             "SELECT json_data FROM movies WHERE path_hash = ?1"
         )?;
         
@@ -87,11 +157,9 @@ impl MediaCache {
             Ok(json_data)
         })?;
         
-        for movie_result in movie_iter {
-            if let Ok(json_data) = movie_result {
-                if let Ok(movie) = serde_json::from_str::<MovieInfo>(&json_data) {
-                    return Ok(Some(movie));
-                }
+        for json_data in movie_iter.flatten() {
+            if let Ok(movie) = serde_json::from_str::<MovieInfo>(&json_data) {
+                return Ok(Some(movie));
             }
         }
         
@@ -102,7 +170,7 @@ impl MediaCache {
     pub fn store_tv_series(&self, series: &TvSeriesInfo) -> Result<()> {
         let json_data = serde_json::to_string(series).unwrap_or_default();
         
-        self.conn.execute(
+        self.conn().execute(
             "INSERT OR REPLACE INTO tv_series (name, year, json_data) 
              VALUES (?1, ?2, ?3)",
             params![
@@ -117,7 +185,8 @@ impl MediaCache {
 
     /// Retrieve TV series info from cache by name
     pub fn get_tv_series_by_name(&self, series_name: &str) -> Result<Option<TvSeriesInfo>> {
-        let mut stmt = self.conn.prepare(
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
             "SELECT json_data FROM tv_series WHERE name = ?1"
         )?;
         
@@ -126,11 +195,9 @@ impl MediaCache {
             Ok(json_data)
         })?;
         
-        for series_result in series_iter {
-            if let Ok(json_data) = series_result {
-                if let Ok(series) = serde_json::from_str::<TvSeriesInfo>(&json_data) {
-                    return Ok(Some(series));
-                }
+        for json_data in series_iter.flatten() {
+            if let Ok(series) = serde_json::from_str::<TvSeriesInfo>(&json_data) {
+                return Ok(Some(series));
             }
         }
         
@@ -141,7 +208,7 @@ impl MediaCache {
     pub fn store_tv_episode(&self, series_name: &str, season: u8, episode: u8, data: &EpisodeTemplateData) -> Result<()> {
         let json_data = serde_json::to_string(data).unwrap_or_default();
         
-        self.conn.execute(
+        self.conn().execute(
             "INSERT OR REPLACE INTO tv_episodes (series_name, season, episode, json_data) 
              VALUES (?1, ?2, ?3, ?4)",
             params![
@@ -157,7 +224,8 @@ impl MediaCache {
 
     /// Retrieve TV episode info from cache
     pub fn get_tv_episode(&self, series_name: &str, season: u8, episode: u8) -> Result<Option<EpisodeTemplateData>> {
-        let mut stmt = self.conn.prepare(
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
             "SELECT json_data FROM tv_episodes WHERE series_name = ?1 AND season = ?2 AND episode = ?3"
         )?;
         
@@ -166,14 +234,182 @@ impl MediaCache {
             Ok(json_data)
         })?;
         
-        for episode_result in episode_iter {
-            if let Ok(json_data) = episode_result {
-                if let Ok(episode_data) = serde_json::from_str::<EpisodeTemplateData>(&json_data) {
-                    return Ok(Some(episode_data));
-                }
+        for json_data in episode_iter.flatten() {
+            if let Ok(episode_data) = serde_json::from_str::<EpisodeTemplateData>(&json_data) {
+                return Ok(Some(episode_data));
             }
         }
         
         Ok(None)
     }
+
+    /// Store a season listing in the cache, keyed by the series' IMDb id, or its name without one
+    pub fn store_tv_season(&self, series: &str, season: u8, listing: &SeasonMetadata) -> Result<()> {
+        let json_data = serde_json::to_string(listing).unwrap_or_default();
+        self.conn().execute(
+            "INSERT OR REPLACE INTO tv_seasons (series_name, season, fetched_at, json_data) VALUES (?1, ?2, ?3, ?4)",
+            params![series, season, now_secs(), json_data],
+        )?;
+        Ok(())
+    }
+
+    /// Retrieve a season listing from cache by the key it was stored under, with when it was fetched
+    pub fn get_tv_season(&self, series: &str, season: u8) -> Result<Option<(SeasonMetadata, i64)>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT json_data, fetched_at FROM tv_seasons WHERE series_name = ?1 AND season = ?2"
+        )?;
+        let mut rows = stmt.query(params![series, season])?;
+        while let Some(row) = rows.next()? {
+            let json_data: String = row.get(0)?;
+            if let Ok(listing) = serde_json::from_str::<SeasonMetadata>(&json_data) {
+                return Ok(Some((listing, row.get(1)?)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Store the probe of a media file, replacing the one of an older version of the file
+    pub fn store_probe(&self, path: &Path, mtime: i64, probe: &MediaProbe) -> Result<()> {
+        let json_data = serde_json::to_string(probe).unwrap_or_default();
+
+        self.conn().execute(
+            "INSERT OR REPLACE INTO media_probes (path, mtime, json_data)
+             VALUES (?1, ?2, ?3)",
+            params![
+                path.to_string_lossy(),
+                mtime,
+                json_data
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Retrieve the probe of a media file, if it was made at this mtime
+    pub fn get_probe(&self, path: &Path, mtime: i64) -> Result<Option<MediaProbe>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT json_data FROM media_probes WHERE path = ?1 AND mtime = ?2"
+        )?;
+
+        let probe_iter = stmt.query_map(params![path.to_string_lossy(), mtime], |row| {
+            let json_data: String = row.get(0)?;
+            Ok(json_data)
+        })?;
+
+        for json_data in probe_iter.flatten() {
+            if let Ok(probe) = serde_json::from_str::<MediaProbe>(&json_data) {
+                return Ok(Some(probe));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Key of a search, so that titles differing only in case or punctuation share it
+    fn match_query(media_type: &str, title: &str, year: Option<u16>) -> String {
+        format!("{}:{}:{}", media_type, normalize_title(title), year.map(|y| y.to_string()).unwrap_or_default())
+    }
+
+    /// Store the outcome of an OMDB search, replacing an earlier one for the same query
+    pub fn store_match(&self, record: &MatchRecord) -> Result<()> {
+        let json_data = serde_json::to_string(record).unwrap_or_default();
+
+        self.conn().execute(
+            "INSERT OR REPLACE INTO omdb_matches (query, media_type, confidence, imdb_id, json_data)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                Self::match_query(&record.media_type, &record.query, record.year),
+                record.media_type,
+                record.confidence(),
+                record.best.as_ref().map(|b| &b.imdb_id),
+                json_data
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Retrieve the outcome of an earlier OMDB search for a title
+    pub fn get_match(&self, media_type: &str, title: &str, year: Option<u16>) -> Result<Option<MatchRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT json_data FROM omdb_matches WHERE query = ?1"
+        )?;
+
+        let match_iter = stmt.query_map([Self::match_query(media_type, title, year)], |row| {
+            let json_data: String = row.get(0)?;
+            Ok(json_data)
+        })?;
+
+        for json_data in match_iter.flatten() {
+            if let Ok(record) = serde_json::from_str::<MatchRecord>(&json_data) {
+                return Ok(Some(record));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Record that OMDB doesn't know a lookup, so it isn't repeated for a while
+    pub fn store_not_found(&self, query: &str) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO omdb_not_found (query, checked_at) VALUES (?1, ?2)",
+            params![query, now_secs()],
+        )?;
+        Ok(())
+    }
+
+    /// Whether OMDB didn't know a lookup within the last `NOT_FOUND_RETRY_SECS`
+    pub fn is_not_found(&self, query: &str) -> Result<bool> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT 1 FROM omdb_not_found WHERE query = ?1 AND checked_at > ?2"
+        )?;
+        stmt.exists(params![query, now_secs() - NOT_FOUND_RETRY_SECS])
+    }
+
+    /// Store the calls made to a service on a day (days since the epoch)
+    pub fn store_quota(&self, name: &str, day: i64, calls: u32) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO http_quota (name, day, calls) VALUES (?1, ?2, ?3)",
+            params![name, day, calls],
+        )?;
+        Ok(())
+    }
+
+    /// The last day calls to a service were counted, and their number
+    pub fn get_quota(&self, name: &str) -> Result<Option<(i64, u32)>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT day, calls FROM http_quota WHERE name = ?1")?;
+        let mut rows = stmt.query(params![name])?;
+        match rows.next()? {
+            Some(row) => Ok(Some((row.get(0)?, row.get(1)?))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Runs a metadata lookup unless the providers recently didn't know `query`, and records it when it doesn't.
+/// Other failures, like network errors, aren't recorded and are retried on the next run.
+pub fn unless_not_found<T>(
+    cache: &Option<MediaCache>,
+    query: &str,
+    lookup: impl FnOnce() -> std::result::Result<T, Box<dyn std::error::Error>>,
+) -> std::result::Result<T, Box<dyn std::error::Error>> {
+    if let Some(media_cache) = cache {
+        if media_cache.is_not_found(query).unwrap_or(false) {
+            return Err(OmdbError::NotFound(format!("{} (cached)", query)).into());
+        }
+    }
+    let result = lookup();
+    if let (Err(err), Some(media_cache)) = (&result, cache) {
+        if crate::provider::is_not_found(&**err) {
+            if let Err(e) = media_cache.store_not_found(query) {
+                log::error!("Failed to store not found '{}' in cache: {}", query, e);
+            }
+        }
+    }
+    result
 }
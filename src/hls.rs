@@ -0,0 +1,325 @@
+use crate::auth::{from_hex, to_hex};
+use crate::probe::MediaProbe;
+use crate::transcode::Ffmpeg;
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// URL prefix of the webserver's HLS endpoint: `/hls/<id>/master.m3u8`.
+pub const HLS_MOUNT: &str = "hls";
+/// Length of every segment but the last, in seconds.
+pub const SEGMENT_SECS: f64 = 6.0;
+/// Default size cap of the on-disk segment cache.
+pub const DEFAULT_CACHE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+/// Audio bitrate shared by all variants, in kbit/s.
+const AUDIO_KBPS: u32 = 128;
+
+/// One rendition of the bitrate ladder.
+#[derive(Debug, PartialEq)]
+pub struct Variant {
+    pub name: &'static str,
+    pub height: u32,
+    pub video_kbps: u32,
+}
+
+/// Renditions offered to the player, best first.
+pub const VARIANTS: &[Variant] = &[
+    Variant { name: "1080p", height: 1080, video_kbps: 5000 },
+    Variant { name: "720p", height: 720, video_kbps: 2800 },
+    Variant { name: "480p", height: 480, video_kbps: 1200 },
+    Variant { name: "360p", height: 360, video_kbps: 700 },
+];
+
+impl Variant {
+    pub fn by_name(name: &str) -> Option<&'static Variant> {
+        VARIANTS.iter().find(|v| v.name == name)
+    }
+
+    /// Peak bandwidth as announced in the master playlist, in bit/s.
+    fn bandwidth(&self) -> u32 {
+        (self.video_kbps * 107 / 100 + AUDIO_KBPS) * 1000
+    }
+}
+
+/// What the playlists need to know about the source file.
+#[derive(Debug, PartialEq, Default)]
+pub struct SourceInfo {
+    pub duration_secs: f64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl SourceInfo {
+    /// Takes what the playlists need from a probe; None if it has no duration.
+    pub fn from_probe(probe: &MediaProbe) -> Option<SourceInfo> {
+        let video = probe.video.as_ref();
+        Some(SourceInfo {
+            duration_secs: probe.duration_secs?,
+            width: video.and_then(|v| v.width),
+            height: video.and_then(|v| v.height),
+        })
+    }
+
+    /// Variants worth offering: no upscaling, but always at least the smallest one.
+    pub fn variants(&self) -> Vec<&'static Variant> {
+        let fitting: Vec<_> = VARIANTS.iter().filter(|v| self.height.is_none_or(|h| v.height <= h)).collect();
+        if fitting.is_empty() {
+            VARIANTS.last().into_iter().collect()
+        } else {
+            fitting
+        }
+    }
+
+    pub fn segment_count(&self) -> u32 {
+        (self.duration_secs / SEGMENT_SECS).ceil().max(1.0) as u32
+    }
+
+    /// Width of `variant` keeping the source aspect ratio, rounded to an even number like `scale=-2:h`.
+    fn width_for(&self, variant: &Variant) -> Option<u32> {
+        match (self.width, self.height) {
+            (Some(w), Some(h)) if h > 0 => Some(((w as f64 * variant.height as f64 / h as f64 / 2.0).round() as u32) * 2),
+            _ => None,
+        }
+    }
+}
+
+/// Id of a media file in HLS URLs: its `/<mount>/<path>` URL path, hex encoded so it fits in one segment.
+pub fn media_id(media_path: &str) -> String {
+    to_hex(media_path.as_bytes())
+}
+
+/// Inverse of [`media_id`].
+pub fn parse_media_id(id: &str) -> Option<String> {
+    from_hex(id).and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+/// Master playlist listing the variants. `query` (e.g. a URL signature) is appended to every URI.
+pub fn master_playlist(source: &SourceInfo, query: &str) -> String {
+    let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:3\n");
+    for variant in source.variants() {
+        playlist.push_str(&format!("#EXT-X-STREAM-INF:BANDWIDTH={}", variant.bandwidth()));
+        if let Some(width) = source.width_for(variant) {
+            playlist.push_str(&format!(",RESOLUTION={}x{}", width, variant.height));
+        }
+        playlist.push_str(&format!(",CODECS=\"avc1.4d4028,mp4a.40.2\"\n{}.m3u8{}\n", variant.name, query));
+    }
+    playlist
+}
+
+/// VOD playlist of one variant, split into `SEGMENT_SECS` segments.
+pub fn media_playlist(source: &SourceInfo, variant: &Variant, query: &str) -> String {
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n",
+        SEGMENT_SECS.ceil() as u32
+    );
+    let count = source.segment_count();
+    for index in 0..count {
+        let length = if index + 1 == count {
+            source.duration_secs - SEGMENT_SECS * index as f64
+        } else {
+            SEGMENT_SECS
+        };
+        playlist.push_str(&format!("#EXTINF:{:.3},\n{}/{}.ts{}\n", length.max(0.0), variant.name, index, query));
+    }
+    playlist.push_str("#EXT-X-ENDLIST\n");
+    playlist
+}
+
+/// ffmpeg arguments that encode segment `index` of `input` at `variant` into an MPEG-TS file.
+pub fn segment_args(input: &Path, variant: &Variant, index: u32, output: &Path) -> Vec<OsString> {
+    let start = format!("{:.3}", SEGMENT_SECS * index as f64);
+    let mut args: Vec<OsString> = vec!["-nostdin".into(), "-v".into(), "error".into(), "-ss".into(), start.clone().into()];
+    args.extend(["-i".into(), input.as_os_str().to_owned()]);
+    args.extend(["-t".to_string(), format!("{:.3}", SEGMENT_SECS)].map(OsString::from));
+    args.extend(["-map", "0:v:0", "-map", "0:a:0?"].map(OsString::from));
+    args.extend([
+        "-c:v".to_string(), "libx264".into(), "-preset".into(), "veryfast".into(),
+        "-profile:v".into(), "main".into(), "-level".into(), "4.0".into(), "-pix_fmt".into(), "yuv420p".into(),
+        "-vf".into(), format!("scale=-2:{}", variant.height),
+        "-b:v".into(), format!("{}k", variant.video_kbps),
+        "-maxrate".into(), format!("{}k", variant.video_kbps * 107 / 100),
+        "-bufsize".into(), format!("{}k", variant.video_kbps * 3 / 2),
+        "-force_key_frames".into(), "expr:gte(t,0)".into(),
+        "-c:a".into(), "aac".into(), "-b:a".into(), format!("{}k", AUDIO_KBPS), "-ac".into(), "2".into(),
+        // Keep timestamps continuous across independently encoded segments
+        "-output_ts_offset".into(), start,
+        "-muxdelay".into(), "0".into(), "-f".into(), "mpegts".into(),
+    ].map(OsString::from));
+    args.push(output.as_os_str().to_owned());
+    args
+}
+
+impl Ffmpeg {
+    /// Encodes one HLS segment into `output`.
+    pub fn encode_segment(&self, input: &Path, variant: &Variant, index: u32, output: &Path) -> io::Result<()> {
+        let status = Command::new(&self.ffmpeg)
+            .args(segment_args(input, variant, index, output))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("ffmpeg failed encoding segment {} of {:?}", index, input)))
+        }
+    }
+}
+
+/// On-disk cache of encoded segments, capped in size with least recently used eviction.
+///
+/// Segments live in `<dir>/<source key>/<variant>/<index>.ts`. The source key covers the file's
+/// path, size and mtime, so replacing a file doesn't serve stale segments. File mtimes double
+/// as access times.
+pub struct SegmentCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Serializes eviction; encoding runs concurrently.
+    eviction: Mutex<()>,
+    temp_counter: AtomicU64,
+}
+
+impl SegmentCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        SegmentCache { dir, max_bytes, eviction: Mutex::new(()), temp_counter: AtomicU64::new(0) }
+    }
+
+    fn source_key(source: &Path) -> io::Result<String> {
+        let metadata = fs::metadata(source)?;
+        let mtime = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(source.as_os_str().as_encoded_bytes());
+        hasher.update(format!("\n{}\n{}", metadata.len(), mtime.as_nanos()));
+        Ok(to_hex(&hasher.finalize()[..12]))
+    }
+
+    /// Returns the cached segment, producing it with `produce` (which writes to the given path) on a miss.
+    pub fn get_or_create<F>(&self, source: &Path, variant: &Variant, index: u32, produce: F) -> io::Result<PathBuf>
+        where F: FnOnce(&Path) -> io::Result<()> {
+        let segment_dir = self.dir.join(Self::source_key(source)?).join(variant.name);
+        let segment_path = segment_dir.join(format!("{}.ts", index));
+        if segment_path.is_file() {
+            // Mark as recently used; failing to do so only makes eviction less accurate
+            let _ = File::options().write(true).open(&segment_path).and_then(|f| f.set_modified(SystemTime::now()));
+            return Ok(segment_path);
+        }
+
+        fs::create_dir_all(&segment_dir)?;
+        // Concurrent requests for the same segment each encode to their own file; the last rename wins
+        let temp_path = segment_dir.join(format!("{}.ts.part{}", index, self.temp_counter.fetch_add(1, Ordering::Relaxed)));
+        if let Err(e) = produce(&temp_path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+        fs::rename(&temp_path, &segment_path)?;
+        self.evict(&segment_path)?;
+        Ok(segment_path)
+    }
+
+    /// Removes the least recently used segments until the cache fits its cap, sparing `keep`.
+    fn evict(&self, keep: &Path) -> io::Result<()> {
+        let _guard = self.eviction.lock().unwrap_or_else(|e| e.into_inner());
+        let mut segments: Vec<(SystemTime, u64, PathBuf)> = walkdir::WalkDir::new(&self.dir).into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext == "ts"))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((metadata.modified().ok()?, metadata.len(), entry.into_path()))
+            })
+            .collect();
+        let mut total: u64 = segments.iter().map(|(_, len, _)| len).sum();
+        segments.sort();
+        for (_, len, path) in segments {
+            if total <= self.max_bytes {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+            log::debug!(target: "cli", "Evicting HLS segment {:?}", path);
+            fs::remove_file(&path)?;
+            total -= len;
+            // Drop directories left empty; fails harmlessly when they aren't
+            for dir in path.ancestors().skip(1).take(2) {
+                let _ = fs::remove_dir(dir);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn source(duration_secs: f64, height: Option<u32>) -> SourceInfo {
+        SourceInfo { duration_secs, width: height.map(|h| h * 16 / 9), height }
+    }
+
+    #[test]
+    fn test_media_id() {
+        let id = media_id("/tv/Show/Season 1/S01E01.mkv");
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(parse_media_id(&id).as_deref(), Some("/tv/Show/Season 1/S01E01.mkv"));
+        assert_eq!(parse_media_id("zz"), None);
+    }
+
+    #[test]
+    fn test_playlists() {
+        let names = |s: &SourceInfo| s.variants().iter().map(|v| v.name).collect::<Vec<_>>();
+        assert_eq!(names(&source(60.0, Some(720))), vec!["720p", "480p", "360p"]);
+        assert_eq!(names(&source(60.0, Some(240))), vec!["360p"]);
+        assert_eq!(names(&source(60.0, None)).len(), VARIANTS.len());
+
+        let master = master_playlist(&source(60.0, Some(480)), "?sig=1");
+        assert_eq!(master, "#EXTM3U\n#EXT-X-VERSION:3\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=1412000,RESOLUTION=854x480,CODECS=\"avc1.4d4028,mp4a.40.2\"\n480p.m3u8?sig=1\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=877000,RESOLUTION=640x360,CODECS=\"avc1.4d4028,mp4a.40.2\"\n360p.m3u8?sig=1\n");
+
+        let media = media_playlist(&source(14.5, Some(480)), &VARIANTS[2], "");
+        assert_eq!(media, "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n\
+            #EXTINF:6.000,\n480p/0.ts\n#EXTINF:6.000,\n480p/1.ts\n#EXTINF:2.500,\n480p/2.ts\n#EXT-X-ENDLIST\n");
+    }
+
+    #[test]
+    fn test_segment_args() {
+        let args = segment_args(Path::new("/media/a.mkv"), Variant::by_name("720p").unwrap(), 2, Path::new("/cache/2.ts"));
+        let args: Vec<&str> = args.iter().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(&args[..9], &["-nostdin", "-v", "error", "-ss", "12.000", "-i", "/media/a.mkv", "-t", "6.000"]);
+        assert!(args.windows(2).any(|w| w == ["-vf", "scale=-2:720"]));
+        assert!(args.windows(2).any(|w| w == ["-output_ts_offset", "12.000"]));
+        assert_eq!(args.last(), Some(&"/cache/2.ts"));
+    }
+
+    #[test]
+    fn test_segment_cache_eviction() {
+        let dir = tempfile::Builder::new().prefix("test_hls").tempdir().unwrap();
+        let media = dir.path().join("movie.mkv");
+        fs::write(&media, b"movie").unwrap();
+        let cache = SegmentCache::new(dir.path().join("cache"), 25);
+        let variant = &VARIANTS[0];
+        let write = |path: &Path| fs::write(path, [0u8; 10]);
+
+        let first = cache.get_or_create(&media, variant, 0, write).unwrap();
+        let second = cache.get_or_create(&media, variant, 1, write).unwrap();
+        // Hits don't encode again and count as a use
+        let hit = cache.get_or_create(&media, variant, 0, |_| panic!("segment should be cached")).unwrap();
+        assert_eq!(hit, first);
+        File::options().write(true).open(&second).unwrap().set_modified(SystemTime::now() - Duration::from_secs(60)).unwrap();
+
+        // A third segment goes over the cap; the least recently used one is dropped
+        let third = cache.get_or_create(&media, variant, 2, write).unwrap();
+        assert!(first.exists() && third.exists());
+        assert!(!second.exists());
+
+        // Failed encodes leave nothing behind
+        assert!(cache.get_or_create(&media, variant, 3, |_| Err(io::Error::other("boom"))).is_err());
+        assert_eq!(fs::read_dir(first.parent().unwrap()).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::cache::MediaCache;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// OMDB requests per second unless `--omdb-requests-per-second` says otherwise.
+pub const DEFAULT_REQUESTS_PER_SECOND: f64 = 5.0;
+/// Daily calls of a free OMDB key.
+pub const DEFAULT_DAILY_QUOTA: u32 = 1000;
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+/// Retries of a request answered with 429 or 5xx, or timing out.
+const MAX_RETRIES: u32 = 3;
+/// Wait before the first retry; it doubles with every further one.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Longest `Retry-After` that is honoured, so a misbehaving server can't stall the run.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum HttpError {
+    /// The daily quota is used up; no request was sent.
+    QuotaExhausted(u32),
+    Request(reqwest::Error),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::QuotaExhausted(limit) => write!(f, "daily quota of {} calls used", limit),
+            HttpError::Request(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HttpError::QuotaExhausted(_) => None,
+            HttpError::Request(e) => Some(e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for HttpError {
+    fn from(e: reqwest::Error) -> Self {
+        HttpError::Request(e)
+    }
+}
+
+fn today() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64) / (24 * 60 * 60)
+}
+
+/// Calls made per UTC day against a limit. Kept in the cache, so runs on the same day share it.
+pub struct DailyQuota {
+    name: String,
+    limit: u32,
+    day: i64,
+    calls: u32,
+    cache: Option<MediaCache>,
+}
+
+impl DailyQuota {
+    pub fn new(name: &str, limit: u32, cache: Option<MediaCache>) -> Self {
+        let (day, calls) = cache.as_ref()
+            .and_then(|c| c.get_quota(name).map_err(|e| log::error!("Failed to read {} quota from cache: {}", name, e)).ok().flatten())
+            .unwrap_or((today(), 0));
+        DailyQuota { name: name.to_string(), limit, day, calls, cache }
+    }
+
+    /// Counts a call on `day`, unless the limit is reached.
+    fn take_on(&mut self, day: i64) -> bool {
+        if day != self.day {
+            self.day = day;
+            self.calls = 0;
+        }
+        if self.calls >= self.limit {
+            return false;
+        }
+        self.calls += 1;
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.store_quota(&self.name, self.day, self.calls) {
+                log::error!("Failed to store {} quota in cache: {}", self.name, e);
+            }
+        }
+        true
+    }
+
+    fn take(&mut self) -> bool {
+        self.take_on(today())
+    }
+
+    pub fn remaining(&self) -> u32 {
+        if self.day == today() { self.limit.saturating_sub(self.calls) } else { self.limit }
+    }
+}
+
+/// A `reqwest` client shared by all requests to one service, reusing connections, spacing requests out
+/// and retrying overloaded answers with exponential backoff.
+pub struct HttpClient {
+    client: reqwest::blocking::Client,
+    min_interval: Duration,
+    next_slot: Mutex<Instant>,
+    quota: Option<Mutex<DailyQuota>>,
+}
+
+impl HttpClient {
+    /// `requests_per_second` of `None` doesn't throttle; `quota` of `None` doesn't count calls.
+    pub fn new(requests_per_second: Option<f64>, quota: Option<DailyQuota>) -> Self {
+        HttpClient {
+            client: reqwest::blocking::Client::builder().timeout(TIMEOUT).build().expect("Failed to build HTTP client"),
+            min_interval: requests_per_second.filter(|r| *r > 0.0).map_or(Duration::ZERO, |r| Duration::from_secs_f64(1.0 / r)),
+            next_slot: Mutex::new(Instant::now()),
+            quota: quota.map(Mutex::new),
+        }
+    }
+
+    /// Waits for the next free request slot.
+    fn throttle(&self) {
+        let wait = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let now = Instant::now();
+            let slot = (*next_slot).max(now);
+            *next_slot = slot + self.min_interval;
+            slot - now
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+
+    fn take_quota(&self) -> Result<(), HttpError> {
+        match &self.quota {
+            Some(quota) => {
+                let mut quota = quota.lock().unwrap();
+                if quota.take() { Ok(()) } else { Err(HttpError::QuotaExhausted(quota.limit)) }
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Calls left today, if a quota is kept.
+    pub fn remaining_quota(&self) -> Option<u32> {
+        self.quota.as_ref().map(|q| q.lock().unwrap().remaining())
+    }
+
+    /// GET with query parameters. Answers other than 429 and 5xx are returned as they are, whatever their status.
+    /// Retries count as one call against the quota.
+    pub fn get(&self, url: &str, query: &[(&str, String)]) -> Result<reqwest::blocking::Response, HttpError> {
+        self.take_quota()?;
+        let mut attempt = 0;
+        loop {
+            self.throttle();
+            let result = self.client.get(url).query(query).send();
+            let retry_after = match &result {
+                Ok(resp) if resp.status().as_u16() == 429 || resp.status().is_server_error() => Some(
+                    resp.headers().get(reqwest::header::RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok()).and_then(|v| v.parse().ok()).map(Duration::from_secs)
+                ),
+                Err(e) if e.is_timeout() => Some(None),
+                _ => None,
+            };
+            match retry_after {
+                Some(retry_after) if attempt < MAX_RETRIES => {
+                    let backoff = retry_after.unwrap_or(INITIAL_BACKOFF * 2u32.pow(attempt)).min(MAX_BACKOFF);
+                    log::info!(target: "cli", "Retrying {} in {:?}: {}", url, backoff,
+                        result.map_or_else(|e| e.to_string(), |resp| resp.status().to_string()));
+                    thread::sleep(backoff);
+                    attempt += 1;
+                }
+                _ => return Ok(result?),
+            }
+        }
+    }
+}
+
+static OMDB_CLIENT: OnceLock<HttpClient> = OnceLock::new();
+static DOWNLOAD_CLIENT: OnceLock<HttpClient> = OnceLock::new();
+
+/// Sets up the OMDB client; without this, it's throttled to the default rate and keeps no quota.
+pub fn init_omdb(client: HttpClient) {
+    if OMDB_CLIENT.set(client).is_err() {
+        log::warn!("OMDB client was already set up");
+    }
+}
+
+/// The client of all OMDB requests.
+pub fn omdb() -> &'static HttpClient {
+    OMDB_CLIENT.get_or_init(|| HttpClient::new(Some(DEFAULT_REQUESTS_PER_SECOND), None))
+}
+
+/// The client of poster downloads, which aren't throttled or counted.
+pub fn downloads() -> &'static HttpClient {
+    DOWNLOAD_CLIENT.get_or_init(|| HttpClient::new(None, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_daily_quota() {
+        let mut quota = DailyQuota::new("test", 2, None);
+        let day = quota.day;
+        assert!(quota.take_on(day));
+        assert!(quota.take_on(day));
+        assert!(!quota.take_on(day));
+        assert_eq!(quota.calls, 2);
+        // A new day starts over
+        assert!(quota.take_on(day + 1));
+        assert_eq!(quota.calls, 1);
+    }
+
+    #[test]
+    fn test_throttle() {
+        let client = HttpClient::new(Some(20.0), None);
+        let start = Instant::now();
+        for _ in 0..3 {
+            client.throttle();
+        }
+        // The first request goes right away, the next two 50ms apart
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(HttpClient::new(None, None).min_interval, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_retry_counts_once() {
+        // Answers 503 once, then 200
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for (i, stream) in listener.incoming().flatten().enumerate() {
+                let mut line = String::new();
+                let mut reader = BufReader::new(&stream);
+                while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                    line.clear();
+                }
+                let status = if i == 0 { "503 Service Unavailable" } else { "200 OK" };
+                let mut stream = &stream;
+                write!(stream, "HTTP/1.1 {}\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+            }
+        });
+        let client = HttpClient::new(None, Some(DailyQuota::new("test", 2, None)));
+        assert_eq!(client.get(&url, &[]).unwrap().status().as_u16(), 200);
+        assert_eq!(client.remaining_quota(), Some(1));
+    }
+}
@@ -1,11 +1,7 @@
 use std::{path::Path, hash::{Hash, Hasher}};
-use clap;
 use movie::MovieInfo;
 use serde::Serialize;
-use tera;
-use walkdir;
 use url::Url;
-use log;
 #[macro_use]
 extern crate lazy_static;
 
@@ -14,6 +10,22 @@ mod media;
 mod tv; // Add tv module
 mod cache; // Add cache module
 mod search; // Add search module
+mod webserver;
+mod auth;
+mod tls;
+mod transcode;
+mod hls;
+mod probe;
+mod subtitles;
+mod scan;
+mod nfo;
+mod artwork;
+mod matching;
+mod overrides;
+mod http;
+mod provider;
+mod omdb;
+mod tmdb;
 
 use media::MediaInfoEquiv;
 use tv::{TvSeriesMediaInfo, TvSeriesInfo};
@@ -25,15 +37,7 @@ const DEFAULT_CSS_FILE: &str = include_str!("media.css");
 const DEFAULT_INDEX_HTML_TEMPLATE: &str = include_str!("index.html");
 const DEFAULT_JS_FILE: &str = include_str!("./../static/media.js");
 const DEFAULT_SEARCH_JS_FILE: &str = include_str!("./../static/search.js");
-
-fn scan_folders(basepath: &Path) -> Vec<std::path::PathBuf> {
-    walkdir::WalkDir::new(basepath)
-    .max_depth(2).into_iter()
-    .filter_map(|entry| entry.ok().map(|e| e.path().to_path_buf()))
-    .filter(|path| path.is_file())
-    .filter(|path| path.extension().filter(|ext| *ext == "mp4").is_some())
-    .collect()
-}
+const DEFAULT_NO_POSTER_FILE: &str = include_str!("./../static/no-poster.svg");
 
 fn split_2_or(s: &str, default_second: Option<&str>) -> (String, String) {
     let mut split = s.split(":");
@@ -44,7 +48,7 @@ fn split_2_or(s: &str, default_second: Option<&str>) -> (String, String) {
 
 fn gen_media_ref(base_url: &Option<Url>, folder_path: &Path, folder_mount: &str, media_path: &Path) -> String {
     let relative_path = media_path.strip_prefix(folder_path).unwrap();
-    let path_components = relative_path.components().into_iter().map(|c| c.as_os_str().to_str().unwrap());
+    let path_components = relative_path.components().map(|c| c.as_os_str().to_str().unwrap());
     
     match base_url {
         Some(url) => {
@@ -56,13 +60,58 @@ fn gen_media_ref(base_url: &Option<Url>, folder_path: &Path, folder_mount: &str,
 }
 
 
-fn render_factory<'a, T>(template: &'a tera::Tera, output_path: &'a Path, base_url: &'a Option<Url>, folder: &'a Path, mount: &'a str) -> Box<dyn Fn(T) -> () + 'a>
+/// Decides which link the Cast button loads for a file.
+struct CastLinks {
+    ffmpeg: transcode::Ffmpeg,
+    /// Send files that need transcoding to the HLS endpoint instead of the single-stream one.
+    hls: bool,
+}
+
+impl CastLinks {
+    /// The file itself when the Cast device can play it, otherwise one of the webserver's ffmpeg endpoints.
+    /// Without probe data the file extension decides.
+    fn cast_target(&self, probe: Option<&probe::MediaProbe>, base_url: &Option<Url>, folder_path: &Path, folder_mount: &str, media_path: &Path) -> transcode::CastTarget {
+        let castability = transcode::castability(media_path, probe);
+        let (url, content_type) = match castability {
+            transcode::Castability::Transcode if self.hls => {
+                (gen_hls_ref(base_url, folder_path, folder_mount, media_path), "application/x-mpegurl".to_string())
+            }
+            transcode::Castability::Transcode => {
+                let transcode_mount = format!("{}/{}", transcode::TRANSCODE_MOUNT, folder_mount);
+                (gen_media_ref(base_url, folder_path, &transcode_mount, media_path), "video/mp4".to_string())
+            }
+            transcode::Castability::Direct | transcode::Castability::Unplayable => {
+                (gen_media_ref(base_url, folder_path, folder_mount, media_path), transcode::media_mime(media_path))
+            }
+        };
+        transcode::CastTarget { url, content_type, castability }
+    }
+}
+
+/// Link to the HLS master playlist of a media file, see `hls::media_id`.
+fn gen_hls_ref(base_url: &Option<Url>, folder_path: &Path, folder_mount: &str, media_path: &Path) -> String {
+    let relative_path = media_path.strip_prefix(folder_path).unwrap();
+    let media_url_path = relative_path.components()
+        .fold(format!("/{}", folder_mount), |s, comp| [s, "/".to_string(), comp.as_os_str().to_string_lossy().to_string()].concat());
+    let playlist = format!("{}/{}/master.m3u8", hls::HLS_MOUNT, hls::media_id(&media_url_path));
+    match base_url {
+        Some(url) => url.join(&playlist).unwrap().to_string(),
+        None => playlist,
+    }
+}
+
+fn render_factory<'a, T>(template: &'a tera::Tera, output_path: &'a Path, base_url: &'a Option<Url>, folder: &'a Path, mount: &'a str, cast_links: &'a CastLinks, cache: &'a Option<cache::MediaCache>) -> Box<dyn Fn(T) + 'a>
     where T: MediaInfoEquiv + Serialize + std::fmt::Debug {
     Box::new(move |media_info: T| {
         let media_path = media_info.path();
-        let media_ref = gen_media_ref(&base_url, folder, &mount, media_path);
+        let media_ref = gen_media_ref(base_url, folder, mount, media_path);
+        let probe = probe::probe_cached(&cast_links.ffmpeg, cache, media_path);
+        let cast = cast_links.cast_target(probe.as_ref(), base_url, folder, mount, media_path);
         let mut ctx = tera::Context::new();
+        ctx.insert("probe", &probe);
+        ctx.insert("subtitles", &subtitles::export_text_tracks(&cast_links.ffmpeg, media_path, probe.as_ref(), output_path, base_url));
         ctx.insert("media_ref", &media_ref);
+        ctx.insert("cast", &cast);
         ctx.insert("media_info", &media_info);
 
         let t = template.render("movie.html", &ctx).unwrap();
@@ -78,13 +127,31 @@ fn logger<T>(movie_info: T)
     println!("Media: {:?}", movie_info);
  }
 
+/// Drops a failed lookup, unless it's a provider error every other lookup would hit too: that stops the run.
+fn unless_fatal<T>(result: Result<T, Box<dyn std::error::Error>>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(err) => {
+            if provider::is_fatal(&*err) {
+                log::error!(target: "cli", "Stopping: {}", err);
+                std::process::exit(1);
+            }
+            None
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct MovieIndexInfo {
     name: String,
-    year: u16,
-    director: String,
+    year: Option<u16>,
+    director: Option<String>,
     poster_url: String,
+    /// Resized poster variants, when the poster was exported
+    poster_srcset: Option<String>,
     page_url: String,
+    /// Shown from its file name only, see `MovieInfo::unmatched`
+    unmatched: bool,
 }
 
 #[derive(Serialize)]
@@ -94,6 +161,16 @@ struct TvSeriesIndexInfo {
     episodes_count: usize,
     page_url: String,
     poster_url: String,
+    poster_srcset: Option<String>,
+}
+
+/// A series with aired episodes missing on disk, for `missing.html`.
+#[derive(Serialize)]
+struct MissingReportEntry {
+    name: String,
+    page_url: String,
+    /// Seasons with missing episodes.
+    seasons: Vec<tv::SeasonCoverage>,
 }
 
 fn main() {
@@ -114,8 +191,23 @@ fn main() {
             .arg(clap::Arg::new("tv-folder").long("tv-folder").action(clap::ArgAction::Append))
             .arg(clap::Arg::new("output-folder").long("output-folder").default_value("./pub"))
             .arg(clap::Arg::new("base-url").long("base-url"))
-            .arg(clap::Arg::new("omdb-api-key").long("omdb-api-key").required(true))
+            .arg(clap::Arg::new("omdb-api-key").long("omdb-api-key").help("OMDB API key; without one or a TMDB key, metadata only comes from NFO files and the cache"))
+            .arg(clap::Arg::new("tmdb-api-key").long("tmdb-api-key").help("TMDB API key (v3), for backdrops, episode stills, collections and localized titles"))
+            .arg(clap::Arg::new("tmdb-language").long("tmdb-language").help("Language of TMDB titles and plots, e.g. de-DE (default: TMDB's, mostly English)"))
+            .arg(clap::Arg::new("metadata-providers").long("metadata-providers").value_delimiter(',').value_parser(["omdb", "tmdb"]).default_value("omdb,tmdb").help("Comma-separated metadata providers in priority order; later ones fill what earlier ones lack, and ones without an API key are skipped"))
             .arg(clap::Arg::new("cache-path").long("cache-path").default_value("./nascast_cache.sqlite"))
+            .arg(clap::Arg::new("image-cache-folder").long("image-cache-folder").default_value("./nascast_image_cache").help("Folder for downloaded posters, so re-runs don't fetch them again"))
+            .arg(clap::Arg::new("ffprobe").long("ffprobe").default_value("ffprobe").help("ffprobe binary used to find files that need transcoding for Cast"))
+            .arg(clap::Arg::new("ffmpeg").long("ffmpeg").default_value("ffmpeg").help("ffmpeg binary used to extract embedded subtitles"))
+            .arg(clap::Arg::new("video-extensions").long("video-extensions").help("Comma-separated extensions of files indexed as movies and episodes (default: mkv,mp4,m4v,avi,mov,wmv,flv,webm)"))
+            .arg(clap::Arg::new("scan-depth").long("scan-depth").default_value("3").help("How many folder levels below a movies/TV folder videos are looked for"))
+            .arg(clap::Arg::new("overrides").long("overrides").help("TOML or YAML file of per-item corrections (IMDb id, title, plot, poster, genre, hidden, not_series) keyed by path"))
+            .arg(clap::Arg::new("omdb-match").long("omdb-match").value_parser(["title", "search"]).default_value("title").help("Match names without an IMDb id with OMDB's exact title lookup, or by scoring its search results"))
+            .arg(clap::Arg::new("match-threshold").long("match-threshold").help("Confidence (0-1) a search result needs with --omdb-match search (default: 0.75)"))
+            .arg(clap::Arg::new("omdb-requests-per-second").long("omdb-requests-per-second").help("Most OMDB requests sent per second (default: 5)"))
+            .arg(clap::Arg::new("omdb-daily-quota").long("omdb-daily-quota").help("OMDB calls allowed per UTC day, counted across runs in the cache; 0 for no limit (default: 1000)"))
+            .arg(clap::Arg::new("omdb-episode-details").long("omdb-episode-details").help("Look every episode up on its own for its plot and director, instead of only its season's listing").action(clap::ArgAction::SetTrue))
+            .arg(clap::Arg::new("hls").long("hls").help("Cast files that need transcoding as adaptive HLS instead of a single ffmpeg stream").action(clap::ArgAction::SetTrue))
             .arg(clap::Arg::new("noop").long("noop").help("NoOp mode: only show metadata, does not write anything to disk").action(clap::ArgAction::SetTrue))
             .arg(clap::Arg::new("verbosity").long("verbosity").short('v').action(clap::ArgAction::Set))
     )
@@ -127,6 +219,18 @@ fn main() {
             .arg(clap::Arg::new("html-folder").long("html-folder").default_value("./pub"))
             .arg(clap::Arg::new("base-url").long("base-url"))
             .arg(clap::Arg::new("port").long("port").default_value("8000").help("Port to run the web server on"))
+            .arg(clap::Arg::new("ffmpeg").long("ffmpeg").default_value("ffmpeg").help("ffmpeg binary used to remux/transcode files Cast devices can't play"))
+            .arg(clap::Arg::new("ffprobe").long("ffprobe").default_value("ffprobe").help("ffprobe binary used to detect codecs"))
+            .arg(clap::Arg::new("hls-cache-folder").long("hls-cache-folder").default_value("./nascast_hls_cache").help("Folder for encoded HLS segments"))
+            .arg(clap::Arg::new("hls-cache-size").long("hls-cache-size").default_value("2048").help("Size cap of the HLS segment cache, in MiB"))
+            .arg(clap::Arg::new("symlinks").long("symlinks").value_parser(["never", "within-root", "follow"]).default_value("within-root").help("Whether symlinks inside the served folders are followed"))
+            .arg(clap::Arg::new("auth-user").long("auth-user").help("Require HTTP Basic auth with these credentials (user:password)"))
+            .arg(clap::Arg::new("auth-token").long("auth-token").help("Require this token as a Bearer header, ?token= parameter or cookie"))
+            .arg(clap::Arg::new("url-secret").long("url-secret").help("Secret for signing media URLs handed to Cast devices (random per run if not set)"))
+            .arg(clap::Arg::new("signed-url-ttl").long("signed-url-ttl").default_value("21600").help("Lifetime of signed media URLs, in seconds"))
+            .arg(clap::Arg::new("tls-cert").long("tls-cert").requires("tls-key").help("Serve HTTPS with this PEM certificate (chain); a self-signed one is generated if neither file exists"))
+            .arg(clap::Arg::new("tls-key").long("tls-key").requires("tls-cert").help("PEM private key for --tls-cert"))
+            .arg(clap::Arg::new("bind").long("bind").action(clap::ArgAction::Append).help("Address to listen on (IPv4/IPv6, optionally with :port); may be repeated. Use 0.0.0.0 or :: to serve the LAN"))
     )
     .get_matches();
     
@@ -134,14 +238,53 @@ fn main() {
         Some(("webserver", webserver_matches)) => {
             let html_dir = webserver_matches.get_one::<String>("html-folder").expect("HTML folder required").to_string();
             let port = webserver_matches.get_one::<String>("port").expect("Port required").parse::<u16>().expect("Port must be a number");
+            let bind_addresses = webserver_matches.get_many::<String>("bind")
+                .map(|values| values.map(|s| s.as_str()).collect::<Vec<_>>())
+                .unwrap_or_else(|| vec![webserver::DEFAULT_BIND_ADDRESS])
+                .into_iter()
+                .map(|s| webserver::parse_bind_address(s, port))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
             
             // If any folders are specified, first generate the content
             let movies_folders = webserver_matches.get_many::<String>("movies-folder").unwrap().map(|s| split_2_or(s, None)).collect();
             let tv_folders = webserver_matches.get_many::<String>("tv-folder").unwrap().map(|s| split_2_or(s, None)).collect();
             let base_url = webserver_matches.get_one::<String>("base-url").and_then(|s| url::Url::parse(s).ok());
-                        
+            let symlink_policy = webserver_matches.get_one::<String>("symlinks").expect("Symlink policy required").parse().unwrap();
+            let auth_basic = webserver_matches.get_one::<String>("auth-user")
+                .map(|s| s.split_once(':').map(|(user, password)| (user.to_string(), password.to_string())).unwrap_or((s.to_string(), String::new())));
+            let auth_token = webserver_matches.get_one::<String>("auth-token").cloned();
+            let url_secret = webserver_matches.get_one::<String>("url-secret").cloned();
+            let signed_url_ttl = webserver_matches.get_one::<String>("signed-url-ttl").expect("Signed URL TTL required").parse::<u64>().expect("Signed URL TTL must be a number");
+            let auth = auth::AuthConfig::new(auth_basic, auth_token, url_secret, std::time::Duration::from_secs(signed_url_ttl));
+            let ffmpeg = transcode::Ffmpeg {
+                ffmpeg: webserver_matches.get_one::<String>("ffmpeg").expect("ffmpeg path required").into(),
+                ffprobe: webserver_matches.get_one::<String>("ffprobe").expect("ffprobe path required").into(),
+            };
+            let hls_cache_folder = webserver_matches.get_one::<String>("hls-cache-folder").expect("HLS cache folder required");
+            let hls_cache_size = webserver_matches.get_one::<String>("hls-cache-size").expect("HLS cache size required").parse::<u64>().expect("HLS cache size must be a number");
+            let hls_cache = hls::SegmentCache::new(hls_cache_folder.into(), hls_cache_size * 1024 * 1024);
+            let config = webserver::WebServerConfig::new(html_dir, tv_folders, movies_folders, base_url, symlink_policy)
+                .with_auth(auth)
+                .with_ffmpeg(ffmpeg)
+                .with_hls_cache(hls_cache);
+
+            let tls = match (webserver_matches.get_one::<String>("tls-cert"), webserver_matches.get_one::<String>("tls-key")) {
+                (Some(cert), Some(key)) => match tls::load_or_create_identity(Path::new(cert), Path::new(key), webserver::certificate_names(&bind_addresses)) {
+                    Ok(identity) => Some(identity),
+                    Err(e) => {
+                        log::error!(target: "cli", "Failed to load TLS certificate: {}", e);
+                        std::process::exit(1);
+                    }
+                },
+                _ => None,
+            };
+
             // Start the web server
-            if let Err(e) = start_webserver(html_dir, tv_folders, movies_folders, port, base_url) {
+            if let Err(e) = webserver::start_webserver(config, bind_addresses, tls) {
                 log::error!(target: "cli", "Failed to start web server: {}", e);
             }
         },
@@ -168,13 +311,44 @@ fn generate_content(app: &clap::ArgMatches) {
     template.add_raw_template("movies.html", include_str!("movies.html")).unwrap();
     template.add_raw_template("tv.html", include_str!("tv.html")).unwrap();
     template.add_raw_template("series.html", include_str!("series.html")).unwrap();
+    template.add_raw_template("missing.html", include_str!("missing.html")).unwrap();
     let output_dir = app.get_one::<String>("output-folder").expect("Output folder required");
     let base_url = app.get_one::<String>("base-url").and_then(|s| url::Url::parse(s).ok());
     let output_path = Path::new(&output_dir);
-    let omdb_api_key = app.get_one::<String>("omdb-api-key").expect("OMDB API Key required");
+    let omdb_api_key = app.get_one::<String>("omdb-api-key").map(String::as_str);
+    let tmdb_api_key = app.get_one::<String>("tmdb-api-key").map(String::as_str);
+    let scanner = scan::VideoScanner::new(
+        app.get_one::<String>("video-extensions").map(|e| e.split(',').map(String::from).collect())
+            .unwrap_or_else(|| scan::DEFAULT_VIDEO_EXTENSIONS.iter().map(|e| e.to_string()).collect()),
+        app.get_one::<String>("scan-depth").expect("Scan depth required").parse().expect("Scan depth must be a number"),
+    );
+    let matching = match app.get_one::<String>("omdb-match").map(String::as_str) {
+        Some("search") => matching::MatchMode::Search {
+            threshold: app.get_one::<String>("match-threshold").map(|t| t.parse().expect("Match threshold must be a number"))
+                .unwrap_or(matching::DEFAULT_MATCH_THRESHOLD),
+        },
+        _ => matching::MatchMode::Exact,
+    };
+    let overrides = match app.get_one::<String>("overrides") {
+        Some(path) => overrides::Overrides::load(Path::new(path)).unwrap_or_else(|e| {
+            log::error!(target: "cli", "Failed to read overrides file {}: {}", path, e);
+            std::process::exit(1);
+        }),
+        None => overrides::Overrides::default(),
+    };
     let noop = app.get_flag("noop");
+    let episode_details = app.get_flag("omdb-episode-details");
+    let cast_links = CastLinks {
+        ffmpeg: transcode::Ffmpeg {
+            ffmpeg: app.get_one::<String>("ffmpeg").expect("ffmpeg path required").into(),
+            ffprobe: app.get_one::<String>("ffprobe").expect("ffprobe path required").into(),
+        },
+        hls: app.get_flag("hls"),
+    };
     
     // Initialize the SQLite cache
+    let image_cache_folder = app.get_one::<String>("image-cache-folder").expect("Image cache folder required");
+    let artwork_exporter = artwork::ArtworkExporter::new(Path::new(image_cache_folder), output_path, &base_url);
     let cache_path_str = app.get_one::<String>("cache-path").expect("Cache path required");
     let cache_path = Path::new(cache_path_str); // Use the string slice directly
     let cache = match crate::cache::MediaCache::new(cache_path) {
@@ -187,6 +361,34 @@ fn generate_content(app: &clap::ArgMatches) {
             None
         }
     };
+    if omdb_api_key.is_some() {
+        let requests_per_second = app.get_one::<String>("omdb-requests-per-second")
+            .map_or(http::DEFAULT_REQUESTS_PER_SECOND, |r| r.parse().expect("OMDB requests per second must be a number"));
+        let daily_quota = app.get_one::<String>("omdb-daily-quota")
+            .map_or(http::DEFAULT_DAILY_QUOTA, |q| q.parse().expect("OMDB daily quota must be a number"));
+        let quota = (daily_quota > 0).then(|| http::DailyQuota::new("omdb", daily_quota, cache.clone()));
+        http::init_omdb(http::HttpClient::new(Some(requests_per_second), quota));
+        if let Some(remaining) = http::omdb().remaining_quota() {
+            log::info!(target: "cli", "{} of {} OMDB calls left today", remaining, daily_quota);
+        }
+    }
+    let providers = app.get_many::<String>("metadata-providers").expect("Metadata providers required")
+        .filter_map(|name| -> Option<Box<dyn provider::MetadataProvider>> {
+            match name.as_str() {
+                "omdb" => omdb_api_key.map(|key| Box::new(omdb::OmdbProvider::new(key, matching, cache.clone())) as _),
+                "tmdb" => tmdb_api_key.map(|key| Box::new(tmdb::TmdbProvider::new(key,
+                    app.get_one::<String>("tmdb-language").map(String::as_str), tmdb::TMDB_API_URL)) as _),
+                _ => unreachable!("Metadata providers are checked by clap"),
+            }
+        })
+        .collect();
+    let provider_chain = provider::ProviderChain::new(providers);
+    let provider = (!provider_chain.is_empty()).then_some(&provider_chain as &dyn provider::MetadataProvider);
+    if provider.is_none() {
+        log::info!(target: "cli", "No metadata provider API key given, building offline from NFO files, the cache and file names");
+    } else {
+        log::info!(target: "cli", "Metadata providers: {}", provider_chain.names().join(", "));
+    }
     
     std::fs::create_dir_all(output_path).unwrap();
     
@@ -194,20 +396,36 @@ fn generate_content(app: &clap::ArgMatches) {
     let mut all_movie_infos = Vec::new(); // For search index generation
     
     for folder_spec in app.get_many::<String>("movies-folder").unwrap_or_default() {
-        let (s_folder, mount) = split_2_or(&folder_spec, None);
+        let (s_folder, mount) = split_2_or(folder_spec, None);
         let folder = Path::new(&s_folder);
         let render = if noop {
             Box::new(logger)
         } else {
-            render_factory(&template, output_path, &base_url, folder, &mount)
+            render_factory(&template, output_path, &base_url, folder, &mount, &cast_links, &cache)
         };
 
-        let media_infos = scan_folders(folder).iter()
+        let media_infos = scanner.scan_videos(folder).iter()
+            .filter(|file| {
+                let excluded = overrides.excludes(folder, file);
+                if excluded {
+                    log::info!(target: "cli", "Skipped {:?}: hidden by overrides", file);
+                }
+                !excluded
+            })
             .filter_map(|file| movie::parse_movie_filename(&movie::MOVIE_PATTERNS_RE, file))
-            .filter_map(|info| movie::get_movie_info_logged(omdb_api_key, info, &cache).ok() ) // Pass cache
+            .filter_map(|info| {
+                let item_override = overrides.get(folder, &info.path);
+                unless_fatal(movie::get_movie_info_logged(provider, info, item_override, &cache)) // Pass cache
+            })
             .collect::<Vec<MovieInfo>>();
 
-        for movie_info in media_infos {
+        for mut movie_info in media_infos {
+            if !noop {
+                movie_info.artwork = artwork_exporter.movie_artwork(&movie_info.path, folder, movie_info.poster_url.as_ref().map(url::Url::as_str));
+                if let Some(poster) = overrides.get(folder, &movie_info.path).and_then(|o| o.poster.as_deref()) {
+                    movie_info.artwork.poster = artwork_exporter.poster(poster).or(movie_info.artwork.poster.take());
+                }
+            }
             let mut hasher = std::collections::hash_map::DefaultHasher::new();
             movie_info.path().to_str().hash(&mut hasher);
             let page_name = hasher.finish().to_string() + ".html";
@@ -216,8 +434,11 @@ fn generate_content(app: &clap::ArgMatches) {
                 name: movie_info.name.clone(),
                 year: movie_info.year,
                 director: movie_info.director.clone(),
-                poster_url: movie_info.poster_url.to_string(),
+                poster_url: movie_info.artwork.poster.as_ref().map(|p| p.thumb_url.clone())
+                    .or(movie_info.poster_url.as_ref().map(url::Url::to_string)).unwrap_or(artwork::NO_POSTER_FILE.to_string()),
+                poster_srcset: movie_info.artwork.poster.as_ref().map(|p| p.srcset.clone()),
                 page_url: page_name,
+                unmatched: movie_info.unmatched,
             });
 
             // Store for search index
@@ -228,48 +449,98 @@ fn generate_content(app: &clap::ArgMatches) {
             }
         }
     }
+    let unmatched_count = all_movies.iter().filter(|movie| movie.unmatched).count();
+    if unmatched_count > 0 {
+        log::warn!(target: "cli", "{} of {} movies have no metadata match and are shown by file name", unmatched_count, all_movies.len());
+    }
 
     // Changed from HashMap to Vec<(TvSeriesMediaInfo, Option<TvSeriesInfo>)>
     let mut all_tv_series: Vec<(TvSeriesMediaInfo, Option<TvSeriesInfo>)> = Vec::new(); 
 
     for folder_spec in app.get_many::<String>("tv-folder").unwrap_or_default() {
-        let (s_folder, mount) = split_2_or(&folder_spec, None);
+        let (s_folder, mount) = split_2_or(folder_spec, None);
         let folder = Path::new(&s_folder);
         log::info!(target: "cli", "Scanning TV folder: {:?}", folder);
 
-        match tv::scan_tv_directory(folder) {
+        match tv::scan_tv_directory(folder, &scanner) {
             Ok(series_list) => {
                 log::info!(target: "cli", "Found {} series in TV folder: {:?}" , series_list.len(), folder);
                 for mut series_data in series_list {
+                    if overrides.excludes(folder, &series_data.path) {
+                        log::info!(target: "cli", "  Skipped {:?}: hidden or not a series by overrides", series_data.path);
+                        continue;
+                    }
+                    series_data.episodes.retain(|episode| {
+                        let excluded = overrides.excludes(folder, &episode.path);
+                        if excluded {
+                            log::info!(target: "cli", "  Skipped {:?}: hidden by overrides", episode.path);
+                        }
+                        !excluded
+                    });
                     log::info!(target: "cli", "  Found Series: '{}', Year: {:?}, Path: {:?}, Episodes: {}", 
                                series_data.name, series_data.year, series_data.path, series_data.episodes.len());
+                    let series_override = overrides.get(folder, &series_data.path);
+                    let series_name = series_data.name.clone();
                     // Get OMDB data for the series ONCE and store Option<TvSeriesInfo>
-                    let series_info = tv::get_series_info(omdb_api_key, &series_data.name, &cache).ok(); // Pass cache
+                    let series_nfo = nfo::find_series_nfo(&series_data.path);
+                    let mut series_imdb_id = series_override.and_then(|o| o.imdb_id.clone())
+                        .or_else(|| series_nfo.as_ref().and_then(|n| n.imdb_id.clone()))
+                        .or(series_data.imdb_id.clone());
+                    let mut series_info = unless_fatal(tv::get_series_info(provider, &series_name, series_nfo.as_ref(), series_imdb_id.as_deref(), &cache)); // Pass cache
+                    if series_imdb_id.is_none() {
+                        // Episodes are looked up by the matched series, which a search may have titled differently than the folder
+                        series_imdb_id = series_info.as_ref().and_then(|info| media::imdb_id_from_url(&info.info_url));
+                    }
                     if let Some(ref info) = series_info {
                         // Update series metadata with OMDB data
-                        series_data.poster_url = Some(info.poster_url.to_string());
-                        series_data.released = Some(info.released.clone());
-                        series_data.genre = Some(info.genre.clone());
-                        series_data.plot = Some(info.plot.clone());
-                        series_data.actors = Some(info.actors.clone());
-                        series_data.language = Some(info.language.clone());
-                        series_data.country = Some(info.country.clone());
-                        series_data.imdb_rating = Some(info.imdb_rating.clone());
-                        series_data.total_seasons = Some(info.total_seasons.clone());
+                        series_data.poster_url = info.poster_url.as_ref().map(url::Url::to_string);
+                        series_data.released = info.released.clone();
+                        series_data.genre = info.genre.clone();
+                        series_data.plot = info.plot.clone();
+                        series_data.actors = info.actors.clone();
+                        series_data.language = info.language.clone();
+                        series_data.country = info.country.clone();
+                        series_data.imdb_rating = info.imdb_rating.clone();
+                        series_data.total_seasons = info.total_seasons.clone();
                         series_data.year = info.year;
                     }
+                    if let Some(series_override) = series_override {
+                        series_data.apply_override(series_info.as_mut(), series_override);
+                    }
+                    if !noop {
+                        let seasons: std::collections::BTreeSet<u8> = series_data.episodes.iter().map(|ep| ep.season).collect();
+                        series_data.artwork = artwork_exporter.series_artwork(&series_data.path, seasons, series_data.poster_url.as_deref());
+                        if let Some(poster) = series_override.and_then(|o| o.poster.as_deref()) {
+                            series_data.artwork.poster = artwork_exporter.poster(poster).or(series_data.artwork.poster.take());
+                        }
+                    }
                     // For each episode, get detailed info and set media_ref
                     for episode in series_data.episodes.iter_mut() {
-                        if let Ok(ep_info) = tv::get_episode_info(omdb_api_key, &series_data.name, episode.season, episode.episode, &cache) { // Pass cache
+                        let episode_override = overrides.get(folder, &episode.path);
+                        let episode_imdb_id = episode_override.and_then(|o| o.imdb_id.as_deref());
+                        if let Some(ep_info) = unless_fatal(tv::get_episode_info(provider, &series_name, series_imdb_id.as_deref(), &episode.path, episode.season, episode.episode, episode_imdb_id, episode_details, &cache)) { // Pass cache
                             episode.title = Some(ep_info.title);
                             episode.plot = ep_info.plot;
                             episode.imdb_rating = ep_info.imdb_rating;
                             episode.air_date = ep_info.aired_date;
                             episode.director = ep_info.director;
+                            episode.still_url = ep_info.still_url;
+                        }
+                        if let Some(episode_override) = episode_override {
+                            episode.apply_override(episode_override);
                         }
                         // Set media_ref for episode
                         let generated_ref = gen_media_ref(&base_url, folder, &mount, &episode.path);
                         episode.media_ref = Some(generated_ref);
+                        episode.probe = probe::probe_cached(&cast_links.ffmpeg, &cache, &episode.path);
+                        if !noop {
+                            episode.subtitles = subtitles::export_text_tracks(&cast_links.ffmpeg, &episode.path, episode.probe.as_ref(), output_path, &base_url);
+                        }
+                        episode.cast = Some(cast_links.cast_target(episode.probe.as_ref(), &base_url, folder, &mount, &episode.path));
+                    }
+                    if series_info.is_some() {
+                        // Unmatched series are left out, as a listing by their name may be another series'
+                        series_data.coverage = unless_fatal(tv::get_series_coverage(provider, &series_name, series_imdb_id.as_deref(), series_data.total_seasons.as_deref(), &series_data.episodes, &cache)).unwrap_or_default();
                     }
                     all_tv_series.push((series_data, series_info));
                 }
@@ -291,7 +562,9 @@ fn generate_content(app: &clap::ArgMatches) {
                 name: series.name.clone(),
                 year: series.year,
                 episodes_count: series.episodes.len(),
-                poster_url: series.poster_url.as_deref().unwrap_or("https://via.placeholder.com/300x450.png?text=No+Poster").to_string(),
+                poster_url: series.artwork.poster.as_ref().map(|p| p.thumb_url.as_str())
+                    .or(series.poster_url.as_deref()).unwrap_or(artwork::NO_POSTER_FILE).to_string(),
+                poster_srcset: series.artwork.poster.as_ref().map(|p| p.srcset.clone()),
                 page_url: page_name,
             }
         }).collect();
@@ -309,9 +582,29 @@ fn generate_content(app: &clap::ArgMatches) {
         let movies_html = template.render("movies.html", &movies_ctx).unwrap();
         std::fs::write(output_path.join("movies.html"), movies_html).unwrap();
         
+        // Generate the report of aired episodes missing on disk
+        let missing_report: Vec<MissingReportEntry> = all_tv_series.iter().zip(&tv_series_index)
+            .filter(|((series, _), _)| series.coverage.iter().any(|c| !c.missing.is_empty()))
+            .map(|((series, _), index_info)| MissingReportEntry {
+                name: series.name.clone(),
+                page_url: index_info.page_url.clone(),
+                seasons: series.coverage.iter().filter(|c| !c.missing.is_empty()).cloned().collect(),
+            })
+            .collect();
+        let missing_count: usize = missing_report.iter().flat_map(|entry| &entry.seasons).map(|c| c.missing.len()).sum();
+        if missing_count > 0 {
+            log::info!(target: "cli", "{} aired episodes of {} series are missing on disk, see missing.html", missing_count, missing_report.len());
+        }
+        let mut missing_ctx = tera::Context::new();
+        missing_ctx.insert("series", &missing_report);
+        missing_ctx.insert("missing_count", &missing_count);
+        let missing_html = template.render("missing.html", &missing_ctx).unwrap();
+        std::fs::write(output_path.join("missing.html"), missing_html).unwrap();
+
         // Generate TV series listing page
         let mut tv_ctx = tera::Context::new();
         tv_ctx.insert("series", &tv_series_index);
+        tv_ctx.insert("missing_count", &missing_count);
         let tv_html = template.render("tv.html", &tv_ctx).unwrap();
         std::fs::write(output_path.join("tv.html"), tv_html).unwrap();
         
@@ -321,30 +614,49 @@ fn generate_content(app: &clap::ArgMatches) {
             series.path.to_str().hash(&mut hasher);
             let page_name = hasher.finish().to_string() + ".html";
 
-            // Use the OMDB info already fetched
+            // Use the metadata already fetched
             // Group episodes by season
             let mut seasons_map: std::collections::BTreeMap<u8, Vec<_>> = std::collections::BTreeMap::new();
             for ep in &series.episodes {
-                seasons_map.entry(ep.season).or_default().push(ep);
+                seasons_map.entry(ep.season).or_default().push(tv::EpisodeTemplateData {
+                    title: ep.title.clone().unwrap_or_default(),
+                    episode_number: ep.episode,
+                    plot: ep.plot.clone(),
+                    imdb_rating: ep.imdb_rating.clone(),
+                    aired_date: ep.air_date.clone(),
+                    director: ep.director.clone(),
+                    media_ref: ep.media_ref.clone().unwrap_or_default(),
+                    cast: ep.cast.clone(),
+                    probe: ep.probe.clone(),
+                    subtitles: ep.subtitles.clone(),
+                    still_url: ep.still_url.clone(),
+                    missing: false,
+                });
+            }
+            // Aired episodes without a file go between the others, greyed out
+            for coverage in &series.coverage {
+                let episodes = seasons_map.entry(coverage.season_number).or_default();
+                for listed in &coverage.missing {
+                    episodes.push(tv::EpisodeTemplateData { missing: true, ..tv::EpisodeTemplateData::from_metadata(listed) });
+                }
+                episodes.sort_by_key(|ep| ep.episode_number);
             }
             let seasons = seasons_map.into_iter().map(|(season_number, episodes)| {
                 tv::SeasonTemplateData {
                     season_number,
-                    episodes: episodes.iter().map(|ep| tv::EpisodeTemplateData {
-                        title: ep.title.clone().unwrap_or_default(),
-                        episode_number: ep.episode,
-                        plot: ep.plot.clone(),
-                        imdb_rating: ep.imdb_rating.clone(),
-                        aired_date: ep.air_date.clone(),
-                        director: ep.director.clone(),
-                        media_ref: ep.media_ref.clone().unwrap_or_default(),
-                    }).collect(),
+                    poster_url: series.artwork.seasons.get(&season_number).map(|p| p.thumb_url.clone()),
+                    episodes,
+                    coverage: series.coverage.iter().find(|c| c.season_number == season_number).map(|c| (c.on_disk, c.aired)),
                 }
             }).collect();
             let page_data = tv::SeriesPageTemplateData {
                 series_info: series_info.clone(),
                 seasons,
                 name: series.name.clone(),
+                poster_url: series.artwork.poster.as_ref().map(|p| p.url.clone()).or(series.poster_url.clone()),
+                poster_srcset: series.artwork.poster.as_ref().map(|p| p.srcset.clone()),
+                fanart_url: series.artwork.fanart.clone()
+                    .or_else(|| series_info.as_ref().and_then(|info| info.backdrop_url.as_ref()).map(url::Url::to_string)),
             };
             let mut ctx = tera::Context::new();
             ctx.insert("media_info", &page_data);
@@ -362,24 +674,24 @@ fn generate_content(app: &clap::ArgMatches) {
             
             let meta = if let Some(movie_info) = movie_metadata {
                 build_meta_string(
-                    Some(&movie_info.genre),
-                    Some(&movie_info.actors),
-                    Some(&movie_info.director),
+                    movie_info.genre.as_deref(),
+                    movie_info.actors.as_deref(),
+                    movie_info.director.as_deref(),
                     None // Movies don't typically have separate writer field in our data
                 )
             } else {
                 build_meta_string(
                     None,
                     None,
-                    Some(&movie.director),
+                    movie.director.as_deref(),
                     None
                 )
             };
             
             search_index.add_entry(SearchIndexEntry {
                 id: generate_id(&std::path::PathBuf::from(&movie.name), "movie"),
-                title: format!("{} ({})", movie.name, movie.year),
-                year: Some(movie.year),
+                title: movie.year.map_or_else(|| movie.name.clone(), |year| format!("{} ({})", movie.name, year)),
+                year: movie.year,
                 media_type: "movie".to_string(),
                 url: movie.page_url.clone(),
                 poster_url: movie.poster_url.clone(),
@@ -396,7 +708,7 @@ fn generate_content(app: &clap::ArgMatches) {
                 build_meta_string(
                     series.genre.as_deref(),
                     series.actors.as_deref(),
-                    Some(&series_info.director),
+                    series_info.director.as_deref(),
                     None
                 )
             } else if let Some((series, None)) = series_data {
@@ -460,7 +772,8 @@ fn generate_content(app: &clap::ArgMatches) {
                         episode.season,
                         episode.episode
                     ),
-                    poster_url: series.poster_url.as_deref().unwrap_or("https://via.placeholder.com/300x450.png?text=No+Poster").to_string(),
+                    poster_url: series.artwork.poster.as_ref().map(|p| p.thumb_url.as_str())
+                        .or(series.poster_url.as_deref()).unwrap_or(artwork::NO_POSTER_FILE).to_string(),
                     meta,
                 });
             }
@@ -474,119 +787,10 @@ fn generate_content(app: &clap::ArgMatches) {
         std::fs::write(output_path.join("media.css"), DEFAULT_CSS_FILE).unwrap();
         std::fs::write(output_path.join("media.js"), DEFAULT_JS_FILE).unwrap();
         std::fs::write(output_path.join("search.js"), DEFAULT_SEARCH_JS_FILE).unwrap();
+        std::fs::write(output_path.join(artwork::NO_POSTER_FILE), DEFAULT_NO_POSTER_FILE).unwrap();
     }
 }
 
-// Function to start the web server
-fn start_webserver(html_dir: String, tv_folders: Vec<(String, String)>, movies_folder: Vec<(String, String)>, port: u16, base_url: Option<url::Url>) -> std::io::Result<()> {
-    use rouille::Response;
-    use std::path::PathBuf;
-    
-    log::info!(target: "cli", "Starting web server on port {} serving files from {}", port, html_dir);
-    
-    let html_dir_path = PathBuf::from(html_dir);
-    let address = format!("127.0.0.1:{}", port);
-    
-    // Determine the base path from base_url
-    let base_path = if let Some(ref url) = base_url {
-        url.path().trim_end_matches('/').to_string()
-    } else {
-        String::new()
-    };
-    
-    // Rouille's start_server doesn't return, so we don't need to handle the result
-    println!("Starting web server at http://{}", address);
-    rouille::start_server(address, move |request| {
-        log::info!(target: "cli", "{} {}", request.method(), request.url());
-        
-        // Strip base path from request URL if it exists
-        let request_url = request.url();
-        let request_path = if !base_path.is_empty() && request_url.starts_with(&base_path) {
-            &request_url[base_path.len()..]
-        } else if base_path.is_empty() {
-            &request_url
-        } else {
-            // Request doesn't match base path, return 404
-            return Response::html("Not found").with_status_code(404);
-        };
-
-        // First try to handle media folders
-        for (folder, mount) in &movies_folder {
-            let mount_str = format!("/{}", mount);
-            if request_path.starts_with(&mount_str) {
-                let rel_path = &request_path[mount_str.len()..];
-                let file_path = PathBuf::from(folder).join(rel_path.trim_start_matches('/'));
-                if file_path.exists() {
-                    // Use mime_guess to determine content type
-                    let mime = mime_guess::from_path(&file_path).first_or_octet_stream().to_string();
-                    return Response::from_file(mime, std::fs::File::open(file_path).unwrap());
-                }
-            }
-        }
-        
-        // Then try TV folders
-        for (folder, mount) in &tv_folders {
-            let mount_str = format!("/{}", mount);
-            if request_path.starts_with(&mount_str) {
-                let rel_path = &request_path[mount_str.len()..];
-                let file_path = PathBuf::from(folder).join(rel_path.trim_start_matches('/'));
-                if file_path.exists() {
-                    // Use mime_guess to determine content type
-                    let mime = mime_guess::from_path(&file_path).first_or_octet_stream().to_string();
-                    return Response::from_file(mime, std::fs::File::open(file_path).unwrap());
-                }
-            }
-        }
-        
-        // Finally serve the static HTML files using the stripped request path
-        match request_path {
-            "/" => {
-                let index_path = html_dir_path.join("index.html");
-                if index_path.exists() {
-                    Response::from_file("text/html", std::fs::File::open(index_path).unwrap())
-                } else {
-                    Response::text("Index file not found").with_status_code(404)
-                }
-            },
-            "/media.css" => {
-                Response::from_file("text/css", std::fs::File::open(html_dir_path.join("media.css")).unwrap())
-            },
-            "/media.js" => {
-                Response::from_file("application/javascript", std::fs::File::open(html_dir_path.join("media.js")).unwrap())
-            },
-            "/search.js" => {
-                Response::from_file("application/javascript", std::fs::File::open(html_dir_path.join("search.js")).unwrap())
-            },
-            "/search-index.json" => {
-                Response::from_file("application/json", std::fs::File::open(html_dir_path.join("search-index.json")).unwrap())
-            },
-            "/movies.html" => {
-                Response::from_file("text/html", std::fs::File::open(html_dir_path.join("movies.html")).unwrap())
-            },
-            "/tv.html" => {
-                Response::from_file("text/html", std::fs::File::open(html_dir_path.join("tv.html")).unwrap())
-            },
-            // Fallback for other HTML files (media pages)
-            _ => {
-                let path = html_dir_path.join(request_path.trim_start_matches('/'));
-                if path.exists() && path.is_file() {
-                    // Use mime_guess to determine content type
-                    let mime = mime_guess::from_path(&path).first_or_octet_stream().to_string();
-                    Response::from_file(mime, std::fs::File::open(path).unwrap())
-                } else {
-                    log::info!(target: "cli", "404 for path: {}", request_path);
-                    Response::html("Not found").with_status_code(404)
-                }
-            }
-        }
-    });
-    
-    // This function never actually returns because start_server blocks indefinitely
-    // We keep the return type for compatibility with the rest of the code
-    #[allow(unreachable_code)]
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
     use std::path::Path;
@@ -600,9 +804,51 @@ mod tests {
     #[test]
     fn test_media_ref() {
         let path = Path::new("./Movies/Some movie 1993/some movie 1993.mp4");
-        assert_eq!(gen_media_ref(&Url::parse("https://someserver:8080/media/").ok(), Path::new("./Movies"), &"movies".to_string(), path), "https://someserver:8080/media/movies/Some%20movie%201993/some%20movie%201993.mp4");
+        assert_eq!(gen_media_ref(&Url::parse("https://someserver:8080/media/").ok(), Path::new("./Movies"), "movies", path), "https://someserver:8080/media/movies/Some%20movie%201993/some%20movie%201993.mp4");
         // technically speaking, the base url should end with / or the last component isn't "the base". Might be confusing but there we are 
-        assert_eq!(gen_media_ref(&Url::parse("https://someserver:8080/media").ok(), Path::new("./Movies"), &"movies".to_string(), path), "https://someserver:8080/movies/Some%20movie%201993/some%20movie%201993.mp4");
-        assert_eq!(gen_media_ref(&None, Path::new("./Movies"), &"movies".to_string(), path), "movies/Some%20movie%201993/some%20movie%201993.mp4");
+        assert_eq!(gen_media_ref(&Url::parse("https://someserver:8080/media").ok(), Path::new("./Movies"), "movies", path), "https://someserver:8080/movies/Some%20movie%201993/some%20movie%201993.mp4");
+        assert_eq!(gen_media_ref(&None, Path::new("./Movies"), "movies", path), "movies/Some%20movie%201993/some%20movie%201993.mp4");
+    }
+
+    #[test]
+    fn test_movie_page_probe_details() {
+        let mut template = tera::Tera::default();
+        template.add_raw_template("base.html", include_str!("base.html")).unwrap();
+        template.add_raw_template("movie.html", include_str!("movie.html")).unwrap();
+        let mut ctx = tera::Context::new();
+        let fields = ["name", "year", "director", "info_url", "poster_url", "language", "plot", "genre", "runtime", "released", "rated", "actors", "imdb_rating"];
+        ctx.insert("media_info", &fields.iter().map(|f| (f.to_string(), "x".to_string())).collect::<std::collections::HashMap<_, _>>());
+        ctx.insert("media_ref", "movies/movie.mkv");
+        ctx.insert("cast", &crate::transcode::CastTarget {
+            url: "transcode/movies/movie.mkv".into(),
+            content_type: "video/mp4".into(),
+            castability: crate::transcode::Castability::Transcode,
+        });
+        ctx.insert("probe", &crate::probe::MediaProbe {
+            duration_secs: Some(5400.0),
+            container: Some("matroska,webm".into()),
+            video: Some(crate::probe::VideoTrack { codec: "hevc".into(), width: Some(3840), height: Some(2160), hdr: true }),
+            audio_tracks: vec![crate::probe::AudioTrack { codec: "eac3".into(), language: Some("eng".into()), channels: Some(6), title: None }],
+            subtitle_tracks: vec![crate::probe::SubtitleTrack { index: 2, codec: "subrip".into(), ..Default::default() }],
+            file_size: 3 * 1024 * 1024 * 1024,
+        });
+        ctx.insert("subtitles", &Vec::<crate::subtitles::TextTrack>::new());
+        let html = template.render("movie.html", &ctx).unwrap();
+        assert!(html.contains("3840&times;2160 HEVC HDR"));
+        assert!(html.contains("eng EAC3 6ch"));
+        assert!(html.contains("90 min, 3 GB"));
+        assert!(html.contains(r#"data-content-type="video&#x2F;mp4""#));
+        assert!(html.contains("needs transcoding"));
+
+        ctx.insert("subtitles", &vec![crate::subtitles::TextTrack {
+            url: "subtitles/1.vtt".into(), language: Some("he".into()), label: "Hebrew".into(), forced: false,
+        }]);
+        let html = template.render("movie.html", &ctx).unwrap();
+        assert!(html.contains("data-text-tracks=\"[{&quot;"));
+        assert!(html.contains("&quot;url&quot;:&quot;subtitles&#x2F;1.vtt&quot;"));
+        assert!(html.contains("Hebrew"));
+
+        ctx.insert("probe", &None::<crate::probe::MediaProbe>);
+        assert!(!template.render("movie.html", &ctx).unwrap().contains("Subtitles"));
     }
 }
\ No newline at end of file
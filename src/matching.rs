@@ -0,0 +1,230 @@
+use crate::cache::MediaCache;
+use crate::media::{omdb_get_metadata, omdb_get_metadata_by_id, omdb_search, parse_year, OmdbError, OmdbResponse, OmdbSearchItem, OmdbType};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Confidence a search result needs to be used, see `score`.
+pub const DEFAULT_MATCH_THRESHOLD: f64 = 0.75;
+
+/// How titles without an IMDb id are matched to OMDB entries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchMode {
+    /// OMDB's `t=` lookup, taking whatever it returns.
+    Exact,
+    /// OMDB's `s=` search, accepting the best scored candidate at or above `threshold`.
+    Search { threshold: f64 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchCandidate {
+    pub imdb_id: String,
+    pub title: String,
+    pub year: Option<u16>,
+    pub media_type: String,
+    /// Between 0 and 1.
+    pub score: f64,
+}
+
+/// Outcome of a search, kept in the cache for review.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchRecord {
+    pub query: String,
+    pub year: Option<u16>,
+    pub media_type: String,
+    /// The best candidate; its score is the match confidence.
+    pub best: Option<MatchCandidate>,
+    pub runners_up: Vec<MatchCandidate>,
+}
+
+impl MatchRecord {
+    pub fn confidence(&self) -> f64 {
+        self.best.as_ref().map_or(0.0, |b| b.score)
+    }
+
+    /// The matched IMDb id, if the confidence reaches `threshold`.
+    pub fn accepted(&self, threshold: f64) -> Option<&str> {
+        self.best.as_ref().filter(|b| b.score >= threshold).map(|b| b.imdb_id.as_str())
+    }
+
+    /// The matched IMDb id, or "not found" so the miss is remembered like OMDB's own.
+    fn matched(&self, threshold: f64) -> Result<&str, OmdbError> {
+        match (self.accepted(threshold), &self.best) {
+            (Some(imdb_id), _) => Ok(imdb_id),
+            (None, Some(best)) => Err(OmdbError::NotFound(format!(
+                "no confident match for '{}': best is '{}' ({:?}) at {:.2}", self.query, best.title, best.year, best.score))),
+            (None, None) => Err(OmdbError::NotFound(format!("search found nothing for '{}'", self.query))),
+        }
+    }
+}
+
+/// Lowercase words without punctuation or a leading article, with `&` spelled out.
+pub fn normalize_title(title: &str) -> String {
+    let spelled = title.to_lowercase().replace('&', " and ");
+    let cleaned: String = spelled.chars().map(|c| if c.is_alphanumeric() { c } else { ' ' }).collect();
+    let mut words: Vec<&str> = cleaned.split_whitespace().collect();
+    if words.len() > 1 && ["the", "a", "an"].contains(&words[0]) {
+        words.remove(0);
+    }
+    words.join(" ")
+}
+
+fn bigrams(s: &str) -> HashSet<(char, char)> {
+    let chars: Vec<char> = s.chars().collect();
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// Dice coefficient of the character bigrams of both normalized titles; 1 for equal titles.
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize_title(a), normalize_title(b));
+    if a == b {
+        return 1.0;
+    }
+    let (a, b) = (bigrams(&a), bigrams(&b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(&b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+/// Festival and release dates are often a year apart, so ±1 still scores well.
+fn year_score(wanted: Option<u16>, found: Option<u16>) -> f64 {
+    match (wanted, found) {
+        (Some(wanted), Some(found)) => match wanted.abs_diff(found) {
+            0 => 1.0,
+            1 => 0.8,
+            2 => 0.3,
+            _ => 0.0,
+        },
+        // Nothing to tell candidates apart by
+        _ => 0.5,
+    }
+}
+
+/// Weighted title similarity (0.6), year closeness (0.3) and type (0.1). A same-titled film from
+/// another decade stays below the default threshold.
+pub fn score(title: &str, year: Option<u16>, media_type: &str, item: &OmdbSearchItem) -> MatchCandidate {
+    let item_year = parse_year(&item.year);
+    let type_score = if item.media_type == media_type { 1.0 } else { 0.0 };
+    MatchCandidate {
+        imdb_id: item.imdb_id.clone(),
+        title: item.title.clone(),
+        year: item_year,
+        media_type: item.media_type.clone(),
+        score: 0.6 * title_similarity(title, &item.title) + 0.3 * year_score(year, item_year) + 0.1 * type_score,
+    }
+}
+
+/// Scores search results, best first.
+pub fn rank(title: &str, year: Option<u16>, media_type: &str, items: &[OmdbSearchItem]) -> MatchRecord {
+    let mut candidates: Vec<MatchCandidate> = items.iter().map(|item| score(title, year, media_type, item)).collect();
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let mut candidates = candidates.into_iter();
+    MatchRecord {
+        query: title.to_string(),
+        year,
+        media_type: media_type.to_string(),
+        best: candidates.next(),
+        runners_up: candidates.take(4).collect(),
+    }
+}
+
+/// Finds the IMDb id of a title with OMDB's search. Accepted matches are reused from the cache;
+/// every search is recorded there with its confidence and runners-up.
+pub fn search_match(
+    omdb_api_key: &str,
+    entity_type: OmdbType,
+    title: &str,
+    year: Option<u16>,
+    threshold: f64,
+    cache: &Option<MediaCache>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let media_type = entity_type.to_string();
+    if let Some(media_cache) = cache {
+        match media_cache.get_match(&media_type, title, year) {
+            Ok(Some(record)) => {
+                if let Some(imdb_id) = record.accepted(threshold) {
+                    return Ok(imdb_id.to_string());
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to read OMDB match of '{}' from cache: {}", title, e),
+        }
+    }
+
+    let items = omdb_search(omdb_api_key, entity_type, title)?;
+    let record = rank(title, year, &media_type, &items);
+    if let Some(media_cache) = cache {
+        if let Err(e) = media_cache.store_match(&record) {
+            log::error!("Failed to store OMDB match of '{}' in cache: {}", title, e);
+        }
+    }
+    let imdb_id = record.matched(threshold)?;
+    if let Some(best) = &record.best {
+        log::info!(target: "cli", "Matched '{}' to '{}' ({:?}, {}) with confidence {:.2}", title, best.title, best.year, imdb_id, best.score);
+    }
+    Ok(imdb_id.to_string())
+}
+
+/// Looks up a title without an IMDb id the way `matching` says.
+pub fn omdb_lookup(
+    omdb_api_key: &str,
+    matching: &MatchMode,
+    entity_type: OmdbType,
+    title: &str,
+    year: Option<u16>,
+    cache: &Option<MediaCache>,
+) -> Result<OmdbResponse, Box<dyn std::error::Error>> {
+    match matching {
+        MatchMode::Exact => Ok(omdb_get_metadata(omdb_api_key, entity_type, title, year)?),
+        MatchMode::Search { threshold } => {
+            let imdb_id = search_match(omdb_api_key, entity_type, title, year, *threshold, cache)?;
+            Ok(omdb_get_metadata_by_id(omdb_api_key, &imdb_id)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(title: &str, year: &str, imdb_id: &str, media_type: &str) -> OmdbSearchItem {
+        OmdbSearchItem { title: title.into(), year: year.into(), imdb_id: imdb_id.into(), media_type: media_type.into() }
+    }
+
+    #[test]
+    fn test_title_similarity() {
+        assert_eq!(normalize_title("The Lord of the Rings: The Fellowship of the Ring"), "lord of the rings the fellowship of the ring");
+        assert_eq!(title_similarity("Fast & Furious", "fast and furious"), 1.0);
+        assert_eq!(title_similarity("The Thing", "Thing"), 1.0);
+        assert!(title_similarity("Star Wars", "Star Wars: Episode IV") > 0.5);
+        assert!(title_similarity("Alien", "Aliens") > title_similarity("Alien", "Predator"));
+    }
+
+    #[test]
+    fn test_rank() {
+        let items = [
+            item("Dune", "2021", "tt1160419", "movie"),
+            item("Dune", "1984", "tt0087182", "movie"),
+            item("Dune: Part Two", "2024", "tt15239678", "movie"),
+        ];
+        let record = rank("Dune", Some(1984), "movie", &items);
+        assert_eq!(record.best.as_ref().unwrap().imdb_id, "tt0087182");
+        assert!(record.confidence() > 0.99);
+        assert_eq!(record.runners_up.len(), 2);
+        assert_eq!(record.accepted(DEFAULT_MATCH_THRESHOLD), Some("tt0087182"));
+
+        // Festival premiere a year before the release still matches
+        assert_eq!(rank("Dune", Some(2020), "movie", &items).accepted(DEFAULT_MATCH_THRESHOLD), Some("tt1160419"));
+        // Only the remake is known: not confident enough
+        let record = rank("Dune", Some(1984), "movie", &items[..1]);
+        assert_eq!(record.accepted(DEFAULT_MATCH_THRESHOLD), None);
+        assert!(record.confidence() < DEFAULT_MATCH_THRESHOLD);
+        assert_eq!(rank("Dune", None, "movie", &[]).best, None);
+
+        // Misses are "not found", so the cache remembers them instead of searching every run
+        let not_found = |record: &MatchRecord| crate::provider::is_not_found(&record.matched(DEFAULT_MATCH_THRESHOLD).unwrap_err());
+        assert!(not_found(&record));
+        assert!(not_found(&rank("Dune", None, "movie", &[])));
+        assert_eq!(rank("Dune", Some(1984), "movie", &items).matched(DEFAULT_MATCH_THRESHOLD).unwrap(), "tt0087182");
+    }
+}
@@ -1,17 +1,51 @@
 use serde::Deserialize;
 use serde_derive::Serialize;
-use reqwest;
+use regex::Regex;
 use url::Url;
 use std::error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::http::{self, HttpError};
 
 const OMDB_API_URL: &str = "https://www.omdbapi.com";
 
-#[derive(Serialize, Debug, PartialEq)]
+#[derive(Serialize, Debug, PartialEq, Clone)]
 
 pub struct MediaInfo {
     pub name: String,
     pub year: Option<u16>,
-    pub path: std::path::PathBuf
+    pub path: std::path::PathBuf,
+    /// IMDb id hinted in the file or folder name
+    pub imdb_id: Option<String>,
+}
+
+lazy_static! {
+    static ref IMDB_HINT_RE: Regex = Regex::new(r"(?i)\[(?:imdbid-)?(tt\d{7,})\]|\{imdb(?:id)?-(tt\d{7,})\}|\bimdbid-(tt\d{7,})\b").unwrap();
+}
+
+pub fn imdb_url(imdb_id: &str) -> Option<Url> {
+    Url::parse("https://www.imdb.com/title/").unwrap().join(imdb_id).ok()
+}
+
+/// The IMDb id of an `imdb_url`, e.g. of a title matched by name.
+pub fn imdb_id_from_url(url: &Url) -> Option<String> {
+    if url.host_str() != Some("www.imdb.com") {
+        return None;
+    }
+    url.path_segments()?.rfind(|s| s.starts_with("tt")).map(|s| s.to_string())
+}
+
+/// Finds an IMDb id hint (`[tt1234567]`, `{imdb-tt1234567}` or `imdbid-tt1234567`, as Plex and Jellyfin
+/// name folders) and returns the name without it.
+pub fn strip_imdb_hint(name: &str) -> (String, Option<String>) {
+    match IMDB_HINT_RE.captures(name) {
+        Some(caps) => {
+            let imdb_id = caps.iter().skip(1).flatten().next().map(|m| m.as_str().to_lowercase());
+            let hint = caps.get(0).unwrap();
+            let stripped = format!("{}{}", name[..hint.start()].trim_end(), &name[hint.end()..]);
+            (stripped.trim().to_string(), imdb_id)
+        }
+        None => (name.to_string(), None),
+    }
 }
 
 pub trait MediaInfoEquiv {
@@ -35,159 +69,163 @@ pub struct OmdbRatings {
     pub value: String
 } 
 
+/// OMDB's placeholder for a missing field.
+const OMDB_NOT_AVAILABLE: &str = "N/A";
+
+/// Deserializes an OMDB field, mapping `"N/A"` and empty strings to `None`.
+fn na_as_none<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let value: Option<String> = Deserialize::deserialize(deserializer)?;
+    Ok(value.filter(|v| !v.trim().is_empty() && v.trim() != OMDB_NOT_AVAILABLE))
+}
+
+/// The start year of an OMDB year such as `2019`, `2005–2013` or `2019–`.
+pub fn parse_year(year: &str) -> Option<u16> {
+    year.get(..4).and_then(|y| y.parse().ok())
+}
+
+/// OMDB title details. Fields OMDB has no value for (`"N/A"`) are `None`.
 #[derive(Deserialize, Debug)]
 #[serde(rename_all(deserialize="PascalCase"), tag="Type")]
 #[allow(dead_code)]
 pub enum OmdbResponse {
     #[serde(rename="movie")]
     Movie {
-        #[serde(rename="Actors")]
-        actors: String,
-        #[serde(rename="Awards")]
-        awards: String,
-        #[serde(rename="Country")]
-        country: String,
-        #[serde(rename="Director")]
-        director: String,
-        #[serde(rename="Genre")]
-        genre: String,
-        #[serde(rename="Language")]
-        language: String,
-        #[serde(rename="Plot")]
-        plot: String,
-        #[serde(rename="Poster")]
-        poster: String,
-        #[serde(rename="Rated")]
-        rated: String,
-        #[serde(rename="Ratings")]
+        #[serde(rename="Actors", default, deserialize_with="na_as_none")]
+        actors: Option<String>,
+        #[serde(rename="Awards", default, deserialize_with="na_as_none")]
+        awards: Option<String>,
+        #[serde(rename="Country", default, deserialize_with="na_as_none")]
+        country: Option<String>,
+        #[serde(rename="Director", default, deserialize_with="na_as_none")]
+        director: Option<String>,
+        #[serde(rename="Genre", default, deserialize_with="na_as_none")]
+        genre: Option<String>,
+        #[serde(rename="Language", default, deserialize_with="na_as_none")]
+        language: Option<String>,
+        #[serde(rename="Plot", default, deserialize_with="na_as_none")]
+        plot: Option<String>,
+        #[serde(rename="Poster", default, deserialize_with="na_as_none")]
+        poster: Option<String>,
+        #[serde(rename="Rated", default, deserialize_with="na_as_none")]
+        rated: Option<String>,
+        #[serde(rename="Ratings", default)]
         ratings: Vec<OmdbRatings>,
-        #[serde(rename="Released")]
-        released: String,
-        #[serde(rename="Runtime")]
-        runtime: String,
+        #[serde(rename="Released", default, deserialize_with="na_as_none")]
+        released: Option<String>,
+        #[serde(rename="Runtime", default, deserialize_with="na_as_none")]
+        runtime: Option<String>,
         #[serde(rename="Title")]
         title: String,
-        #[serde(rename="Writer")]
-        writer: String,
-        #[serde(rename="Year")]
-        year: String,
+        #[serde(rename="Writer", default, deserialize_with="na_as_none")]
+        writer: Option<String>,
+        #[serde(rename="Year", default, deserialize_with="na_as_none")]
+        year: Option<String>,
         #[serde(rename="imdbID")]
         imdb_id: String,
-        #[serde(rename="imdbRating")]
-        imdb_rating: String,
-        #[serde(rename="BoxOffice")]
+        #[serde(rename="imdbRating", default, deserialize_with="na_as_none")]
+        imdb_rating: Option<String>,
+        #[serde(rename="BoxOffice", default, deserialize_with="na_as_none")]
         box_office: Option<String>,
-        #[serde(rename="DVD")]
+        #[serde(rename="DVD", default, deserialize_with="na_as_none")]
         dvd: Option<String>,
-        #[serde(rename="Metascore")]
+        #[serde(rename="Metascore", default, deserialize_with="na_as_none")]
         metascore: Option<String>,
-        #[serde(rename="Production")]
+        #[serde(rename="Production", default, deserialize_with="na_as_none")]
         production: Option<String>,
-        #[serde(rename="Website")]
+        #[serde(rename="Website", default, deserialize_with="na_as_none")]
         website: Option<String>,
     },
     #[serde(rename="series")]
     Series {
-        #[serde(rename="Actors")]
-        actors: String,
-        #[serde(rename="Awards")]
-        awards: String,
-        #[serde(rename="Country")]
-        country: String,
-        #[serde(rename="Director")]
-        director: String,
-        #[serde(rename="Genre")]
-        genre: String,
-        #[serde(rename="Language")]
-        language: String,
-        #[serde(rename="Plot")]
-        plot: String,
-        #[serde(rename="Poster")]
-        poster: String,
-        #[serde(rename="Rated")]
-        rated: String,
-        #[serde(rename="Ratings")]
+        #[serde(rename="Actors", default, deserialize_with="na_as_none")]
+        actors: Option<String>,
+        #[serde(rename="Awards", default, deserialize_with="na_as_none")]
+        awards: Option<String>,
+        #[serde(rename="Country", default, deserialize_with="na_as_none")]
+        country: Option<String>,
+        #[serde(rename="Director", default, deserialize_with="na_as_none")]
+        director: Option<String>,
+        #[serde(rename="Genre", default, deserialize_with="na_as_none")]
+        genre: Option<String>,
+        #[serde(rename="Language", default, deserialize_with="na_as_none")]
+        language: Option<String>,
+        #[serde(rename="Plot", default, deserialize_with="na_as_none")]
+        plot: Option<String>,
+        #[serde(rename="Poster", default, deserialize_with="na_as_none")]
+        poster: Option<String>,
+        #[serde(rename="Rated", default, deserialize_with="na_as_none")]
+        rated: Option<String>,
+        #[serde(rename="Ratings", default)]
         ratings: Vec<OmdbRatings>,
-        #[serde(rename="Released")]
-        released: String,
-        #[serde(rename="Runtime")]
-        runtime: String,
+        #[serde(rename="Released", default, deserialize_with="na_as_none")]
+        released: Option<String>,
+        #[serde(rename="Runtime", default, deserialize_with="na_as_none")]
+        runtime: Option<String>,
         #[serde(rename="Title")]
         title: String,
-        #[serde(rename="Writer")]
-        writer: String,
-        #[serde(rename="Year")]
-        year: String,
+        #[serde(rename="Writer", default, deserialize_with="na_as_none")]
+        writer: Option<String>,
+        #[serde(rename="Year", default, deserialize_with="na_as_none")]
+        year: Option<String>,
         #[serde(rename="imdbID")]
         imdb_id: String,
-        #[serde(rename="imdbRating")]
-        imdb_rating: String,
-        #[serde(rename="totalSeasons")]
-        total_seasons: String,
-        #[serde(rename="Metascore")]
+        #[serde(rename="imdbRating", default, deserialize_with="na_as_none")]
+        imdb_rating: Option<String>,
+        #[serde(rename="totalSeasons", default, deserialize_with="na_as_none")]
+        total_seasons: Option<String>,
+        #[serde(rename="Metascore", default, deserialize_with="na_as_none")]
         metascore: Option<String>,
     },
     #[serde(rename="episode")]
     Episode {
         #[serde(rename="Title")]
         title: String,
-        #[serde(rename="Year")]
-        year: String,
-        #[serde(rename="Rated")]
-        rated: String,
-        #[serde(rename="Released")]
-        released: String,
-        #[serde(rename="Season")]
-        season: String,
-        #[serde(rename="Episode")]
-        episode: String,
-        #[serde(rename="Runtime")]
-        runtime: String,
-        #[serde(rename="Genre")]
-        genre: String,
-        #[serde(rename="Director")]
-        director: String,
-        #[serde(rename="Writer")]
-        writer: String,
-        #[serde(rename="Actors")]
-        actors: String,
-        #[serde(rename="Plot")]
-        plot: String,
-        #[serde(rename="Language")]
-        language: String,
-        #[serde(rename="Country")]
-        country: String,
-        #[serde(rename="Awards")]
-        awards: String,
-        #[serde(rename="Poster")]
-        poster: String,
-        #[serde(rename="Ratings")]
+        #[serde(rename="Year", default, deserialize_with="na_as_none")]
+        year: Option<String>,
+        #[serde(rename="Rated", default, deserialize_with="na_as_none")]
+        rated: Option<String>,
+        #[serde(rename="Released", default, deserialize_with="na_as_none")]
+        released: Option<String>,
+        #[serde(rename="Season", default, deserialize_with="na_as_none")]
+        season: Option<String>,
+        #[serde(rename="Episode", default, deserialize_with="na_as_none")]
+        episode: Option<String>,
+        #[serde(rename="Runtime", default, deserialize_with="na_as_none")]
+        runtime: Option<String>,
+        #[serde(rename="Genre", default, deserialize_with="na_as_none")]
+        genre: Option<String>,
+        #[serde(rename="Director", default, deserialize_with="na_as_none")]
+        director: Option<String>,
+        #[serde(rename="Writer", default, deserialize_with="na_as_none")]
+        writer: Option<String>,
+        #[serde(rename="Actors", default, deserialize_with="na_as_none")]
+        actors: Option<String>,
+        #[serde(rename="Plot", default, deserialize_with="na_as_none")]
+        plot: Option<String>,
+        #[serde(rename="Language", default, deserialize_with="na_as_none")]
+        language: Option<String>,
+        #[serde(rename="Country", default, deserialize_with="na_as_none")]
+        country: Option<String>,
+        #[serde(rename="Awards", default, deserialize_with="na_as_none")]
+        awards: Option<String>,
+        #[serde(rename="Poster", default, deserialize_with="na_as_none")]
+        poster: Option<String>,
+        #[serde(rename="Ratings", default)]
         ratings: Vec<OmdbRatings>,
-        #[serde(rename="Metascore")]
-        metascore: String,
-        #[serde(rename="imdbRating")]
-        imdb_rating: String,
-        #[serde(rename="imdbVotes")]
-        imdb_votes: String,
+        #[serde(rename="Metascore", default, deserialize_with="na_as_none")]
+        metascore: Option<String>,
+        #[serde(rename="imdbRating", default, deserialize_with="na_as_none")]
+        imdb_rating: Option<String>,
+        #[serde(rename="imdbVotes", default, deserialize_with="na_as_none")]
+        imdb_votes: Option<String>,
         #[serde(rename="imdbID")]
         imdb_id: String,
-        #[serde(rename="seriesID")]
-        series_id: String,
+        #[serde(rename="seriesID", default, deserialize_with="na_as_none")]
+        series_id: Option<String>,
     }
 }
 
 
-impl OmdbResponse {
-    pub fn imdb_url(&self) -> Url {
-        let imdb_id = match self {
-            OmdbResponse::Movie { imdb_id, .. } => imdb_id,
-            OmdbResponse::Series { imdb_id, .. } => imdb_id,
-            OmdbResponse::Episode { imdb_id, .. } => imdb_id,
-        };
-        Url::parse("https://www.imdb.com/title/").unwrap().join(imdb_id).unwrap()
-    }
-}
-
 impl std::fmt::Display for OmdbType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -198,7 +236,94 @@ impl std::fmt::Display for OmdbType {
     }
 }
 
-pub fn omdb_get_metadata(omdb_api_key: &str, entity_type: OmdbType, title: &str, year: Option<u16>) -> Result<OmdbResponse, Box<dyn error::Error>> {
+/// Why an OMDB request failed.
+#[derive(Debug)]
+pub enum OmdbError {
+    /// OMDB has no such title; its message, e.g. `Movie not found!`
+    NotFound(String),
+    /// The daily request limit of the key is used up.
+    RateLimited,
+    InvalidKey,
+    /// No answer from OMDB, e.g. it's down or there's no network.
+    Transport(reqwest::Error),
+    /// An answer that isn't what was asked for, or an OMDB error message not known here.
+    Decode(String),
+}
+
+impl OmdbError {
+    /// Whether every further request would fail the same way, so the run should stop.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, OmdbError::InvalidKey)
+    }
+}
+
+impl std::fmt::Display for OmdbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OmdbError::NotFound(message) => write!(f, "not found on OMDB: {}", message),
+            OmdbError::RateLimited => write!(f, "OMDB request limit reached"),
+            OmdbError::InvalidKey => write!(f, "invalid OMDB API key"),
+            OmdbError::Transport(e) => write!(f, "OMDB request failed: {}", e),
+            OmdbError::Decode(message) => write!(f, "unexpected OMDB response: {}", message),
+        }
+    }
+}
+
+impl error::Error for OmdbError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            OmdbError::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for OmdbError {
+    fn from(e: reqwest::Error) -> Self {
+        OmdbError::Transport(e)
+    }
+}
+
+/// Set once OMDB says the request limit is reached; it's a daily limit, so the rest of the run doesn't ask again.
+static OMDB_RATE_LIMITED: AtomicBool = AtomicBool::new(false);
+
+/// OMDB's `{"Response":"False","Error":"..."}` as an `OmdbError`.
+fn omdb_error(message: &str) -> OmdbError {
+    match message {
+        "Request limit reached!" => OmdbError::RateLimited,
+        "Invalid API key!" | "No API key provided." => OmdbError::InvalidKey,
+        m if m.ends_with("not found!") || m.starts_with("Incorrect IMDb ID") || m == "Too many results." => OmdbError::NotFound(m.to_string()),
+        m => OmdbError::Decode(m.to_string()),
+    }
+}
+
+/// Sends a request to OMDB and decodes its answer, telling error payloads (which come with any HTTP status) apart.
+fn omdb_request<T: serde::de::DeserializeOwned>(params: &[(&str, String)]) -> Result<T, OmdbError> {
+    if OMDB_RATE_LIMITED.load(Ordering::Relaxed) {
+        return Err(OmdbError::RateLimited);
+    }
+    let body = match http::omdb().get(OMDB_API_URL, params) {
+        Ok(resp) => resp.text()?,
+        Err(HttpError::QuotaExhausted(limit)) => {
+            if !OMDB_RATE_LIMITED.swap(true, Ordering::Relaxed) {
+                log::warn!(target: "cli", "OMDB daily quota of {} calls used, no more OMDB lookups in this run", limit);
+            }
+            return Err(OmdbError::RateLimited);
+        }
+        Err(HttpError::Request(e)) => return Err(e.into()),
+    };
+    let value: serde_json::Value = serde_json::from_str(&body).map_err(|e| OmdbError::Decode(e.to_string()))?;
+    if value.get("Response").and_then(|r| r.as_str()) == Some("False") {
+        let error = omdb_error(value.get("Error").and_then(|e| e.as_str()).unwrap_or_default());
+        if matches!(error, OmdbError::RateLimited) && !OMDB_RATE_LIMITED.swap(true, Ordering::Relaxed) {
+            log::warn!(target: "cli", "OMDB request limit reached, no more OMDB lookups in this run");
+        }
+        return Err(error);
+    }
+    serde_json::from_value(value).map_err(|e| OmdbError::Decode(e.to_string()))
+}
+
+pub fn omdb_get_metadata(omdb_api_key: &str, entity_type: OmdbType, title: &str, year: Option<u16>) -> Result<OmdbResponse, OmdbError> {
     let mut params = vec![
         ("apiKey", omdb_api_key.to_string()),
         ("t", title.to_string()),
@@ -208,38 +333,160 @@ pub fn omdb_get_metadata(omdb_api_key: &str, entity_type: OmdbType, title: &str,
         params.push(("y", y.to_string()));
     }
 
-    let client = reqwest::blocking::Client::new();
-    let resp = client.get(OMDB_API_URL)
-        .query(&params)
-        .send()?
-        .json::<OmdbResponse>()?;
-    Ok(resp)
+    omdb_request(&params)
+}
+
+/// One result of an OMDB `s=` search.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct OmdbSearchItem {
+    #[serde(rename="Title")]
+    pub title: String,
+    /// Start year, or a range like `2005–2013` for series.
+    #[serde(rename="Year")]
+    pub year: String,
+    #[serde(rename="imdbID")]
+    pub imdb_id: String,
+    #[serde(rename="Type")]
+    pub media_type: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct OmdbSearchResponse {
+    #[serde(rename="Search", default)]
+    search: Vec<OmdbSearchItem>,
 }
 
+/// Searches titles of a type with OMDB's `s=`; no results is an empty list, not an error.
+pub fn omdb_search(omdb_api_key: &str, entity_type: OmdbType, title: &str) -> Result<Vec<OmdbSearchItem>, OmdbError> {
+    let params = vec![
+        ("apiKey", omdb_api_key.to_string()),
+        ("s", title.to_string()),
+        ("type", entity_type.to_string()),
+    ];
+
+    match omdb_request::<OmdbSearchResponse>(&params) {
+        Ok(resp) => Ok(resp.search),
+        Err(OmdbError::NotFound(_)) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Looks up a title by its IMDb id, e.g. one pinned by an NFO file.
+pub fn omdb_get_metadata_by_id(omdb_api_key: &str, imdb_id: &str) -> Result<OmdbResponse, OmdbError> {
+    let params = vec![
+        ("apiKey", omdb_api_key.to_string()),
+        ("i", imdb_id.to_string()),
+    ];
+
+    omdb_request(&params)
+}
+
+/// One episode of an OMDB season listing; the listing has no plot, director or actors.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct OmdbSeasonEpisode {
+    #[serde(rename="Title")]
+    pub title: String,
+    #[serde(rename="Released", default, deserialize_with="na_as_none")]
+    pub released: Option<String>,
+    #[serde(rename="Episode")]
+    pub episode: String,
+    #[serde(rename="imdbRating", default, deserialize_with="na_as_none")]
+    pub imdb_rating: Option<String>,
+    #[serde(rename="imdbID")]
+    pub imdb_id: String,
+}
+
+/// All episodes of a season OMDB knows, from one `Season=` request.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct OmdbSeason {
+    #[serde(rename="Title")]
+    pub title: String,
+    #[serde(rename="totalSeasons", default, deserialize_with="na_as_none")]
+    pub total_seasons: Option<String>,
+    #[serde(rename="Episodes", default)]
+    pub episodes: Vec<OmdbSeasonEpisode>,
+}
+
+/// Season listing by series title, or by the series' IMDb id when it is known.
+pub fn omdb_get_season(omdb_api_key: &str, series_title: &str, series_imdb_id: Option<&str>, season: u8) -> Result<OmdbSeason, OmdbError> {
+    let series_param = match series_imdb_id {
+        Some(imdb_id) => ("i", imdb_id.to_string()),
+        None => ("t", series_title.to_string()),
+    };
+    let params = vec![
+        ("apiKey", omdb_api_key.to_string()),
+        series_param,
+        ("Season", season.to_string()),
+    ];
+
+    omdb_request(&params)
+}
+
+/// Episode lookup by series title, or by the series' IMDb id when it is known.
 pub fn omdb_get_episode_metadata(
     omdb_api_key: &str,
     series_title: &str,
+    series_imdb_id: Option<&str>,
     season: u8,
     episode: u8,
-) -> Result<OmdbResponse, Box<dyn error::Error>> {
+) -> Result<OmdbResponse, OmdbError> {
+    let series_param = match series_imdb_id {
+        Some(imdb_id) => ("i", imdb_id.to_string()),
+        None => ("t", series_title.to_string()),
+    };
     let params = vec![
         ("apiKey", omdb_api_key.to_string()),
-        ("t", series_title.to_string()),
+        series_param,
         ("Season", season.to_string()),
         ("Episode", episode.to_string()),
         ("type", OmdbType::Episode.to_string()),
     ];
 
-    let client = reqwest::blocking::Client::new();
-    let resp = client.get(OMDB_API_URL)
-        .query(&params)
-        .send()?
-        .json::<OmdbResponse>()?;
-
+    let resp = omdb_request::<OmdbResponse>(&params)?;
     if let OmdbResponse::Episode { .. } = resp {
         Ok(resp)
     } else {
         log::warn!(target: "cli", "OMDB did not return episode data for {} S{}E{}. Response: {:?}", series_title, season, episode, resp);
-        Err(Box::from(format!("OMDB did not return valid episode data for {} S{}E{}. Check series title, season, and episode numbers.", series_title, season, episode)))
+        Err(OmdbError::Decode(format!("not episode data for {} S{}E{}. Check series title, season, and episode numbers.", series_title, season, episode)))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_omdb_not_available() {
+        let json = r#"{"Title":"Some Show","Year":"2019–","Rated":"N/A","Released":"N/A","Runtime":"N/A","Genre":"Drama",
+            "Director":"N/A","Writer":"N/A","Actors":"","Plot":"N/A","Language":"English","Country":"N/A","Awards":"N/A",
+            "Poster":"N/A","imdbRating":"N/A","imdbID":"tt1234567","Type":"series","totalSeasons":"N/A","Response":"True"}"#;
+        let OmdbResponse::Series { title, year, poster, plot, genre, actors, ratings, total_seasons, .. } = serde_json::from_str(json).unwrap() else {
+            panic!("not a series");
+        };
+        assert_eq!(title, "Some Show");
+        assert_eq!(year.as_deref().and_then(parse_year), Some(2019));
+        assert_eq!((poster, plot, actors, total_seasons), (None, None, None, None));
+        assert_eq!(genre.as_deref(), Some("Drama"));
+        assert!(ratings.is_empty());
+
+        let json = r#"{"Title":"Some Show","Season":"1","totalSeasons":"2","Episodes":[
+            {"Title":"Pilot","Released":"2019-01-06","Episode":"1","imdbRating":"8.1","imdbID":"tt0000001"},
+            {"Title":"Second","Released":"N/A","Episode":"2","imdbRating":"N/A","imdbID":"tt0000002"}],"Response":"True"}"#;
+        let season: OmdbSeason = serde_json::from_str(json).unwrap();
+        assert_eq!(season.total_seasons.as_deref(), Some("2"));
+        assert_eq!(season.episodes[0].imdb_rating.as_deref(), Some("8.1"));
+        assert_eq!(season.episodes[1].released, None);
+        assert_eq!(parse_year("N/A"), None);
+    }
+
+    #[test]
+    fn test_omdb_error() {
+        assert!(matches!(omdb_error("Movie not found!"), OmdbError::NotFound(_)));
+        assert!(matches!(omdb_error("Series or episode not found!"), OmdbError::NotFound(_)));
+        assert!(matches!(omdb_error("Incorrect IMDb ID."), OmdbError::NotFound(_)));
+        assert!(matches!(omdb_error("Request limit reached!"), OmdbError::RateLimited));
+        assert!(omdb_error("Invalid API key!").is_fatal());
+        assert!(!omdb_error("Movie not found!").is_fatal());
+        assert!(matches!(omdb_error("Something else"), OmdbError::Decode(_)));
+    }
+}
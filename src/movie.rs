@@ -2,28 +2,47 @@ use std::path::PathBuf;
 use regex::Regex;
 use url::Url;
 use serde_derive::{Serialize, Deserialize}; // Add Deserialize
-use crate::media::{omdb_get_metadata, MediaInfo, MediaInfoEquiv, OmdbResponse, OmdbType};
+use crate::media::{MediaInfo, MediaInfoEquiv};
+use crate::provider::{self, MetadataProvider, MovieMetadata, TitleQuery};
 use crate::cache::MediaCache; // Import MediaCache
+use crate::nfo::{self, Nfo};
+use crate::overrides::Override;
+use crate::artwork::Artwork;
 use std::hash::{Hash, Hasher}; // For hashing
 use std::collections::hash_map::DefaultHasher; // For hashing
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)] // Add Deserialize and Clone
 pub struct MovieInfo {
     pub name: String,
-    pub year: u16,
-    pub director: String,
+    pub year: Option<u16>,
+    pub director: Option<String>,
     pub path: PathBuf,
     pub info_url: Url,
-    pub poster_url: Url,
-    pub language: String,
-    pub plot: String,
-    pub genre: String,
-    pub runtime: String,
-    pub released: String,
-    pub rated: String,
-    pub actors: String,
-    pub imdb_rating: String,
-    pub rotten_tomatoes_rating: Option<String>
+    pub poster_url: Option<Url>,
+    pub language: Option<String>,
+    pub plot: Option<String>,
+    pub genre: Option<String>,
+    pub runtime: Option<String>,
+    pub released: Option<String>,
+    pub rated: Option<String>,
+    pub actors: Option<String>,
+    pub imdb_rating: Option<String>,
+    pub rotten_tomatoes_rating: Option<String>,
+    /// Local posters and fanart, preferred over `poster_url`
+    #[serde(default)]
+    pub artwork: Artwork,
+    /// Neither a metadata provider nor a complete NFO knew the movie, so only its file name, NFO fields and artwork are shown
+    #[serde(default)]
+    pub unmatched: bool,
+    /// Title in the language asked of TMDB, shown when it differs from `name`
+    #[serde(default)]
+    pub localized_title: Option<String>,
+    /// Provider backdrop, shown when there's no local fanart
+    #[serde(default)]
+    pub backdrop_url: Option<Url>,
+    /// The series of films it's part of
+    #[serde(default)]
+    pub collection: Option<String>,
 }
 
 impl MediaInfoEquiv for MovieInfo {
@@ -32,6 +51,104 @@ impl MediaInfoEquiv for MovieInfo {
     }
 }
 
+/// Placeholder poster URL that older versions cached for movies without a poster; it's never downloaded.
+pub const NO_POSTER_URL: &str = "https://via.placeholder.com/300x450.png?text=No+Poster";
+
+impl MovieInfo {
+    /// Just the name and year of the file name, linking to an IMDb search unless an id is known.
+    fn from_media_info(movie_file_info: MediaInfo) -> MovieInfo {
+        let search_url = Url::parse_with_params("https://www.imdb.com/find", [("q", &movie_file_info.name)]).unwrap();
+        MovieInfo {
+            name: movie_file_info.name,
+            year: movie_file_info.year,
+            director: None,
+            path: movie_file_info.path,
+            info_url: movie_file_info.imdb_id.as_deref().and_then(crate::media::imdb_url).unwrap_or(search_url),
+            poster_url: None,
+            language: None,
+            plot: None,
+            genre: None,
+            runtime: None,
+            released: None,
+            rated: None,
+            actors: None,
+            imdb_rating: None,
+            rotten_tomatoes_rating: None,
+            artwork: Artwork::default(),
+            unmatched: false,
+            localized_title: None,
+            backdrop_url: None,
+            collection: None,
+        }
+    }
+
+    /// Builds the movie info from what the metadata providers found.
+    fn from_metadata(metadata: MovieMetadata, mut movie_file_info: MediaInfo) -> MovieInfo {
+        if metadata.imdb_id.is_some() {
+            movie_file_info.imdb_id = metadata.imdb_id;
+        }
+        let mut movie_info = MovieInfo::from_media_info(movie_file_info);
+        movie_info.name = metadata.title;
+        movie_info.year = metadata.year;
+        movie_info.director = metadata.director;
+        movie_info.poster_url = metadata.poster_url;
+        movie_info.language = metadata.language;
+        movie_info.plot = metadata.plot;
+        movie_info.genre = metadata.genre;
+        movie_info.runtime = metadata.runtime;
+        movie_info.released = metadata.released;
+        movie_info.rated = metadata.rated;
+        movie_info.actors = metadata.actors;
+        movie_info.imdb_rating = metadata.imdb_rating;
+        movie_info.rotten_tomatoes_rating = metadata.rotten_tomatoes_rating;
+        movie_info.localized_title = metadata.localized_title.filter(|t| *t != movie_info.name);
+        movie_info.backdrop_url = metadata.backdrop_url;
+        movie_info.collection = metadata.collection;
+        movie_info
+    }
+
+    /// Builds the movie info from an NFO alone, without OMDB.
+    pub fn from_nfo(nfo: &Nfo, movie_file_info: MediaInfo) -> MovieInfo {
+        let mut movie_info = MovieInfo::from_media_info(movie_file_info);
+        movie_info.apply_nfo(nfo);
+        movie_info
+    }
+
+    /// Builds the info of a movie no metadata source knows, from its file name and whatever its NFO has.
+    pub fn unmatched(movie_file_info: MediaInfo, nfo: Option<&Nfo>) -> MovieInfo {
+        let mut movie_info = MovieInfo::from_media_info(movie_file_info);
+        if let Some(nfo) = nfo {
+            movie_info.apply_nfo(nfo);
+        }
+        movie_info.unmatched = true;
+        movie_info
+    }
+
+    /// Overrides fields with the ones the NFO has.
+    pub fn apply_nfo(&mut self, nfo: &Nfo) {
+        nfo::set(&mut self.name, nfo.title.clone());
+        nfo::set(&mut self.year, nfo.year.map(Some));
+        nfo::set(&mut self.director, nfo.director().map(Some));
+        nfo::set(&mut self.info_url, nfo.imdb_url());
+        nfo::set(&mut self.poster_url, nfo.poster_url.clone().map(Some));
+        nfo::set(&mut self.plot, nfo.plot.clone().map(Some));
+        nfo::set(&mut self.genre, nfo.genre().map(Some));
+        nfo::set(&mut self.runtime, nfo.runtime().map(Some));
+        nfo::set(&mut self.released, nfo.premiered.clone().map(Some));
+        nfo::set(&mut self.rated, nfo.mpaa.clone().map(Some));
+        nfo::set(&mut self.actors, nfo.actor_names().map(Some));
+        nfo::set(&mut self.imdb_rating, nfo.rating.clone().map(Some));
+    }
+
+    /// Overwrites fields with the ones set in an overrides file.
+    pub fn apply_override(&mut self, item_override: &Override) {
+        nfo::set(&mut self.name, item_override.title.clone());
+        nfo::set(&mut self.plot, item_override.plot.clone().map(Some));
+        nfo::set(&mut self.genre, item_override.genre.clone().map(Some));
+        nfo::set(&mut self.poster_url, item_override.poster.as_deref().and_then(|p| Url::parse(p).ok()).map(Some));
+    }
+}
+
 lazy_static! {
     pub static ref MOVIE_PATTERNS_RE: Vec<Regex> = [
         "(?P<name>[a-zA-Z0-9'.]+)\\.(?P<year>(?:19|20)\\d{2})\\..*",
@@ -43,8 +160,13 @@ lazy_static! {
 }
 
 
-pub fn parse_movie_filename(regexs: &Vec<Regex>, path: &PathBuf) -> Option<MediaInfo> {
-    let filename = path.file_stem()?.to_str()?;
+pub fn parse_movie_filename(regexs: &[Regex], path: &PathBuf) -> Option<MediaInfo> {
+    let (filename, file_imdb_id) = crate::media::strip_imdb_hint(path.file_stem()?.to_str()?);
+    let filename = filename.as_str();
+    // Plex and Jellyfin put the hint on the movie's folder
+    let imdb_id = file_imdb_id.or_else(|| {
+        path.parent().and_then(|p| p.file_name()).and_then(|n| n.to_str()).and_then(|n| crate::media::strip_imdb_hint(n).1)
+    });
     let filename_match = regexs.iter().find_map(|re| re.captures(filename))?;
     let name = {
         let n = filename_match.name("name")?.as_str();
@@ -58,110 +180,122 @@ pub fn parse_movie_filename(regexs: &Vec<Regex>, path: &PathBuf) -> Option<Media
 
     log::info!(target: "cli", "Media file discovered: {name:?} ({path:?})");
 
-    Some(MediaInfo{name: name, year: year, path: path.to_owned()})
+    Some(MediaInfo{name, year, path: path.to_owned(), imdb_id})
 }
 
+/// Looks a movie up in its NFO, the cache and the metadata providers; a movie none of them knows is flagged
+/// as unmatched. Fails only on provider errors that would fail every other lookup too, like an invalid key.
 pub fn get_movie_info_logged(
-    omdb_api_key: &str,
+    provider: Option<&dyn MetadataProvider>, // Without one, only NFO files and the cache are used
+    mut movie_file_info: MediaInfo,
+    item_override: Option<&Override>,
+    cache: &Option<MediaCache>,
+) -> Result<MovieInfo, Box<dyn std::error::Error>> {
+    let mut nfo = nfo::find_movie_nfo(&movie_file_info.path);
+    if let Some(imdb_id) = item_override.and_then(|o| o.imdb_id.clone()) {
+        // A forced id wins over the NFO's and the file name's
+        if let Some(nfo) = nfo.as_mut() {
+            nfo.imdb_id = Some(imdb_id.clone());
+        }
+        movie_file_info.imdb_id = Some(imdb_id);
+    }
+    let mut info = match lookup_movie_info(provider, movie_file_info.clone(), nfo.as_ref(), cache) {
+        Ok(info) => info,
+        Err(err) => {
+            if provider::is_fatal(&*err) {
+                return Err(err);
+            } else if provider::is_not_found(&*err) {
+                log::info!(target: "cli", "No metadata provider knows movie {}, using its file name", movie_file_info.name);
+            } else {
+                log::warn!("Failed to get movie info for {}, using its file name. Error: {}", movie_file_info.name, err);
+            }
+            MovieInfo::unmatched(movie_file_info, nfo.as_ref())
+        }
+    };
+    if let Some(item_override) = item_override {
+        info.apply_override(item_override);
+    }
+    Ok(info)
+}
+
+/// NFO, cache, then the metadata providers.
+fn lookup_movie_info(
+    provider: Option<&dyn MetadataProvider>,
     movie_file_info: MediaInfo,
+    nfo: Option<&Nfo>,
     cache: &Option<MediaCache>,
 ) -> Result<MovieInfo, Box<dyn std::error::Error>> {
     let name = movie_file_info.name.clone();
+    if let Some(nfo) = nfo.filter(|nfo| nfo.is_complete()) {
+        log::info!(target: "cli", "Using NFO for movie: {}", name);
+        return Ok(MovieInfo::from_nfo(nfo, movie_file_info));
+    }
     let path_hash = {
         let mut hasher = DefaultHasher::new();
         movie_file_info.path.hash(&mut hasher);
         hasher.finish().to_string()
     };
+    let pinned_url = nfo.and_then(|n| n.imdb_id.as_deref()).or(movie_file_info.imdb_id.as_deref())
+        .and_then(crate::media::imdb_url);
 
-    // Try to get from cache first
+    // Try to get from cache first; an entry from before an IMDb id was pinned to another title doesn't count
     if let Some(media_cache) = cache {
-        if let Some(cached_movie_info) = media_cache.get_movie_by_path_hash(&path_hash).map_err(|e| e.to_string())? {
-            log::info!(target: "cli", "Cache hit for movie (by path_hash {}): {}", path_hash, name);
-            return Ok(cached_movie_info);
+        if let Some(mut cached_movie_info) = media_cache.get_movie_by_path_hash(&path_hash).map_err(|e| e.to_string())? {
+            if pinned_url.as_ref().is_none_or(|url| *url == cached_movie_info.info_url) {
+                log::info!(target: "cli", "Cache hit for movie (by path_hash {}): {}", path_hash, name);
+                if let Some(nfo) = nfo {
+                    cached_movie_info.apply_nfo(nfo);
+                }
+                return Ok(cached_movie_info);
+            }
         }
     }
 
-    log::info!(target: "cli", "Cache miss for movie (by path_hash {}): {}. Fetching from OMDB.", path_hash, name);
-    let movie_info_result = get_movie_info(omdb_api_key, movie_file_info, cache, &path_hash); // Pass cache and path_hash
-
-    match movie_info_result {
-        Ok(info) => Ok(info),
-        Err(err) => {
-            log::warn!("Failed to get movie info for {}, error: {}", name, err);
-            Err(err)
-        }
+    let Some(provider) = provider else {
+        log::info!(target: "cli", "No metadata provider, using the file name for movie: {}", name);
+        return Ok(MovieInfo::unmatched(movie_file_info, nfo));
+    };
+    log::info!(target: "cli", "Cache miss for movie (by path_hash {}): {}. Fetching from {}.", path_hash, name, provider.name());
+    let query = format!("movie:{}:{}", path_hash, pinned_url.as_ref().map(Url::as_str).unwrap_or_default());
+    let mut info = crate::cache::unless_not_found(cache, &query, || {
+        get_movie_info(provider, movie_file_info, nfo, cache, &path_hash) // Pass cache and path_hash
+    })?;
+    if let Some(nfo) = nfo {
+        info.apply_nfo(nfo);
     }
+    Ok(info)
 }
 
 pub fn get_movie_info(
-    omdb_api_key: &str,
+    provider: &dyn MetadataProvider,
     movie_file_info: MediaInfo,
+    nfo: Option<&Nfo>, // Its IMDb id, or its title and year, take precedence over the file name's
     cache: &Option<MediaCache>,
     path_hash: &str, // Added path_hash parameter
 ) -> Result<MovieInfo, Box<dyn std::error::Error>> {
-    let r = omdb_get_metadata(omdb_api_key, OmdbType::Movie, &movie_file_info.name, movie_file_info.year)?;
-    match r {
-        OmdbResponse::Movie { .. } => {
-            let info_url = r.imdb_url();
-            if let OmdbResponse::Movie {
-                title,
-                year,
-                director,
-                poster,
-                language,
-                plot,
-                genre,
-                runtime,
-                released,
-                rated,
-                actors,
-                imdb_rating,
-                ratings,
-                ..
-            } = r
-            {
-                let rotten_tomatoes_rating = ratings.iter()
-                    .find(|r_item| r_item.source == "Rotten Tomatoes")
-                    .map(|r_item| r_item.value.to_string());
-
-                let fetched_movie_info = MovieInfo {
-                    name: title,
-                    year: year.parse()?,
-                    director,
-                    poster_url: Url::parse(&poster)?,
-                    language,
-                    plot,
-                    info_url,
-                    path: movie_file_info.path,
-                    genre,
-                    runtime,
-                    released,
-                    rated,
-                    actors,
-                    imdb_rating,
-                    rotten_tomatoes_rating,
-                };
-
-                // Store in cache
-                if let Some(media_cache) = cache {
-                    if let Err(e) = media_cache.store_movie(&fetched_movie_info, path_hash) {
-                        log::error!("Failed to store movie '{}' in cache: {}", fetched_movie_info.name, e);
-                    } else {
-                        log::info!(target: "cli", "Stored movie '{}' in cache (path_hash: {})", fetched_movie_info.name, path_hash);
-                    }
-                }
-                Ok(fetched_movie_info)
-            } else {
-                unreachable!()
-            }
+    let query = TitleQuery {
+        title: nfo.and_then(|n| n.title.as_deref()).unwrap_or(&movie_file_info.name),
+        year: nfo.and_then(|n| n.year).or(movie_file_info.year),
+        imdb_id: nfo.and_then(|n| n.imdb_id.as_deref()).or(movie_file_info.imdb_id.as_deref()),
+    };
+    let metadata = provider.movie(&query)?;
+    let fetched_movie_info = MovieInfo::from_metadata(metadata, movie_file_info);
+
+    // Store in cache
+    if let Some(media_cache) = cache {
+        if let Err(e) = media_cache.store_movie(&fetched_movie_info, path_hash) {
+            log::error!("Failed to store movie '{}' in cache: {}", fetched_movie_info.name, e);
+        } else {
+            log::info!(target: "cli", "Stored movie '{}' in cache (path_hash: {})", fetched_movie_info.name, path_hash);
         }
-        _ => Err("Wrong OMDB response".into()),
     }
+    Ok(fetched_movie_info)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::movie::{parse_movie_filename, MOVIE_PATTERNS_RE};
+    use crate::overrides::Override;
+    use crate::movie::{get_movie_info_logged, parse_movie_filename, MOVIE_PATTERNS_RE};
     use crate::media::MediaInfo;
     use std::path::Path;
 
@@ -169,7 +303,7 @@ mod tests {
     fn test_movie_name_parsing() {
         fn assert_movie_file_info(path: &str, name: &str, year: Option<u16>) {
             let _path = Path::new(path).to_path_buf();
-            assert_eq!(parse_movie_filename(&MOVIE_PATTERNS_RE, &_path), Some(MediaInfo{path: _path, name: name.into(), year: year}));
+            assert_eq!(parse_movie_filename(&MOVIE_PATTERNS_RE, &_path), Some(MediaInfo{path: _path, name: name.into(), year, imdb_id: None}));
         }
 
         assert_movie_file_info("movies/Journey.To.The.West.Conquering.The.Demons.2013.720p.WEBRip.x264.AC3-JYK.mp4", "Journey To The West Conquering The Demons", Some(2013));
@@ -178,5 +312,49 @@ mod tests {
         assert_movie_file_info("Movies/The Kick [2011].x264.DVDrip(MartialArts).mp4", "The Kick", Some(2011));
         assert_movie_file_info("Movies/Tropic Thunder 2008 Unrated DC 1080p BluRay HEVC H265 5.1 BONE.mp4", "Tropic Thunder", Some(2008));
         assert_movie_file_info("Lesbian Vampire Killers 2009 720p BluRay x264 AAC-Mkvking.mkv", "Lesbian Vampire Killers", Some(2009));
+
+        // IMDb id hints on the file or its folder
+        let info = parse_movie_filename(&MOVIE_PATTERNS_RE, &Path::new("Movies/Dune (2021) [tt1160419].mkv").to_path_buf()).unwrap();
+        assert_eq!((info.name.as_str(), info.year, info.imdb_id.as_deref()), ("Dune", Some(2021), Some("tt1160419")));
+        let info = parse_movie_filename(&MOVIE_PATTERNS_RE, &Path::new("Movies/Dune (1984) {imdb-tt0087182}/Dune.1984.1080p.mkv").to_path_buf()).unwrap();
+        assert_eq!((info.name.as_str(), info.year, info.imdb_id.as_deref()), ("Dune", Some(1984), Some("tt0087182")));
+        let info = parse_movie_filename(&MOVIE_PATTERNS_RE, &Path::new("Movies/Dune.2021.imdbid-tt1160419.2160p.mkv").to_path_buf()).unwrap();
+        assert_eq!((info.name.as_str(), info.imdb_id.as_deref()), ("Dune", Some("tt1160419")));
+    }
+
+    #[test]
+    fn test_movie_info_from_nfo() {
+        let dir = tempfile::Builder::new().prefix("test_movie_nfo").tempdir().unwrap();
+        let path = dir.path().join("Tropic Thunder (2008)/Tropic.Thunder.2008.mkv");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path.parent().unwrap().join("movie.nfo"), "<movie><title>Tropic Thunder</title><plot>Actors at war.</plot>\
+            <director>Ben Stiller</director><uniqueid type=\"imdb\">tt0942385</uniqueid></movie>").unwrap();
+        let file_info = MediaInfo { name: "Tropic Thunder".into(), year: Some(2008), path: path.clone(), imdb_id: None };
+
+        // A complete NFO needs no OMDB key or network
+        let movie_info = get_movie_info_logged(None, file_info, None, &None).unwrap();
+        assert_eq!((movie_info.name.as_str(), movie_info.year), ("Tropic Thunder", Some(2008)));
+        assert_eq!(movie_info.director.as_deref(), Some("Ben Stiller"));
+        assert_eq!(movie_info.plot.as_deref(), Some("Actors at war."));
+        assert_eq!(movie_info.info_url.as_str(), "https://www.imdb.com/title/tt0942385");
+        assert_eq!(movie_info.path, path);
+
+        // Overrides apply on top of it
+        let file_info = MediaInfo { name: "Tropic Thunder".into(), year: Some(2008), path: path.clone(), imdb_id: None };
+        let item_override = Override { title: Some("Tropic Thunder (Director's Cut)".into()), genre: Some("Comedy".into()), ..Default::default() };
+        let movie_info = get_movie_info_logged(None, file_info, Some(&item_override), &None).unwrap();
+        assert_eq!(movie_info.name, "Tropic Thunder (Director's Cut)");
+        assert_eq!(movie_info.genre.as_deref(), Some("Comedy"));
+        assert_eq!(movie_info.plot.as_deref(), Some("Actors at war."));
+        assert!(!movie_info.unmatched);
+
+        // Without an OMDB key or a complete NFO, the file name and partial NFO are used
+        std::fs::write(path.parent().unwrap().join("movie.nfo"), "<movie><director>Ben Stiller</director></movie>").unwrap();
+        let file_info = MediaInfo { name: "Tropic Thunder".into(), year: Some(2008), path: path.clone(), imdb_id: None };
+        let movie_info = get_movie_info_logged(None, file_info, None, &None).unwrap();
+        assert!(movie_info.unmatched);
+        assert_eq!((movie_info.name.as_str(), movie_info.year), ("Tropic Thunder", Some(2008)));
+        assert_eq!(movie_info.director.as_deref(), Some("Ben Stiller"));
+        assert_eq!(movie_info.info_url.as_str(), "https://www.imdb.com/find?q=Tropic+Thunder");
     }
-}
\ No newline at end of file
+}
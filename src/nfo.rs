@@ -0,0 +1,274 @@
+use regex::Regex;
+use roxmltree::{Document, Node};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+lazy_static! {
+    static ref IMDB_ID_RE: Regex = Regex::new(r"\btt\d{7,}\b").unwrap();
+    /// Kodi allows a URL after the XML, and multi-episode files hold several roots; only the first root is parsed.
+    static ref ROOT_END_RE: Regex = Regex::new(r"</(?:movie|tvshow|episodedetails)\s*>").unwrap();
+}
+
+/// Metadata from a Kodi-style `.nfo` file (`<movie>`, `<tvshow>` or `<episodedetails>`).
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Nfo {
+    pub title: Option<String>,
+    pub year: Option<u16>,
+    pub plot: Option<String>,
+    pub genres: Vec<String>,
+    pub directors: Vec<String>,
+    pub actors: Vec<String>,
+    pub countries: Vec<String>,
+    pub runtime_mins: Option<u32>,
+    /// `<premiered>` of movies and shows, `<aired>` of episodes.
+    pub premiered: Option<String>,
+    /// Certification with Kodi's "Rated " or "US:" prefix removed.
+    pub mpaa: Option<String>,
+    /// IMDb rating with one decimal, e.g. `7.1`.
+    pub rating: Option<String>,
+    pub imdb_id: Option<String>,
+    pub poster_url: Option<Url>,
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn text_of(node: Node, name: &str) -> Option<String> {
+    child(node, name).and_then(|n| n.text()).map(str::trim).filter(|t| !t.is_empty()).map(String::from)
+}
+
+fn texts_of(node: Node, name: &str) -> Vec<String> {
+    node.children().filter(|n| n.has_tag_name(name))
+        .filter_map(|n| n.text()).map(str::trim).filter(|t| !t.is_empty()).map(String::from)
+        .collect()
+}
+
+/// Kodi writes ratings like `7.100000`.
+fn format_rating(rating: &str) -> Option<String> {
+    rating.parse::<f32>().ok().filter(|r| *r > 0.0).map(|r| format!("{:.1}", r))
+}
+
+/// The IMDb rating of `<ratings>`, falling back to the default one and to the old `<rating>` element.
+fn rating_of(root: Node) -> Option<String> {
+    let ratings: Vec<Node> = child(root, "ratings").map(|r| r.children().filter(|n| n.has_tag_name("rating")).collect()).unwrap_or_default();
+    ratings.iter().find(|r| r.attribute("name") == Some("imdb"))
+        .or_else(|| ratings.iter().find(|r| r.attribute("default") == Some("true")))
+        .or(ratings.first())
+        .and_then(|r| text_of(*r, "value"))
+        .or_else(|| text_of(root, "rating"))
+        .and_then(|r| format_rating(&r))
+}
+
+fn imdb_id_of(root: Node) -> Option<String> {
+    root.children()
+        .filter(|n| n.has_tag_name("uniqueid") && n.attribute("type") == Some("imdb"))
+        .filter_map(|n| n.text())
+        .chain(["imdbid", "imdb_id", "id"].iter().filter_map(|name| child(root, name).and_then(|n| n.text())))
+        .find_map(|id| IMDB_ID_RE.find(id.trim()).map(|m| m.as_str().to_string()))
+}
+
+/// The first poster `<thumb>`; season posters of `tvshow.nfo` are skipped.
+fn poster_of(root: Node) -> Option<Url> {
+    let thumbs: Vec<Node> = root.children().filter(|n| n.has_tag_name("thumb") && n.attribute("season").is_none()).collect();
+    thumbs.iter().find(|t| t.attribute("aspect") == Some("poster"))
+        .or_else(|| thumbs.iter().find(|t| t.attribute("aspect").is_none()))
+        .and_then(|t| t.text())
+        .and_then(|url| Url::parse(url.trim()).ok())
+        .filter(|url| url.scheme() == "http" || url.scheme() == "https")
+}
+
+/// Parses the contents of an NFO file. Files that are just an IMDb link yield only `imdb_id`.
+pub fn parse_nfo(contents: &str) -> Result<Nfo, roxmltree::Error> {
+    let xml = match ROOT_END_RE.find(contents) {
+        Some(root_end) => &contents[..root_end.end()],
+        None => return Ok(Nfo {
+            imdb_id: IMDB_ID_RE.find(contents).map(|m| m.as_str().to_string()),
+            ..Default::default()
+        }),
+    };
+    let document = Document::parse(xml)?;
+    let root = document.root_element();
+    let premiered = text_of(root, "premiered").or_else(|| text_of(root, "aired"));
+    let year = text_of(root, "year").or_else(|| premiered.clone())
+        .and_then(|y| y.get(..4).and_then(|y| y.parse().ok()));
+    Ok(Nfo {
+        title: text_of(root, "title"),
+        year,
+        plot: text_of(root, "plot").or_else(|| text_of(root, "outline")),
+        genres: texts_of(root, "genre"),
+        directors: texts_of(root, "director"),
+        actors: root.children().filter(|n| n.has_tag_name("actor")).filter_map(|a| text_of(a, "name")).collect(),
+        countries: texts_of(root, "country"),
+        runtime_mins: text_of(root, "runtime").and_then(|r| r.parse().ok()).filter(|r| *r > 0),
+        premiered,
+        mpaa: text_of(root, "mpaa").map(|m| {
+            let m = m.trim_start_matches("Rated ");
+            m.rsplit(':').next().unwrap_or(m).trim().to_string()
+        }),
+        rating: rating_of(root),
+        imdb_id: imdb_id_of(root).or_else(|| IMDB_ID_RE.find(&contents[xml.len()..]).map(|m| m.as_str().to_string())),
+        poster_url: poster_of(root),
+    })
+}
+
+impl Nfo {
+    /// Whether the NFO describes the title well enough to skip OMDB.
+    pub fn is_complete(&self) -> bool {
+        self.title.is_some() && self.plot.is_some()
+    }
+
+    pub fn imdb_url(&self) -> Option<Url> {
+        self.imdb_id.as_deref().and_then(crate::media::imdb_url)
+    }
+
+    pub fn runtime(&self) -> Option<String> {
+        self.runtime_mins.map(|r| format!("{} min", r))
+    }
+
+    pub fn genre(&self) -> Option<String> {
+        joined(&self.genres)
+    }
+
+    pub fn director(&self) -> Option<String> {
+        joined(&self.directors)
+    }
+
+    pub fn actor_names(&self) -> Option<String> {
+        joined(&self.actors)
+    }
+
+    pub fn country(&self) -> Option<String> {
+        joined(&self.countries)
+    }
+}
+
+fn joined(values: &[String]) -> Option<String> {
+    Some(values.join(", ")).filter(|v| !v.is_empty())
+}
+
+/// Overwrites `target` with a value from the NFO, if it has one.
+pub fn set<T>(target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+/// Reads the first existing NFO of `candidates`; unreadable or broken files are logged and skipped.
+fn read_first(candidates: &[PathBuf]) -> Option<Nfo> {
+    for path in candidates.iter().filter(|p| p.is_file()) {
+        let contents = match std::fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                log::warn!(target: "cli", "Could not read {:?}: {}", path, e);
+                continue;
+            }
+        };
+        match parse_nfo(String::from_utf8_lossy(&contents).trim_start_matches('\u{feff}')) {
+            Ok(nfo) => {
+                log::info!(target: "cli", "Read NFO {:?}", path);
+                return Some(nfo);
+            }
+            Err(e) => log::warn!(target: "cli", "Could not parse NFO {:?}: {}", path, e),
+        }
+    }
+    None
+}
+
+/// `<video name>.nfo`, or `movie.nfo` in the movie's folder.
+pub fn find_movie_nfo(video: &Path) -> Option<Nfo> {
+    let folder = video.parent()?;
+    read_first(&[video.with_extension("nfo"), folder.join("movie.nfo")])
+}
+
+/// `tvshow.nfo` in the series folder.
+pub fn find_series_nfo(series_folder: &Path) -> Option<Nfo> {
+    read_first(&[series_folder.join("tvshow.nfo")])
+}
+
+/// `<video name>.nfo`, or `SxxEyy.nfo` next to the episode.
+pub fn find_episode_nfo(video: &Path, season: u8, episode: u8) -> Option<Nfo> {
+    let folder = video.parent()?;
+    let tag = format!("S{:02}E{:02}", season, episode);
+    read_first(&[video.with_extension("nfo"), folder.join(format!("{}.nfo", tag)), folder.join(format!("{}.nfo", tag.to_lowercase()))])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_movie_nfo() {
+        let nfo = parse_nfo(r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<movie>
+    <title>Tropic Thunder</title>
+    <ratings>
+        <rating name="themoviedb" max="10" default="true"><value>6.700000</value></rating>
+        <rating name="imdb" max="10"><value>7.100000</value><votes>424101</votes></rating>
+    </ratings>
+    <plot>Through a series of freak occurrences...</plot>
+    <runtime>107</runtime>
+    <thumb aspect="landscape">https://example.com/landscape.jpg</thumb>
+    <thumb aspect="poster" preview="https://example.com/small.jpg">https://example.com/poster.jpg</thumb>
+    <mpaa>Rated R</mpaa>
+    <uniqueid type="tmdb" default="true">7446</uniqueid>
+    <uniqueid type="imdb">tt0942385</uniqueid>
+    <genre>Action</genre>
+    <genre>Comedy</genre>
+    <country>United States</country>
+    <director>Ben Stiller</director>
+    <premiered>2008-08-13</premiered>
+    <actor><name>Ben Stiller</name><role>Tugg Speedman</role></actor>
+    <actor><name>Jack Black</name><role>Jeff Portnoy</role></actor>
+</movie>
+https://www.imdb.com/title/tt0942385/"#).unwrap();
+        assert_eq!(nfo.title.as_deref(), Some("Tropic Thunder"));
+        assert_eq!(nfo.year, Some(2008));
+        assert_eq!(nfo.rating.as_deref(), Some("7.1"));
+        assert_eq!(nfo.runtime().as_deref(), Some("107 min"));
+        assert_eq!(nfo.mpaa.as_deref(), Some("R"));
+        assert_eq!(nfo.imdb_id.as_deref(), Some("tt0942385"));
+        assert_eq!(nfo.imdb_url().unwrap().as_str(), "https://www.imdb.com/title/tt0942385");
+        assert_eq!(nfo.poster_url.as_ref().unwrap().as_str(), "https://example.com/poster.jpg");
+        assert_eq!(nfo.genre().as_deref(), Some("Action, Comedy"));
+        assert_eq!(nfo.actor_names().as_deref(), Some("Ben Stiller, Jack Black"));
+        assert!(nfo.directors == vec!["Ben Stiller"] && nfo.countries == vec!["United States"]);
+        assert!(nfo.is_complete());
+    }
+
+    #[test]
+    fn test_parse_partial_nfo() {
+        // A link-only NFO just pins the IMDb id
+        let nfo = parse_nfo("https://www.imdb.com/title/tt0942385/\n").unwrap();
+        assert_eq!(nfo, Nfo { imdb_id: Some("tt0942385".into()), ..Default::default() });
+        assert!(!nfo.is_complete());
+
+        let nfo = parse_nfo("<episodedetails><title>Pilot</title><rating>8</rating><aired>2008-01-20</aired><id>tt0959621</id></episodedetails>\n\
+                             <episodedetails><title>Second</title></episodedetails>").unwrap();
+        assert_eq!(nfo.title.as_deref(), Some("Pilot"));
+        assert_eq!(nfo.rating.as_deref(), Some("8.0"));
+        assert_eq!((nfo.year, nfo.premiered.as_deref()), (Some(2008), Some("2008-01-20")));
+        assert_eq!(nfo.imdb_id.as_deref(), Some("tt0959621"));
+        assert!(!nfo.is_complete());
+
+        assert!(parse_nfo("<movie><title>Broken</movie>").is_err());
+    }
+
+    #[test]
+    fn test_find_nfo() {
+        let dir = tempfile::Builder::new().prefix("test_nfo").tempdir().unwrap();
+        let movie = dir.path().join("Movie (2001)/Movie.2001.mkv");
+        std::fs::create_dir_all(movie.parent().unwrap()).unwrap();
+        assert_eq!(find_movie_nfo(&movie), None);
+        std::fs::write(movie.parent().unwrap().join("movie.nfo"), "<movie><title>Folder</title></movie>").unwrap();
+        assert_eq!(find_movie_nfo(&movie).unwrap().title.as_deref(), Some("Folder"));
+        std::fs::write(movie.with_extension("nfo"), "<movie><title>Named</title></movie>").unwrap();
+        assert_eq!(find_movie_nfo(&movie).unwrap().title.as_deref(), Some("Named"));
+
+        let episode = dir.path().join("Show/Season 1/Show.S01E02.mkv");
+        std::fs::create_dir_all(episode.parent().unwrap()).unwrap();
+        std::fs::write(episode.parent().unwrap().join("S01E02.nfo"), "<episodedetails><title>Two</title></episodedetails>").unwrap();
+        assert_eq!(find_episode_nfo(&episode, 1, 2).unwrap().title.as_deref(), Some("Two"));
+        assert_eq!(find_episode_nfo(&episode, 1, 3), None);
+    }
+}
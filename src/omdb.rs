@@ -0,0 +1,191 @@
+use crate::cache::MediaCache;
+use crate::matching::{omdb_lookup, MatchMode};
+use crate::media::{
+    omdb_get_episode_metadata, omdb_get_metadata_by_id, omdb_get_season, parse_year, OmdbError, OmdbRatings, OmdbResponse, OmdbType,
+};
+use crate::provider::{EpisodeMetadata, MetadataProvider, MovieMetadata, SeasonMetadata, SeriesMetadata, TitleQuery};
+use std::error::Error;
+use url::Url;
+
+/// OMDB as a `MetadataProvider`.
+pub struct OmdbProvider {
+    api_key: String,
+    /// How titles without an IMDb id are matched
+    matching: MatchMode,
+    /// Where search matches are recorded
+    cache: Option<MediaCache>,
+}
+
+impl OmdbProvider {
+    pub fn new(api_key: &str, matching: MatchMode, cache: Option<MediaCache>) -> Self {
+        OmdbProvider { api_key: api_key.to_string(), matching, cache }
+    }
+
+    fn lookup(&self, entity_type: OmdbType, query: &TitleQuery) -> Result<OmdbResponse, Box<dyn Error>> {
+        match query.imdb_id {
+            Some(imdb_id) => Ok(omdb_get_metadata_by_id(&self.api_key, imdb_id)?),
+            None => omdb_lookup(&self.api_key, &self.matching, entity_type, query.title, query.year, &self.cache),
+        }
+    }
+}
+
+fn rotten_tomatoes(ratings: &[OmdbRatings]) -> Option<String> {
+    ratings.iter().find(|r| r.source == "Rotten Tomatoes").map(|r| r.value.to_string())
+}
+
+fn poster(poster: Option<String>) -> Option<Url> {
+    poster.as_deref().and_then(|p| Url::parse(p).ok())
+}
+
+/// The movie of an OMDB answer to `query`.
+fn movie_metadata(response: OmdbResponse, query: &TitleQuery) -> Result<MovieMetadata, Box<dyn Error>> {
+    match response {
+        OmdbResponse::Movie {
+            title, year, director, poster: poster_url, language, country, plot, genre, runtime, released, rated, actors,
+            imdb_rating, ratings, imdb_id, ..
+        } => Ok(MovieMetadata {
+            imdb_id: Some(imdb_id),
+            title,
+            year: year.as_deref().and_then(parse_year),
+            director,
+            plot,
+            genre,
+            runtime,
+            released,
+            rated,
+            actors,
+            language,
+            country,
+            imdb_rating,
+            rotten_tomatoes_rating: rotten_tomatoes(&ratings),
+            poster_url: poster(poster_url),
+            ..Default::default()
+        }),
+        _ => Err(OmdbError::Decode(format!("not a movie: {}", query.title)).into()),
+    }
+}
+
+/// The series of an OMDB answer to `query`.
+fn series_metadata(response: OmdbResponse, query: &TitleQuery) -> Result<SeriesMetadata, Box<dyn Error>> {
+    match response {
+        OmdbResponse::Series {
+            title, year, director, poster: poster_url, language, country, plot, genre, runtime, released, rated, actors,
+            imdb_rating, total_seasons, ratings, imdb_id, ..
+        } => Ok(SeriesMetadata {
+            imdb_id: Some(imdb_id),
+            title,
+            year: year.as_deref().and_then(parse_year), // e.g. "2008–2013" or "2008–"
+            director,
+            plot,
+            genre,
+            runtime,
+            released,
+            rated,
+            actors,
+            language,
+            country,
+            imdb_rating,
+            rotten_tomatoes_rating: rotten_tomatoes(&ratings),
+            total_seasons: total_seasons.and_then(|t| t.parse().ok()),
+            poster_url: poster(poster_url),
+            ..Default::default()
+        }),
+        _ => Err(OmdbError::Decode(format!("not a series: {}", query.title)).into()),
+    }
+}
+
+impl MetadataProvider for OmdbProvider {
+    fn name(&self) -> &'static str {
+        "OMDB"
+    }
+
+    fn movie(&self, query: &TitleQuery) -> Result<MovieMetadata, Box<dyn Error>> {
+        movie_metadata(self.lookup(OmdbType::Movie, query)?, query)
+    }
+
+    fn series(&self, query: &TitleQuery) -> Result<SeriesMetadata, Box<dyn Error>> {
+        series_metadata(self.lookup(OmdbType::Series, query)?, query)
+    }
+
+    fn season(&self, series: &TitleQuery, season: u8) -> Result<SeasonMetadata, Box<dyn Error>> {
+        let listing = omdb_get_season(&self.api_key, series.title, series.imdb_id, season)?;
+        Ok(SeasonMetadata {
+            total_seasons: listing.total_seasons.and_then(|t| t.parse().ok()),
+            episodes: listing.episodes.into_iter()
+                .filter_map(|e| Some(EpisodeMetadata {
+                    episode: e.episode.trim().parse().ok()?,
+                    imdb_id: Some(e.imdb_id),
+                    title: Some(e.title),
+                    released: e.released,
+                    imdb_rating: e.imdb_rating,
+                    ..Default::default()
+                }))
+                .collect(),
+        })
+    }
+
+    fn episode(&self, series: &TitleQuery, season: u8, episode: u8, imdb_id: Option<&str>) -> Result<EpisodeMetadata, Box<dyn Error>> {
+        let r = match imdb_id {
+            Some(imdb_id) => omdb_get_metadata_by_id(&self.api_key, imdb_id)?,
+            None => omdb_get_episode_metadata(&self.api_key, series.title, series.imdb_id, season, episode)?,
+        };
+        match r {
+            OmdbResponse::Episode { title, plot, imdb_rating, released, director, imdb_id, .. } => Ok(EpisodeMetadata {
+                episode,
+                imdb_id: Some(imdb_id),
+                title: Some(title),
+                plot,
+                released, // OMDB 'Released' is the air date for episodes
+                imdb_rating,
+                director,
+                still_url: None,
+            }),
+            // Reached when an episode NFO's IMDb id points at something other than an episode
+            _ => Err(OmdbError::Decode(format!("not an episode: {} S{:02}E{:02}", series.title, season, episode)).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata() {
+        let json = r#"{"Title":"Tropic Thunder","Year":"2008","Rated":"R","Released":"13 Aug 2008","Runtime":"107 min",
+            "Genre":"Action, Comedy, War","Director":"Ben Stiller","Actors":"Ben Stiller, Jack Black","Plot":"N/A",
+            "Language":"English, Mandarin","Country":"United States","Poster":"https://m.media-amazon.com/images/M/tropic.jpg",
+            "Ratings":[{"Source":"Internet Movie Database","Value":"7.1/10"},{"Source":"Rotten Tomatoes","Value":"82%"}],
+            "imdbRating":"7.1","imdbID":"tt0942385","Type":"movie","Response":"True"}"#;
+        let query = TitleQuery { title: "Tropic Thunder", year: Some(2008), imdb_id: None };
+        let movie = movie_metadata(serde_json::from_str(json).unwrap(), &query).unwrap();
+        assert_eq!(movie, MovieMetadata {
+            imdb_id: Some("tt0942385".to_string()),
+            title: "Tropic Thunder".to_string(),
+            year: Some(2008),
+            director: Some("Ben Stiller".to_string()),
+            genre: Some("Action, Comedy, War".to_string()),
+            runtime: Some("107 min".to_string()),
+            released: Some("13 Aug 2008".to_string()),
+            rated: Some("R".to_string()),
+            actors: Some("Ben Stiller, Jack Black".to_string()),
+            language: Some("English, Mandarin".to_string()),
+            country: Some("United States".to_string()),
+            imdb_rating: Some("7.1".to_string()),
+            rotten_tomatoes_rating: Some("82%".to_string()),
+            poster_url: Some(Url::parse("https://m.media-amazon.com/images/M/tropic.jpg").unwrap()),
+            ..Default::default()
+        });
+        assert!(series_metadata(serde_json::from_str(json).unwrap(), &query).is_err());
+
+        let json = r#"{"Title":"Breaking Bad","Year":"2008–2013","Genre":"Crime, Drama","Poster":"N/A","Ratings":[],
+            "imdbRating":"9.5","imdbID":"tt0903747","Type":"series","totalSeasons":"5","Response":"True"}"#;
+        let query = TitleQuery { title: "Breaking Bad", year: None, imdb_id: None };
+        let series = series_metadata(serde_json::from_str(json).unwrap(), &query).unwrap();
+        assert_eq!(series.imdb_id.as_deref(), Some("tt0903747"));
+        assert_eq!(series.year, Some(2008));
+        assert_eq!(series.total_seasons, Some(5));
+        assert_eq!((series.poster_url, series.rotten_tomatoes_rating, series.localized_title), (None, None, None));
+        assert!(movie_metadata(serde_json::from_str(json).unwrap(), &query).is_err());
+    }
+}
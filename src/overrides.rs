@@ -0,0 +1,113 @@
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Hand-edited corrections of one movie, series or episode. They're applied on top of OMDB, cache and
+/// NFO data on every run and never stored in the cache.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Override {
+    /// Looks the title up by this IMDb id, in place of an NFO's or the name's.
+    pub imdb_id: Option<String>,
+    pub title: Option<String>,
+    pub plot: Option<String>,
+    /// An image URL, or a local image path (relative to the overrides file).
+    pub poster: Option<String>,
+    pub genre: Option<String>,
+    /// Leaves the item out of the site.
+    pub hidden: bool,
+    /// Marks a folder of a TV library that isn't a series, e.g. `Extras`; nothing below it is indexed.
+    pub not_series: bool,
+}
+
+/// Overrides keyed by the path of a movie or episode file, or a series folder.
+#[derive(Debug, Default)]
+pub struct Overrides {
+    entries: HashMap<PathBuf, Override>,
+}
+
+impl Overrides {
+    /// Parses TOML or, for `.yaml`/`.yml` files, YAML: a table per path.
+    pub fn parse(text: &str, yaml: bool) -> Result<Overrides, Box<dyn Error>> {
+        let entries: HashMap<PathBuf, Override> = if yaml {
+            serde_yaml::from_str(text)?
+        } else {
+            toml::from_str(text)?
+        };
+        Ok(Overrides { entries })
+    }
+
+    /// Reads an overrides file; relative poster paths in it are resolved against its folder.
+    pub fn load(path: &Path) -> Result<Overrides, Box<dyn Error>> {
+        let yaml = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("yaml") || e.eq_ignore_ascii_case("yml"));
+        let mut overrides = Overrides::parse(&std::fs::read_to_string(path)?, yaml)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for entry in overrides.entries.values_mut() {
+            if let Some(poster) = entry.poster.as_mut().filter(|p| !p.contains("://")) {
+                *poster = base.join(&*poster).to_string_lossy().into_owned();
+            }
+        }
+        Ok(overrides)
+    }
+
+    /// The override of `path`, keyed by the full path or by the path within its library folder.
+    pub fn get(&self, library_folder: &Path, path: &Path) -> Option<&Override> {
+        self.entries.get(path)
+            .or_else(|| path.strip_prefix(library_folder).ok().and_then(|relative| self.entries.get(relative)))
+    }
+
+    /// Whether `path` is hidden, or inside a folder of the library that is marked as not a series.
+    pub fn excludes(&self, library_folder: &Path, path: &Path) -> bool {
+        if self.get(library_folder, path).is_some_and(|o| o.hidden) {
+            return true;
+        }
+        path.ancestors()
+            .take_while(|folder| folder.starts_with(library_folder) && *folder != library_folder)
+            .any(|folder| self.get(library_folder, folder).is_some_and(|o| o.not_series))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_get() {
+        let toml = r#"
+            ["Dune (1984)/Dune.1984.1080p.mkv"]
+            imdb_id = "tt0087182"
+            title = "Dune"
+
+            ["/media/tv/Extras"]
+            not_series = true
+
+            ["Show/Season 1/Show.S01E02.mkv"]
+            hidden = true
+        "#;
+        let yaml = "
+            Dune (1984)/Dune.1984.1080p.mkv:
+              imdb_id: tt0087182
+              title: Dune
+            /media/tv/Extras:
+              not_series: true
+            Show/Season 1/Show.S01E02.mkv:
+              hidden: true
+        ";
+        for overrides in [Overrides::parse(toml, false).unwrap(), Overrides::parse(yaml, true).unwrap()] {
+            let movies = Path::new("/media/movies");
+            let dune = overrides.get(movies, Path::new("/media/movies/Dune (1984)/Dune.1984.1080p.mkv")).unwrap();
+            assert_eq!(dune.imdb_id.as_deref(), Some("tt0087182"));
+            assert_eq!(dune.title.as_deref(), Some("Dune"));
+            assert_eq!(dune.plot, None);
+            assert_eq!(overrides.get(movies, Path::new("/media/movies/Other.2001.1080p.mkv")), None);
+
+            let tv = Path::new("/media/tv");
+            assert!(overrides.excludes(tv, Path::new("/media/tv/Extras")));
+            assert!(overrides.excludes(tv, Path::new("/media/tv/Extras/Making Of/S01E01.mkv")));
+            assert!(overrides.excludes(tv, Path::new("/media/tv/Show/Season 1/Show.S01E02.mkv")));
+            assert!(!overrides.excludes(tv, Path::new("/media/tv/Show/Season 1/Show.S01E01.mkv")));
+        }
+        assert!(Overrides::parse("[\"a.mkv\"]\nimdb = \"tt0087182\"", false).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use crate::cache::MediaCache;
+use crate::transcode::{Ffmpeg, StreamCodecs};
+use serde_derive::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::UNIX_EPOCH;
+
+/// Technical details of a media file as reported by ffprobe.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct MediaProbe {
+    /// Length in seconds.
+    pub duration_secs: Option<f64>,
+    /// ffprobe's format name, e.g. `matroska,webm` or `mov,mp4,m4a,3gp,3g2,mj2`.
+    pub container: Option<String>,
+    /// The main video stream; cover art doesn't count.
+    pub video: Option<VideoTrack>,
+    pub audio_tracks: Vec<AudioTrack>,
+    /// Subtitle streams embedded in the file.
+    pub subtitle_tracks: Vec<SubtitleTrack>,
+    /// Size in bytes.
+    pub file_size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct VideoTrack {
+    pub codec: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// PQ (HDR10, Dolby Vision) or HLG transfer.
+    pub hdr: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct AudioTrack {
+    pub codec: String,
+    /// ISO 639-2 language tag, e.g. `eng`.
+    pub language: Option<String>,
+    pub channels: Option<u32>,
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct SubtitleTrack {
+    /// Stream index within the file, as ffmpeg's `-map 0:<index>` expects.
+    pub index: u32,
+    pub codec: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub forced: bool,
+}
+
+impl MediaProbe {
+    /// Codecs of the main video stream and the first audio track.
+    pub fn codecs(&self) -> StreamCodecs {
+        StreamCodecs {
+            video: self.video.as_ref().map(|v| v.codec.clone()),
+            audio: self.audio_tracks.first().map(|a| a.codec.clone()),
+        }
+    }
+}
+
+fn tag(stream: &serde_json::Value, name: &str) -> Option<String> {
+    stream["tags"][name].as_str().map(String::from)
+}
+
+fn number<T: TryFrom<u64>>(value: &serde_json::Value) -> Option<T> {
+    value.as_u64().and_then(|n| T::try_from(n).ok())
+}
+
+/// Parses the JSON `ffprobe -show_format -show_streams` prints.
+fn parse_probe_output(json: &[u8], file_size: u64) -> io::Result<MediaProbe> {
+    let value: serde_json::Value = serde_json::from_slice(json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut probe = MediaProbe {
+        duration_secs: value["format"]["duration"].as_str().and_then(|d| d.parse().ok()),
+        container: value["format"]["format_name"].as_str().map(String::from),
+        file_size,
+        ..Default::default()
+    };
+    for stream in value["streams"].as_array().into_iter().flatten() {
+        let codec = stream["codec_name"].as_str().unwrap_or("unknown").to_string();
+        match stream["codec_type"].as_str() {
+            // Cover art is reported as a video stream too
+            Some("video") if probe.video.is_none() && stream["disposition"]["attached_pic"].as_i64() != Some(1) => {
+                let transfer = stream["color_transfer"].as_str().unwrap_or_default();
+                probe.video = Some(VideoTrack {
+                    codec,
+                    width: number(&stream["width"]),
+                    height: number(&stream["height"]),
+                    hdr: transfer == "smpte2084" || transfer == "arib-std-b67",
+                });
+            }
+            Some("audio") => probe.audio_tracks.push(AudioTrack {
+                codec,
+                language: tag(stream, "language"),
+                channels: number(&stream["channels"]),
+                title: tag(stream, "title"),
+            }),
+            Some("subtitle") => probe.subtitle_tracks.push(SubtitleTrack {
+                index: number(&stream["index"]).unwrap_or_default(),
+                codec,
+                language: tag(stream, "language"),
+                title: tag(stream, "title"),
+                forced: stream["disposition"]["forced"].as_i64() == Some(1),
+            }),
+            _ => {}
+        }
+    }
+    Ok(probe)
+}
+
+impl Ffmpeg {
+    /// Reads the container and stream details of `path` with ffprobe.
+    pub fn probe(&self, path: &Path) -> io::Result<MediaProbe> {
+        let file_size = std::fs::metadata(path)?.len();
+        let output = Command::new(&self.ffprobe)
+            .args(["-v", "error", "-show_format", "-show_streams", "-of", "json"])
+            .arg(path)
+            .stdin(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!("ffprobe failed for {:?}: {}", path, String::from_utf8_lossy(&output.stderr).trim())));
+        }
+        parse_probe_output(&output.stdout, file_size)
+    }
+}
+
+/// Modification time used to key cached probes, in nanoseconds since the epoch.
+fn mtime_key(path: &Path) -> io::Result<i64> {
+    let mtime = std::fs::metadata(path)?.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok(mtime.as_nanos() as i64)
+}
+
+/// Probes `path`, reusing the cached result while the file's mtime is unchanged.
+///
+/// Returns None (after logging) when the file can't be probed, e.g. without ffprobe.
+pub fn probe_cached(ffmpeg: &Ffmpeg, cache: &Option<MediaCache>, path: &Path) -> Option<MediaProbe> {
+    let mtime = match mtime_key(path) {
+        Ok(mtime) => mtime,
+        Err(e) => {
+            log::warn!(target: "cli", "Could not stat {:?}: {}", path, e);
+            return None;
+        }
+    };
+    if let Some(media_cache) = cache {
+        match media_cache.get_probe(path, mtime) {
+            Ok(Some(probe)) => return Some(probe),
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to read probe of {:?} from cache: {}", path, e),
+        }
+    }
+
+    match ffmpeg.probe(path) {
+        Ok(probe) => {
+            log::info!(target: "cli", "Probed {:?}", path);
+            if let Some(media_cache) = cache {
+                if let Err(e) = media_cache.store_probe(path, mtime, &probe) {
+                    log::error!("Failed to store probe of {:?} in cache: {}", path, e);
+                }
+            }
+            Some(probe)
+        }
+        // A missing ffprobe is a supported setup, not worth a warning per file
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            log::debug!(target: "cli", "ffprobe not available, skipping probe of {:?}", path);
+            None
+        }
+        Err(e) => {
+            log::warn!(target: "cli", "Could not probe {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_probe_output() {
+        let json = br#"{
+            "streams": [
+                {"index": 0, "codec_name": "mjpeg", "codec_type": "video", "disposition": {"attached_pic": 1}},
+                {"index": 1, "codec_name": "hevc", "codec_type": "video", "width": 3840, "height": 2160,
+                 "color_transfer": "smpte2084", "disposition": {"attached_pic": 0}},
+                {"index": 2, "codec_name": "eac3", "codec_type": "audio", "channels": 6, "tags": {"language": "eng", "title": "Surround"}},
+                {"index": 3, "codec_name": "aac", "codec_type": "audio", "channels": 2, "tags": {"language": "fra"}},
+                {"index": 4, "codec_name": "subrip", "codec_type": "subtitle", "tags": {"language": "eng"}, "disposition": {"forced": 1}},
+                {"index": 5, "codec_name": "hdmv_pgs_subtitle", "codec_type": "subtitle"}
+            ],
+            "format": {"format_name": "matroska,webm", "duration": "5400.250000"}
+        }"#;
+        let probe = parse_probe_output(json, 1234).unwrap();
+        assert_eq!(probe.duration_secs, Some(5400.25));
+        assert_eq!(probe.container.as_deref(), Some("matroska,webm"));
+        assert_eq!(probe.file_size, 1234);
+        assert_eq!(probe.video, Some(VideoTrack { codec: "hevc".into(), width: Some(3840), height: Some(2160), hdr: true }));
+        assert_eq!(probe.audio_tracks.len(), 2);
+        assert_eq!(probe.audio_tracks[0], AudioTrack {
+            codec: "eac3".into(), language: Some("eng".into()), channels: Some(6), title: Some("Surround".into()),
+        });
+        assert_eq!(probe.subtitle_tracks[0], SubtitleTrack {
+            index: 4, codec: "subrip".into(), language: Some("eng".into()), title: None, forced: true,
+        });
+        assert_eq!(probe.subtitle_tracks[1].language, None);
+        assert_eq!(probe.codecs(), StreamCodecs { video: Some("hevc".into()), audio: Some("eac3".into()) });
+
+        assert!(parse_probe_output(b"not json", 0).is_err());
+        assert_eq!(parse_probe_output(b"{}", 0).unwrap(), MediaProbe::default());
+    }
+
+    #[test]
+    fn test_probe_cache() {
+        let dir = tempfile::Builder::new().prefix("test_probe").tempdir().unwrap();
+        let media = dir.path().join("movie.mkv");
+        std::fs::write(&media, b"movie").unwrap();
+        let cache = Some(MediaCache::new(&dir.path().join("cache.sqlite")).unwrap());
+        let no_ffprobe = Ffmpeg { ffmpeg: "/nonexistent/ffmpeg".into(), ffprobe: "/nonexistent/ffprobe".into() };
+        assert_eq!(probe_cached(&no_ffprobe, &cache, &media), None);
+
+        // A cached probe is used as long as the mtime matches
+        let probe = MediaProbe { duration_secs: Some(60.0), file_size: 5, ..Default::default() };
+        let mtime = mtime_key(&media).unwrap();
+        cache.as_ref().unwrap().store_probe(&media, mtime, &probe).unwrap();
+        assert_eq!(probe_cached(&no_ffprobe, &cache, &media), Some(probe));
+        cache.as_ref().unwrap().store_probe(&media, mtime - 1, &MediaProbe::default()).unwrap();
+        assert_eq!(probe_cached(&no_ffprobe, &cache, &media), None);
+    }
+}
@@ -0,0 +1,409 @@
+use crate::media::OmdbError;
+use crate::tmdb::TmdbError;
+use serde_derive::{Deserialize, Serialize};
+use std::error::Error;
+use url::Url;
+
+/// What a title is looked up by; an IMDb id, when known, wins over the title and year.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TitleQuery<'a> {
+    pub title: &'a str,
+    pub year: Option<u16>,
+    pub imdb_id: Option<&'a str>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MovieMetadata {
+    pub imdb_id: Option<String>,
+    pub title: String,
+    /// Title in the language asked of the provider, e.g. `--tmdb-language`.
+    pub localized_title: Option<String>,
+    pub year: Option<u16>,
+    pub director: Option<String>,
+    pub plot: Option<String>,
+    pub genre: Option<String>,
+    pub runtime: Option<String>,
+    pub released: Option<String>,
+    pub rated: Option<String>,
+    pub actors: Option<String>,
+    pub language: Option<String>,
+    pub country: Option<String>,
+    pub imdb_rating: Option<String>,
+    pub rotten_tomatoes_rating: Option<String>,
+    pub poster_url: Option<Url>,
+    pub backdrop_url: Option<Url>,
+    /// The series of films it's part of, e.g. `Dune Collection`.
+    pub collection: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SeriesMetadata {
+    pub imdb_id: Option<String>,
+    pub title: String,
+    pub localized_title: Option<String>,
+    pub year: Option<u16>,
+    pub director: Option<String>,
+    pub plot: Option<String>,
+    pub genre: Option<String>,
+    pub runtime: Option<String>,
+    pub released: Option<String>,
+    pub rated: Option<String>,
+    pub actors: Option<String>,
+    pub language: Option<String>,
+    pub country: Option<String>,
+    pub imdb_rating: Option<String>,
+    pub rotten_tomatoes_rating: Option<String>,
+    pub total_seasons: Option<u8>,
+    pub poster_url: Option<Url>,
+    pub backdrop_url: Option<Url>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EpisodeMetadata {
+    pub episode: u8,
+    pub imdb_id: Option<String>,
+    pub title: Option<String>,
+    pub plot: Option<String>,
+    /// Air date; `YYYY-MM-DD` in season listings.
+    pub released: Option<String>,
+    pub imdb_rating: Option<String>,
+    pub director: Option<String>,
+    /// A frame of the episode.
+    pub still_url: Option<Url>,
+}
+
+/// The episodes of a season a provider knows, aired or announced.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SeasonMetadata {
+    pub total_seasons: Option<u8>,
+    pub episodes: Vec<EpisodeMetadata>,
+}
+
+impl SeasonMetadata {
+    pub fn episode(&self, episode: u8) -> Option<&EpisodeMetadata> {
+        self.episodes.iter().find(|e| e.episode == episode)
+    }
+}
+
+/// A source of movie and TV metadata. Lookups fail with the provider's own error type;
+/// `is_not_found` and `is_fatal` tell the kinds apart.
+pub trait MetadataProvider {
+    fn name(&self) -> &'static str;
+    fn movie(&self, query: &TitleQuery) -> Result<MovieMetadata, Box<dyn Error>>;
+    fn series(&self, query: &TitleQuery) -> Result<SeriesMetadata, Box<dyn Error>>;
+    fn season(&self, series: &TitleQuery, season: u8) -> Result<SeasonMetadata, Box<dyn Error>>;
+    /// `imdb_id` is the episode's own, e.g. from its NFO.
+    fn episode(&self, series: &TitleQuery, season: u8, episode: u8, imdb_id: Option<&str>) -> Result<EpisodeMetadata, Box<dyn Error>>;
+}
+
+/// Whether a provider doesn't know the title, as opposed to failing to answer.
+pub fn is_not_found(err: &(dyn Error + 'static)) -> bool {
+    matches!(err.downcast_ref::<OmdbError>(), Some(OmdbError::NotFound(_)))
+        || matches!(err.downcast_ref::<TmdbError>(), Some(TmdbError::NotFound(_)))
+}
+
+/// Whether every further lookup would fail the same way, like with an invalid key.
+pub fn is_fatal(err: &(dyn Error + 'static)) -> bool {
+    err.downcast_ref::<OmdbError>().is_some_and(OmdbError::is_fatal)
+        || err.downcast_ref::<TmdbError>().is_some_and(TmdbError::is_fatal)
+}
+
+/// Metadata that a lower priority provider can complete.
+trait Merge {
+    fn imdb_id(&self) -> Option<&str>;
+    /// Sets the fields `self` lacks from `other`.
+    fn fill(&mut self, other: Self);
+}
+
+macro_rules! fill_fields {
+    ($target:expr, $other:expr, $($field:ident),+) => {
+        $(if $target.$field.is_none() { $target.$field = $other.$field; })+
+    };
+}
+
+impl Merge for MovieMetadata {
+    fn imdb_id(&self) -> Option<&str> {
+        self.imdb_id.as_deref()
+    }
+
+    fn fill(&mut self, other: Self) {
+        fill_fields!(self, other, imdb_id, localized_title, year, director, plot, genre, runtime, released, rated, actors,
+            language, country, imdb_rating, rotten_tomatoes_rating, poster_url, backdrop_url, collection);
+    }
+}
+
+impl Merge for SeriesMetadata {
+    fn imdb_id(&self) -> Option<&str> {
+        self.imdb_id.as_deref()
+    }
+
+    fn fill(&mut self, other: Self) {
+        fill_fields!(self, other, imdb_id, localized_title, year, director, plot, genre, runtime, released, rated, actors,
+            language, country, imdb_rating, rotten_tomatoes_rating, total_seasons, poster_url, backdrop_url);
+    }
+}
+
+impl Merge for EpisodeMetadata {
+    fn imdb_id(&self) -> Option<&str> {
+        self.imdb_id.as_deref()
+    }
+
+    fn fill(&mut self, other: Self) {
+        fill_fields!(self, other, imdb_id, title, plot, released, imdb_rating, director, still_url);
+    }
+}
+
+impl Merge for SeasonMetadata {
+    fn imdb_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// Fills the episodes both know, and adds the ones only `other` knows.
+    fn fill(&mut self, other: Self) {
+        fill_fields!(self, other, total_seasons);
+        for episode in other.episodes {
+            match self.episodes.iter_mut().find(|e| e.episode == episode.episode) {
+                Some(known) => known.fill(episode),
+                None => self.episodes.push(episode),
+            }
+        }
+        self.episodes.sort_by_key(|e| e.episode);
+    }
+}
+
+/// Providers in priority order: the first that knows a title gives its metadata, and the others fill
+/// in what it lacks, looking the title up by the IMDb id found so far.
+pub struct ProviderChain {
+    providers: Vec<Box<dyn MetadataProvider>>,
+}
+
+impl ProviderChain {
+    pub fn new(providers: Vec<Box<dyn MetadataProvider>>) -> Self {
+        ProviderChain { providers }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.providers.iter().map(|p| p.name()).collect()
+    }
+
+    /// Runs `lookup` on each provider, passing the IMDb id found by the ones before.
+    fn lookup<T: Merge>(
+        &self,
+        what: &str,
+        lookup: impl Fn(&dyn MetadataProvider, Option<&str>) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut found: Option<T> = None;
+        let mut error: Option<Box<dyn Error>> = None;
+        for provider in &self.providers {
+            let imdb_id = found.as_ref().and_then(|f| f.imdb_id().map(String::from));
+            match lookup(provider.as_ref(), imdb_id.as_deref()) {
+                Ok(metadata) => match found.as_mut() {
+                    Some(found) => found.fill(metadata),
+                    None => found = Some(metadata),
+                },
+                Err(e) if is_fatal(&*e) => return Err(e),
+                Err(e) => {
+                    log::info!(target: "cli", "{} has no metadata for {}: {}", provider.name(), what, e);
+                    // "Not found" only when no provider failed otherwise, as it's remembered in the cache
+                    if error.as_ref().is_none_or(|first| is_not_found(&**first) && !is_not_found(&*e)) {
+                        error = Some(e);
+                    }
+                }
+            }
+        }
+        found.ok_or_else(|| error.unwrap_or_else(|| "no metadata provider configured".into()))
+    }
+}
+
+impl MetadataProvider for ProviderChain {
+    fn name(&self) -> &'static str {
+        "metadata providers"
+    }
+
+    fn movie(&self, query: &TitleQuery) -> Result<MovieMetadata, Box<dyn Error>> {
+        self.lookup(&format!("movie '{}'", query.title), |provider, imdb_id| {
+            provider.movie(&TitleQuery { imdb_id: query.imdb_id.or(imdb_id), ..*query })
+        })
+    }
+
+    fn series(&self, query: &TitleQuery) -> Result<SeriesMetadata, Box<dyn Error>> {
+        self.lookup(&format!("series '{}'", query.title), |provider, imdb_id| {
+            provider.series(&TitleQuery { imdb_id: query.imdb_id.or(imdb_id), ..*query })
+        })
+    }
+
+    fn season(&self, series: &TitleQuery, season: u8) -> Result<SeasonMetadata, Box<dyn Error>> {
+        self.lookup(&format!("'{}' S{:02}", series.title, season), |provider, _| provider.season(series, season))
+    }
+
+    fn episode(&self, series: &TitleQuery, season: u8, episode: u8, imdb_id: Option<&str>) -> Result<EpisodeMetadata, Box<dyn Error>> {
+        self.lookup(&format!("'{}' S{:02}E{:02}", series.title, season, episode), |provider, found_id| {
+            provider.episode(series, season, episode, imdb_id.or(found_id))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Answers every lookup with its metadata, or the error of `error` when it has none.
+    struct Stub {
+        movie: Option<MovieMetadata>,
+        season: Option<SeasonMetadata>,
+        error: fn() -> OmdbError,
+        /// The IMDb ids it was asked for
+        queried: Rc<RefCell<Vec<Option<String>>>>,
+    }
+
+    impl Stub {
+        fn found(movie: MovieMetadata) -> Self {
+            Stub { movie: Some(movie), season: None, error: || OmdbError::NotFound("Movie not found!".to_string()), queried: Rc::default() }
+        }
+
+        fn failing(error: fn() -> OmdbError) -> Self {
+            Stub { movie: None, season: None, error, queried: Rc::default() }
+        }
+    }
+
+    impl MetadataProvider for Stub {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        fn movie(&self, query: &TitleQuery) -> Result<MovieMetadata, Box<dyn Error>> {
+            self.queried.borrow_mut().push(query.imdb_id.map(String::from));
+            self.movie.clone().ok_or_else(|| (self.error)().into())
+        }
+
+        fn series(&self, _: &TitleQuery) -> Result<SeriesMetadata, Box<dyn Error>> {
+            Err((self.error)().into())
+        }
+
+        fn season(&self, _: &TitleQuery, _: u8) -> Result<SeasonMetadata, Box<dyn Error>> {
+            self.season.clone().ok_or_else(|| (self.error)().into())
+        }
+
+        fn episode(&self, _: &TitleQuery, _: u8, _: u8, _: Option<&str>) -> Result<EpisodeMetadata, Box<dyn Error>> {
+            Err((self.error)().into())
+        }
+    }
+
+    const QUERY: TitleQuery = TitleQuery { title: "Dune", year: Some(2021), imdb_id: None };
+
+    fn first() -> MovieMetadata {
+        MovieMetadata {
+            imdb_id: Some("tt1160419".to_string()),
+            title: "Dune".to_string(),
+            plot: Some("Paul Atreides arrives on Arrakis.".to_string()),
+            imdb_rating: Some("8.0".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn second() -> MovieMetadata {
+        MovieMetadata {
+            title: "Dune: Part One".to_string(),
+            localized_title: Some("Der Wüstenplanet".to_string()),
+            plot: Some("Der Sohn einer adligen Familie...".to_string()),
+            collection: Some("Dune Collection".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn chain(providers: Vec<Stub>) -> ProviderChain {
+        ProviderChain::new(providers.into_iter().map(|p| Box::new(p) as Box<dyn MetadataProvider>).collect())
+    }
+
+    #[test]
+    fn test_fill_missing_fields() {
+        let (a, b) = (Stub::found(first()), Stub::found(second()));
+        let (a_queried, b_queried) = (a.queried.clone(), b.queried.clone());
+        let movie = chain(vec![a, b]).movie(&QUERY).unwrap();
+        // The first provider's fields win, the second only fills the gaps
+        assert_eq!(movie, MovieMetadata {
+            localized_title: Some("Der Wüstenplanet".to_string()),
+            collection: Some("Dune Collection".to_string()),
+            ..first()
+        });
+        // The second is asked by the IMDb id the first found
+        assert_eq!(*a_queried.borrow(), vec![None]);
+        assert_eq!(*b_queried.borrow(), vec![Some("tt1160419".to_string())]);
+
+        // In the other order, the other fields win
+        let movie = chain(vec![Stub::found(second()), Stub::found(first())]).movie(&QUERY).unwrap();
+        assert_eq!(movie.title, "Dune: Part One");
+        assert_eq!(movie.plot.as_deref(), Some("Der Sohn einer adligen Familie..."));
+        assert_eq!(movie.imdb_id.as_deref(), Some("tt1160419"));
+
+        // An IMDb id in the query is passed to all
+        let b = Stub::found(second());
+        let b_queried = b.queried.clone();
+        chain(vec![Stub::found(first()), b]).movie(&TitleQuery { imdb_id: Some("tt0087182"), ..QUERY }).unwrap();
+        assert_eq!(*b_queried.borrow(), vec![Some("tt0087182".to_string())]);
+    }
+
+    #[test]
+    fn test_not_found() {
+        let b = Stub::found(second());
+        let b_queried = b.queried.clone();
+        let movie = chain(vec![Stub::failing(|| OmdbError::NotFound("Movie not found!".to_string())), b]).movie(&QUERY).unwrap();
+        assert_eq!(movie, second());
+        assert_eq!(*b_queried.borrow(), vec![None]);
+    }
+
+    #[test]
+    fn test_errors() {
+        // A fatal error stops the lookup, even after a provider found the title
+        let b = Stub::found(second());
+        let b_queried = b.queried.clone();
+        let e = chain(vec![Stub::failing(|| OmdbError::InvalidKey), b]).movie(&QUERY).unwrap_err();
+        assert!(is_fatal(&*e));
+        assert!(b_queried.borrow().is_empty());
+        assert!(chain(vec![Stub::found(first()), Stub::failing(|| OmdbError::InvalidKey)]).movie(&QUERY).is_err());
+
+        // When all fail, "not found" only if none failed otherwise
+        let not_found = || OmdbError::NotFound("Movie not found!".to_string());
+        for providers in [
+            vec![Stub::failing(not_found), Stub::failing(|| OmdbError::RateLimited)],
+            vec![Stub::failing(|| OmdbError::RateLimited), Stub::failing(not_found)],
+        ] {
+            let e = chain(providers).movie(&QUERY).unwrap_err();
+            assert!(matches!(e.downcast_ref::<OmdbError>(), Some(OmdbError::RateLimited)));
+        }
+        let e = chain(vec![Stub::failing(not_found), Stub::failing(not_found)]).movie(&QUERY).unwrap_err();
+        assert!(is_not_found(&*e));
+        // Without providers
+        let e = chain(vec![]).movie(&QUERY).unwrap_err();
+        assert_eq!(e.to_string(), "no metadata provider configured");
+    }
+
+    #[test]
+    fn test_season_merge() {
+        let episode = |episode, imdb_id: Option<&str>, title: Option<&str>| EpisodeMetadata {
+            episode,
+            imdb_id: imdb_id.map(String::from),
+            title: title.map(String::from),
+            ..Default::default()
+        };
+        let a = Stub {
+            season: Some(SeasonMetadata { total_seasons: None, episodes: vec![episode(1, None, Some("Pilot")), episode(3, None, Some("Third"))] }),
+            ..Stub::failing(|| OmdbError::RateLimited)
+        };
+        let b = Stub {
+            season: Some(SeasonMetadata { total_seasons: Some(5), episodes: vec![episode(1, Some("tt0959621"), Some("Pilot (1)")), episode(2, None, None)] }),
+            ..Stub::failing(|| OmdbError::RateLimited)
+        };
+        let season = chain(vec![a, b]).season(&QUERY, 1).unwrap();
+        assert_eq!(season, SeasonMetadata {
+            total_seasons: Some(5),
+            episodes: vec![episode(1, Some("tt0959621"), Some("Pilot")), episode(2, None, None), episode(3, None, Some("Third"))],
+        });
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Extensions treated as video files unless `--video-extensions` says otherwise.
+pub const DEFAULT_VIDEO_EXTENSIONS: &[&str] = &["mkv", "mp4", "m4v", "avi", "mov", "wmv", "flv", "webm"];
+/// How deep below a library folder videos are looked for, e.g. `Collection/Movie (2001)/movie.mkv` is 3.
+pub const DEFAULT_SCAN_DEPTH: usize = 3;
+
+/// Files commonly kept next to videos; skipping them isn't worth more than a debug line.
+const COMPANION_EXTENSIONS: &[&str] = &["srt", "vtt", "ass", "ssa", "sub", "idx", "nfo", "jpg", "jpeg", "png", "txt"];
+
+/// Why a file found while scanning isn't indexed.
+#[derive(Debug, PartialEq)]
+pub enum SkipReason {
+    NotVideo,
+    Hidden,
+    /// Scene release samples, e.g. `movie-sample.mkv` or anything in a `Sample` folder.
+    Sample,
+    TooDeep(usize),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::NotVideo => write!(f, "not a video file extension"),
+            SkipReason::Hidden => write!(f, "hidden file"),
+            SkipReason::Sample => write!(f, "sample clip"),
+            SkipReason::TooDeep(depth) => write!(f, "deeper than the scan depth of {}", depth),
+        }
+    }
+}
+
+/// Decides which files of a library folder are videos to index; shared by the movie and TV scanners.
+#[derive(Debug, Clone)]
+pub struct VideoScanner {
+    extensions: Vec<String>,
+    /// Maximum depth of a video below the library folder.
+    pub max_depth: usize,
+}
+
+impl Default for VideoScanner {
+    fn default() -> Self {
+        VideoScanner::new(DEFAULT_VIDEO_EXTENSIONS.iter().map(|e| e.to_string()).collect(), DEFAULT_SCAN_DEPTH)
+    }
+}
+
+impl VideoScanner {
+    pub fn new(extensions: Vec<String>, max_depth: usize) -> Self {
+        let extensions = extensions.iter().map(|e| e.trim().trim_start_matches('.').to_lowercase()).filter(|e| !e.is_empty()).collect();
+        VideoScanner { extensions, max_depth }
+    }
+
+    /// Checks a file by name; `Ok` means it should be indexed.
+    pub fn classify(&self, path: &Path) -> Result<(), SkipReason> {
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        if file_name.starts_with('.') {
+            return Err(SkipReason::Hidden);
+        }
+        let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).unwrap_or_default();
+        if !self.extensions.contains(&extension) {
+            return Err(SkipReason::NotVideo);
+        }
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_lowercase();
+        let in_sample_folder = path.parent().and_then(|p| p.file_name()).is_some_and(|n| n.eq_ignore_ascii_case("sample"));
+        if in_sample_folder || stem == "sample" || stem.ends_with("-sample") || stem.ends_with(".sample") {
+            return Err(SkipReason::Sample);
+        }
+        Ok(())
+    }
+
+    /// Logs that `path` isn't indexed and why.
+    fn log_skipped(path: &Path, reason: &SkipReason) {
+        let companion = path.extension().and_then(|e| e.to_str())
+            .is_some_and(|e| COMPANION_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+        if *reason == SkipReason::NotVideo && companion {
+            log::debug!(target: "cli", "Skipped {:?}: {}", path, reason);
+        } else {
+            log::info!(target: "cli", "Skipped {:?}: {}", path, reason);
+        }
+    }
+
+    /// Checks a file `depth` levels below the library folder and logs it if it's skipped.
+    pub fn accept(&self, path: &Path, depth: usize) -> bool {
+        let result = if depth > self.max_depth {
+            Err(SkipReason::TooDeep(self.max_depth))
+        } else {
+            self.classify(path)
+        };
+        match result {
+            Ok(()) => true,
+            Err(reason) => {
+                Self::log_skipped(path, &reason);
+                false
+            }
+        }
+    }
+
+    /// All videos under `basepath`, sorted. Files one level too deep are logged as skipped.
+    pub fn scan_videos(&self, basepath: &Path) -> Vec<PathBuf> {
+        let mut videos: Vec<PathBuf> = walkdir::WalkDir::new(basepath)
+            .max_depth(self.max_depth + 1).into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| self.accept(entry.path(), entry.depth()))
+            .map(|entry| entry.into_path())
+            .collect();
+        videos.sort();
+        videos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let scanner = VideoScanner::default();
+        assert_eq!(scanner.classify(Path::new("Movies/Movie (2001)/Movie.MKV")), Ok(()));
+        assert_eq!(scanner.classify(Path::new("Movies/Movie (2001)/Movie.avi")), Ok(()));
+        assert_eq!(scanner.classify(Path::new("Movies/Movie (2001)/Movie.en.srt")), Err(SkipReason::NotVideo));
+        assert_eq!(scanner.classify(Path::new("Movies/Movie (2001)/.Movie.mkv")), Err(SkipReason::Hidden));
+        assert_eq!(scanner.classify(Path::new("Movies/Movie (2001)/movie-sample.mkv")), Err(SkipReason::Sample));
+        assert_eq!(scanner.classify(Path::new("Movies/Movie (2001)/Sample/movie.mkv")), Err(SkipReason::Sample));
+
+        let scanner = VideoScanner::new(vec![".MP4".into(), " ts".into()], 2);
+        assert_eq!(scanner.classify(Path::new("a.mp4")), Ok(()));
+        assert_eq!(scanner.classify(Path::new("a.ts")), Ok(()));
+        assert_eq!(scanner.classify(Path::new("a.mkv")), Err(SkipReason::NotVideo));
+    }
+
+    #[test]
+    fn test_scan_videos() {
+        let dir = tempfile::Builder::new().prefix("test_scan").tempdir().unwrap();
+        let base = dir.path();
+        for file in ["Movie (2001)/movie.mkv", "Movie (2001)/movie.nfo", "Collection/Other (1999)/other.avi",
+                     "Collection/Deeper/Nested (2005)/nested.mp4", "loose.mp4"] {
+            std::fs::create_dir_all(base.join(file).parent().unwrap()).unwrap();
+            std::fs::write(base.join(file), b"").unwrap();
+        }
+        let found = |depth| VideoScanner::new(vec!["mkv".into(), "mp4".into(), "avi".into()], depth).scan_videos(base)
+            .into_iter().map(|p| p.strip_prefix(base).unwrap().to_str().unwrap().to_string()).collect::<Vec<_>>();
+        assert_eq!(found(3), vec!["Collection/Other (1999)/other.avi", "Movie (2001)/movie.mkv", "loose.mp4"]);
+        assert_eq!(found(2), vec!["Movie (2001)/movie.mkv", "loose.mp4"]);
+        assert_eq!(found(4).len(), 4);
+    }
+}
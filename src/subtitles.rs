@@ -0,0 +1,452 @@
+use crate::probe::MediaProbe;
+use crate::transcode::Ffmpeg;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::UNIX_EPOCH;
+use url::Url;
+
+/// Folder of the generated site that holds the WebVTT files.
+pub const SUBTITLES_DIR: &str = "subtitles";
+
+/// Embedded subtitle codecs ffmpeg can turn into WebVTT. Image based ones (PGS, VobSub) can't be.
+const TEXT_SUBTITLE_CODECS: &[&str] = &["subrip", "srt", "ass", "ssa", "webvtt", "mov_text", "text"];
+
+/// Folders next to a video that are searched for subtitles as well.
+const SUBTITLE_SUBFOLDERS: &[&str] = &["Subs", "subs", "Subtitles", "subtitles"];
+
+/// Languages recognized in subtitle file names: ISO 639-1 code, other spellings, English name.
+const LANGUAGES: &[(&str, &[&str], &str)] = &[
+    ("en", &["eng", "english"], "English"),
+    ("he", &["heb", "hebrew", "iw"], "Hebrew"),
+    ("ar", &["ara", "arabic"], "Arabic"),
+    ("de", &["ger", "deu", "german"], "German"),
+    ("es", &["spa", "spanish"], "Spanish"),
+    ("fr", &["fre", "fra", "french"], "French"),
+    ("it", &["ita", "italian"], "Italian"),
+    ("nl", &["dut", "nld", "dutch"], "Dutch"),
+    ("pt", &["por", "portuguese"], "Portuguese"),
+    ("ru", &["rus", "russian"], "Russian"),
+    ("uk", &["ukr", "ukrainian"], "Ukrainian"),
+    ("pl", &["pol", "polish"], "Polish"),
+    ("sv", &["swe", "swedish"], "Swedish"),
+    ("el", &["gre", "ell", "greek"], "Greek"),
+    ("tr", &["tur", "turkish"], "Turkish"),
+    ("ja", &["jpn", "japanese"], "Japanese"),
+    ("ko", &["kor", "korean"], "Korean"),
+    ("zh", &["chi", "zho", "chinese"], "Chinese"),
+];
+
+lazy_static! {
+    static ref EPISODE_TAG_RE: Regex = Regex::new(r"(?i)s(\d{1,2})e(\d{1,3})").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    Ass,
+}
+
+impl SubtitleFormat {
+    fn from_path(path: &Path) -> Option<SubtitleFormat> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "srt" => Some(SubtitleFormat::Srt),
+            "vtt" => Some(SubtitleFormat::Vtt),
+            "ass" | "ssa" => Some(SubtitleFormat::Ass),
+            _ => None,
+        }
+    }
+}
+
+/// A subtitle file found next to a video.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SidecarSubtitle {
+    pub path: PathBuf,
+    pub format: SubtitleFormat,
+    /// ISO 639-1 code when the name has a known language tag.
+    pub language: Option<String>,
+    pub forced: bool,
+}
+
+/// A WebVTT track as listed on the pages and passed to the Cast receiver.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TextTrack {
+    pub url: String,
+    pub language: Option<String>,
+    pub label: String,
+    pub forced: bool,
+}
+
+/// Maps a language tag from a file name (`en`, `heb`, `English`) to its ISO 639-1 code.
+pub fn normalize_language(tag: &str) -> Option<&'static str> {
+    let tag = tag.to_lowercase();
+    LANGUAGES.iter().find(|(code, aliases, _)| *code == tag || aliases.contains(&tag.as_str())).map(|(code, _, _)| *code)
+}
+
+/// Human readable label of a track, e.g. `Hebrew (forced)`.
+pub fn track_label(language: Option<&str>, forced: bool) -> String {
+    let name = language
+        .and_then(|code| LANGUAGES.iter().find(|(c, _, _)| *c == code).map(|(_, _, name)| name.to_string()))
+        .or_else(|| language.map(|code| code.to_uppercase()))
+        .unwrap_or_else(|| "Unknown".to_string());
+    if forced { format!("{} (forced)", name) } else { name }
+}
+
+/// Splits `Movie.en.forced` into the base name and the language/forced tags at its end.
+fn parse_subtitle_stem(stem: &str) -> (&str, Option<String>, bool) {
+    let mut base = stem;
+    let mut language = None;
+    let mut forced = false;
+    while let Some((rest, tag)) = base.rsplit_once('.') {
+        let lower = tag.to_lowercase();
+        if lower == "forced" {
+            forced = true;
+        } else if ["sdh", "cc", "hi", "default"].contains(&lower.as_str()) {
+            // Known flags without a field of their own
+        } else if let Some(code) = normalize_language(tag).filter(|_| language.is_none()) {
+            language = Some(code.to_string());
+        } else {
+            break;
+        }
+        base = rest;
+    }
+    (base, language, forced)
+}
+
+fn episode_tag(name: &str) -> Option<(u32, u32)> {
+    let captures = EPISODE_TAG_RE.captures(name)?;
+    Some((captures[1].parse().ok()?, captures[2].parse().ok()?))
+}
+
+/// Finds the subtitle files belonging to `video`: in its folder or a `Subs` folder next to it,
+/// named like the video (`Movie.en.srt` for `Movie.mkv`) or, for episodes, carrying the same
+/// `SxxEyy` tag (`S01E01.heb.forced.srt` for `Show.S01E01.720p.mkv`).
+pub fn find_sidecar_subtitles(video: &Path) -> Vec<SidecarSubtitle> {
+    let (folder, video_stem) = match (video.parent(), video.file_stem().and_then(|s| s.to_str())) {
+        (Some(folder), Some(stem)) => (folder, stem),
+        _ => return Vec::new(),
+    };
+    let video_episode = episode_tag(video_stem);
+    let folders = std::iter::once(folder.to_path_buf()).chain(SUBTITLE_SUBFOLDERS.iter().map(|sub| folder.join(sub)));
+
+    let mut subtitles: Vec<SidecarSubtitle> = folders
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()))
+        .filter_map(|path| {
+            let format = SubtitleFormat::from_path(&path)?;
+            let stem = path.file_stem()?.to_str()?;
+            let (base, language, forced) = parse_subtitle_stem(stem);
+            let matches = base.eq_ignore_ascii_case(video_stem)
+                || (video_episode.is_some() && episode_tag(base) == video_episode);
+            matches.then(|| SidecarSubtitle { path: path.clone(), format, language, forced })
+        })
+        .collect();
+    subtitles.sort_by(|a, b| a.path.cmp(&b.path));
+    subtitles
+}
+
+/// Decodes a subtitle file. Files that aren't UTF-8 are read with the legacy Windows code page
+/// of their language, which is what most non-Latin subtitles in the wild use.
+pub fn decode_subtitle(bytes: &[u8], language: Option<&str>) -> String {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.trim_start_matches('\u{feff}').to_string();
+    }
+    let encoding = match language {
+        Some("he") => encoding_rs::WINDOWS_1255,
+        Some("ar") => encoding_rs::WINDOWS_1256,
+        Some("ru") | Some("uk") => encoding_rs::WINDOWS_1251,
+        Some("el") => encoding_rs::WINDOWS_1253,
+        Some("tr") => encoding_rs::WINDOWS_1254,
+        Some("pl") => encoding_rs::WINDOWS_1250,
+        _ => encoding_rs::WINDOWS_1252,
+    };
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// Converts SubRip to WebVTT: a header and `.` instead of `,` in the cue timings.
+pub fn srt_to_vtt(srt: &str) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for line in srt.lines() {
+        if line.contains("-->") {
+            vtt.push_str(&line.replace(',', "."));
+        } else {
+            vtt.push_str(line);
+        }
+        vtt.push('\n');
+    }
+    vtt
+}
+
+/// `H:MM:SS.cc` (ASS) to `HH:MM:SS.mmm` (WebVTT).
+fn ass_time_to_vtt(time: &str) -> Option<String> {
+    let mut parts = time.trim().split(':');
+    let (hours, minutes, seconds) = (parts.next()?, parts.next()?, parts.next()?);
+    let (secs, centis) = seconds.split_once('.').unwrap_or((seconds, "0"));
+    Some(format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        hours.parse::<u32>().ok()?,
+        minutes.parse::<u32>().ok()?,
+        secs.parse::<u32>().ok()?,
+        centis.parse::<u32>().ok()? * 10
+    ))
+}
+
+lazy_static! {
+    static ref ASS_OVERRIDE_RE: Regex = Regex::new(r"\{[^}]*\}").unwrap();
+}
+
+/// Converts the dialogue of an ASS/SSA file to WebVTT cues. Styling and positioning are dropped.
+pub fn ass_to_vtt(ass: &str) -> String {
+    let mut fields: Vec<String> = Vec::new();
+    let mut in_events = false;
+    let mut cues: Vec<(String, String, String)> = Vec::new();
+    for line in ass.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        if let Some(format) = line.strip_prefix("Format:") {
+            fields = format.split(',').map(|f| f.trim().to_lowercase()).collect();
+        } else if let Some(dialogue) = line.strip_prefix("Dialogue:") {
+            // Text is the last field and may itself contain commas
+            let values: Vec<&str> = dialogue.splitn(fields.len().max(1), ',').collect();
+            let field = |name: &str| fields.iter().position(|f| f == name).and_then(|i| values.get(i)).copied();
+            let (start, end, text) = match (field("start").and_then(ass_time_to_vtt), field("end").and_then(ass_time_to_vtt), field("text")) {
+                (Some(start), Some(end), Some(text)) => (start, end, text),
+                _ => continue,
+            };
+            let text = ASS_OVERRIDE_RE.replace_all(text, "").replace("\\N", "\n").replace("\\n", "\n").replace("\\h", " ");
+            if !text.trim().is_empty() {
+                cues.push((start, end, text.trim().to_string()));
+            }
+        }
+    }
+    cues.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut vtt = String::from("WEBVTT\n\n");
+    for (start, end, text) in cues {
+        vtt.push_str(&format!("{} --> {}\n{}\n\n", start, end, text));
+    }
+    vtt
+}
+
+/// Reads a sidecar subtitle and returns it as WebVTT.
+pub fn convert_to_vtt(subtitle: &SidecarSubtitle) -> std::io::Result<String> {
+    let text = decode_subtitle(&std::fs::read(&subtitle.path)?, subtitle.language.as_deref());
+    Ok(match subtitle.format {
+        SubtitleFormat::Srt => srt_to_vtt(&text),
+        SubtitleFormat::Ass => ass_to_vtt(&text),
+        SubtitleFormat::Vtt => text.replace("\r\n", "\n"),
+    })
+}
+
+/// URL of a file in the generated site's subtitles folder.
+pub fn track_url(base_url: &Option<Url>, file_name: &str) -> String {
+    let path = format!("{}/{}", SUBTITLES_DIR, file_name);
+    match base_url {
+        Some(url) => url.join(&path).unwrap().to_string(),
+        None => path,
+    }
+}
+
+/// Converts the sidecar subtitles of `video` to WebVTT files in `<output_dir>/subtitles`
+/// and returns them as tracks.
+pub fn export_sidecar_tracks(video: &Path, output_dir: &Path, base_url: &Option<Url>) -> Vec<TextTrack> {
+    let mut tracks = Vec::new();
+    for subtitle in find_sidecar_subtitles(video) {
+        let vtt = match convert_to_vtt(&subtitle) {
+            Ok(vtt) => vtt,
+            Err(e) => {
+                log::warn!(target: "cli", "Failed to read subtitle {:?}: {}", subtitle.path, e);
+                continue;
+            }
+        };
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        subtitle.path.to_str().hash(&mut hasher);
+        let file_name = format!("{}.vtt", hasher.finish());
+        let dir = output_dir.join(SUBTITLES_DIR);
+        if let Err(e) = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(dir.join(&file_name), vtt)) {
+            log::error!(target: "cli", "Failed to write subtitle {:?}: {}", file_name, e);
+            continue;
+        }
+        log::info!(target: "cli", "Subtitle discovered: {:?}", subtitle.path);
+        tracks.push(TextTrack {
+            url: track_url(base_url, &file_name),
+            label: track_label(subtitle.language.as_deref(), subtitle.forced),
+            language: subtitle.language,
+            forced: subtitle.forced,
+        });
+    }
+    tracks
+}
+
+impl Ffmpeg {
+    /// Writes subtitle stream `stream_index` of `input` as WebVTT to `output`.
+    pub fn extract_subtitle(&self, input: &Path, stream_index: u32, output: &Path) -> std::io::Result<()> {
+        let status = Command::new(&self.ffmpeg)
+            .args(["-nostdin", "-v", "error", "-y", "-i"])
+            .arg(input)
+            .args(["-map", &format!("0:{}", stream_index), "-f", "webvtt"])
+            .arg(output)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(std::io::Error::other(format!("ffmpeg failed extracting subtitle stream {} of {:?}", stream_index, input)))
+        }
+    }
+}
+
+/// Name of the cached WebVTT file of an embedded stream; changes when the video does.
+fn embedded_track_file_name(video: &Path, stream_index: u32) -> std::io::Result<String> {
+    let mtime = std::fs::metadata(video)?.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    video.to_str().hash(&mut hasher);
+    mtime.as_nanos().hash(&mut hasher);
+    Ok(format!("{}-{}.vtt", hasher.finish(), stream_index))
+}
+
+/// Extracts the text subtitle streams listed in `probe` to WebVTT files in `<output_dir>/subtitles`
+/// and returns them as tracks. Files extracted on an earlier run are reused.
+pub fn export_embedded_tracks(ffmpeg: &Ffmpeg, video: &Path, probe: &MediaProbe, output_dir: &Path, base_url: &Option<Url>) -> Vec<TextTrack> {
+    let dir = output_dir.join(SUBTITLES_DIR);
+    let mut tracks = Vec::new();
+    for stream in &probe.subtitle_tracks {
+        if !TEXT_SUBTITLE_CODECS.contains(&stream.codec.as_str()) {
+            log::debug!(target: "cli", "Skipping image based subtitle stream {} ({}) of {:?}", stream.index, stream.codec, video);
+            continue;
+        }
+        let file_name = match embedded_track_file_name(video, stream.index) {
+            Ok(name) => name,
+            Err(e) => {
+                log::warn!(target: "cli", "Could not stat {:?}: {}", video, e);
+                return tracks;
+            }
+        };
+        let vtt_path = dir.join(&file_name);
+        if !vtt_path.is_file() {
+            // Extract next to the final name so an interrupted run leaves no truncated track behind
+            let temp_path = dir.join(format!("{}.part", file_name));
+            let extracted = std::fs::create_dir_all(&dir)
+                .and_then(|_| ffmpeg.extract_subtitle(video, stream.index, &temp_path))
+                .and_then(|_| std::fs::rename(&temp_path, &vtt_path));
+            if let Err(e) = extracted {
+                let _ = std::fs::remove_file(&temp_path);
+                log::warn!(target: "cli", "Failed to extract subtitle stream {} of {:?}: {}", stream.index, video, e);
+                continue;
+            }
+            log::info!(target: "cli", "Extracted subtitle stream {} of {:?}", stream.index, video);
+        }
+        let language = stream.language.as_deref().map(|tag| normalize_language(tag).map(String::from).unwrap_or_else(|| tag.to_lowercase()));
+        tracks.push(TextTrack {
+            url: track_url(base_url, &file_name),
+            label: stream.title.clone().unwrap_or_else(|| track_label(language.as_deref(), stream.forced)),
+            language,
+            forced: stream.forced,
+        });
+    }
+    tracks
+}
+
+/// All subtitle tracks of `video`: sidecar files first, then the streams embedded in it.
+pub fn export_text_tracks(ffmpeg: &Ffmpeg, video: &Path, probe: Option<&MediaProbe>, output_dir: &Path, base_url: &Option<Url>) -> Vec<TextTrack> {
+    let mut tracks = export_sidecar_tracks(video, output_dir, base_url);
+    if let Some(probe) = probe {
+        tracks.extend(export_embedded_tracks(ffmpeg, video, probe, output_dir, base_url));
+    }
+    tracks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_sidecar_subtitles() {
+        let dir = tempfile::Builder::new().prefix("test_subtitles").tempdir().unwrap();
+        let base = dir.path();
+        std::fs::create_dir_all(base.join("Season 1/Subs")).unwrap();
+        for file in ["Movie.mkv", "Movie.en.srt", "Movie.HEB.forced.srt", "Movie.srt", "Other.en.srt", "Movie.nfo",
+                     "Season 1/Show.S01E01.720p.mkv", "Season 1/Subs/S01E01.heb.forced.srt", "Season 1/S01E02.en.ass"] {
+            std::fs::write(base.join(file), b"").unwrap();
+        }
+
+        let found = find_sidecar_subtitles(&base.join("Movie.mkv"));
+        let summary: Vec<_> = found.iter()
+            .map(|s| (s.path.file_name().unwrap().to_str().unwrap(), s.language.as_deref(), s.forced, s.format))
+            .collect();
+        assert_eq!(summary, vec![
+            ("Movie.HEB.forced.srt", Some("he"), true, SubtitleFormat::Srt),
+            ("Movie.en.srt", Some("en"), false, SubtitleFormat::Srt),
+            ("Movie.srt", None, false, SubtitleFormat::Srt),
+        ]);
+
+        let found = find_sidecar_subtitles(&base.join("Season 1/Show.S01E01.720p.mkv"));
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].language.as_deref(), found[0].forced), (Some("he"), true));
+    }
+
+    #[test]
+    fn test_srt_to_vtt() {
+        let srt = "1\r\n00:00:01,000 --> 00:00:02,500\r\nHello, world\r\n\r\n2\r\n00:01:00,000 --> 00:01:01,000\r\n<i>Bye</i>\r\n";
+        assert_eq!(srt_to_vtt(&decode_subtitle(srt.as_bytes(), None)),
+            "WEBVTT\n\n1\n00:00:01.000 --> 00:00:02.500\nHello, world\n\n2\n00:01:00.000 --> 00:01:01.000\n<i>Bye</i>\n");
+    }
+
+    #[test]
+    fn test_ass_to_vtt() {
+        let ass = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname\nStyle: Default,Arial\n\n[Events]\n\
+            Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+            Dialogue: 0,0:00:05.50,0:00:07.00,Default,,0,0,0,,{\\i1}Second{\\i0}, with comma\\Nline two\n\
+            Comment: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Not shown\n\
+            Dialogue: 0,0:00:01.00,0:00:02.25,Default,,0,0,0,,First\n";
+        assert_eq!(ass_to_vtt(ass), "WEBVTT\n\n00:00:01.000 --> 00:00:02.250\nFirst\n\n00:00:05.500 --> 00:00:07.000\nSecond, with comma\nline two\n\n");
+    }
+
+    #[test]
+    fn test_export_embedded_tracks() {
+        use crate::probe::SubtitleTrack;
+        let dir = tempfile::Builder::new().prefix("test_subtitles").tempdir().unwrap();
+        let video = dir.path().join("Movie.mkv");
+        std::fs::write(&video, b"").unwrap();
+        let output = dir.path().join("pub");
+        let probe = MediaProbe {
+            subtitle_tracks: vec![
+                SubtitleTrack { index: 2, codec: "subrip".into(), language: Some("heb".into()), ..Default::default() },
+                SubtitleTrack { index: 3, codec: "hdmv_pgs_subtitle".into(), language: Some("eng".into()), ..Default::default() },
+                SubtitleTrack { index: 4, codec: "ass".into(), language: Some("eng".into()), title: Some("Signs".into()), forced: true },
+            ],
+            ..Default::default()
+        };
+        let no_ffmpeg = Ffmpeg { ffmpeg: "/nonexistent/ffmpeg".into(), ffprobe: "/nonexistent/ffprobe".into() };
+        assert!(export_embedded_tracks(&no_ffmpeg, &video, &probe, &output, &None).is_empty());
+
+        // Tracks extracted before are reused without ffmpeg
+        for index in [2, 4] {
+            std::fs::write(output.join(SUBTITLES_DIR).join(embedded_track_file_name(&video, index).unwrap()), "WEBVTT\n").unwrap();
+        }
+        let tracks = export_embedded_tracks(&no_ffmpeg, &video, &probe, &output, &None);
+        assert_eq!(tracks.len(), 2);
+        assert_eq!((tracks[0].language.as_deref(), tracks[0].label.as_str()), (Some("he"), "Hebrew"));
+        assert_eq!((tracks[1].label.as_str(), tracks[1].forced), ("Signs", true));
+        assert!(tracks[1].url.starts_with("subtitles/") && tracks[1].url.ends_with("-4.vtt"));
+    }
+
+    #[test]
+    fn test_decode_legacy_encoding() {
+        // "שלום" in windows-1255
+        assert_eq!(decode_subtitle(&[0xf9, 0xec, 0xe5, 0xed], Some("he")), "שלום");
+        assert_eq!(decode_subtitle("\u{feff}hi".as_bytes(), Some("he")), "hi");
+        assert_eq!(track_label(Some("he"), true), "Hebrew (forced)");
+        assert_eq!(track_label(Some("xx"), false), "XX");
+        assert_eq!(track_label(None, false), "Unknown");
+    }
+}
@@ -0,0 +1,450 @@
+use crate::http::{HttpClient, HttpError};
+use crate::media::parse_year;
+use crate::provider::{EpisodeMetadata, MetadataProvider, MovieMetadata, SeasonMetadata, SeriesMetadata, TitleQuery};
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use url::Url;
+
+pub const TMDB_API_URL: &str = "https://api.themoviedb.org/3";
+const TMDB_IMAGE_URL: &str = "https://image.tmdb.org/t/p/";
+/// TMDB allows about 50 requests per second; this stays well below.
+const TMDB_REQUESTS_PER_SECOND: f64 = 20.0;
+/// Actors listed, as many as OMDB lists.
+const TMDB_ACTORS: usize = 3;
+
+/// Why a TMDB request failed.
+#[derive(Debug)]
+pub enum TmdbError {
+    /// TMDB has no such title; what was looked up.
+    NotFound(String),
+    RateLimited,
+    InvalidKey,
+    /// No answer from TMDB, e.g. it's down or there's no network.
+    Transport(reqwest::Error),
+    /// An answer that isn't what was asked for.
+    Decode(String),
+}
+
+impl TmdbError {
+    /// Whether every further request would fail the same way, so the run should stop.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, TmdbError::InvalidKey)
+    }
+}
+
+impl std::fmt::Display for TmdbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TmdbError::NotFound(what) => write!(f, "not found on TMDB: {}", what),
+            TmdbError::RateLimited => write!(f, "TMDB request limit reached"),
+            TmdbError::InvalidKey => write!(f, "invalid TMDB API key"),
+            TmdbError::Transport(e) => write!(f, "TMDB request failed: {}", e),
+            TmdbError::Decode(message) => write!(f, "unexpected TMDB response: {}", message),
+        }
+    }
+}
+
+impl Error for TmdbError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TmdbError::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for TmdbError {
+    fn from(e: reqwest::Error) -> Self {
+        TmdbError::Transport(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct TmdbId {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct TmdbSearch {
+    #[serde(default)]
+    results: Vec<TmdbId>,
+}
+
+#[derive(Deserialize)]
+struct TmdbFind {
+    #[serde(default)]
+    movie_results: Vec<TmdbId>,
+    #[serde(default)]
+    tv_results: Vec<TmdbId>,
+    #[serde(default)]
+    tv_episode_results: Vec<TmdbEpisode>,
+}
+
+#[derive(Deserialize)]
+struct TmdbNamed {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct TmdbLanguage {
+    english_name: String,
+}
+
+#[derive(Deserialize)]
+struct TmdbCrew {
+    name: String,
+    job: String,
+}
+
+#[derive(Deserialize, Default)]
+struct TmdbCredits {
+    #[serde(default)]
+    cast: Vec<TmdbNamed>,
+    #[serde(default)]
+    crew: Vec<TmdbCrew>,
+}
+
+#[derive(Deserialize, Default)]
+struct TmdbExternalIds {
+    imdb_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TmdbMovie {
+    /// In the requested language, or English without one, when TMDB has a translation
+    title: String,
+    original_title: String,
+    overview: Option<String>,
+    release_date: Option<String>,
+    runtime: Option<u32>,
+    #[serde(default)]
+    genres: Vec<TmdbNamed>,
+    poster_path: Option<String>,
+    backdrop_path: Option<String>,
+    belongs_to_collection: Option<TmdbNamed>,
+    imdb_id: Option<String>,
+    #[serde(default)]
+    spoken_languages: Vec<TmdbLanguage>,
+    #[serde(default)]
+    production_countries: Vec<TmdbNamed>,
+    #[serde(default)]
+    credits: TmdbCredits,
+}
+
+#[derive(Deserialize)]
+struct TmdbSeries {
+    /// In the requested language, or English without one, when TMDB has a translation
+    name: String,
+    original_name: String,
+    overview: Option<String>,
+    first_air_date: Option<String>,
+    #[serde(default)]
+    episode_run_time: Vec<u32>,
+    #[serde(default)]
+    genres: Vec<TmdbNamed>,
+    poster_path: Option<String>,
+    backdrop_path: Option<String>,
+    number_of_seasons: Option<u8>,
+    #[serde(default)]
+    created_by: Vec<TmdbNamed>,
+    #[serde(default)]
+    spoken_languages: Vec<TmdbLanguage>,
+    #[serde(default)]
+    origin_country: Vec<String>,
+    #[serde(default)]
+    credits: TmdbCredits,
+    #[serde(default)]
+    external_ids: TmdbExternalIds,
+}
+
+#[derive(Deserialize)]
+struct TmdbSeason {
+    #[serde(default)]
+    episodes: Vec<TmdbEpisode>,
+}
+
+#[derive(Deserialize)]
+struct TmdbEpisode {
+    episode_number: u8,
+    name: Option<String>,
+    overview: Option<String>,
+    air_date: Option<String>,
+    still_path: Option<String>,
+    #[serde(default)]
+    crew: Vec<TmdbCrew>,
+    #[serde(default)]
+    external_ids: TmdbExternalIds,
+}
+
+/// TMDB leaves unknown text fields empty rather than out.
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.trim().is_empty())
+}
+
+fn joined<'a>(names: impl Iterator<Item = &'a str>) -> Option<String> {
+    non_empty(Some(names.collect::<Vec<_>>().join(", ")))
+}
+
+fn directors(crew: &[TmdbCrew]) -> Option<String> {
+    joined(crew.iter().filter(|c| c.job == "Director").map(|c| c.name.as_str()))
+}
+
+/// The translated title, when a language was asked for and it differs from the original.
+fn localized(language: Option<&String>, translated: String, original: &str) -> Option<String> {
+    language.and(Some(translated)).filter(|t| t != original)
+}
+
+fn image_url(size: &str, path: Option<&str>) -> Option<Url> {
+    path.and_then(|p| Url::parse(TMDB_IMAGE_URL).ok()?.join(&format!("{}{}", size, p)).ok())
+}
+
+impl TmdbEpisode {
+    fn into_metadata(self) -> EpisodeMetadata {
+        EpisodeMetadata {
+            episode: self.episode_number,
+            imdb_id: non_empty(self.external_ids.imdb_id),
+            title: non_empty(self.name),
+            plot: non_empty(self.overview),
+            released: non_empty(self.air_date),
+            imdb_rating: None,
+            director: directors(&self.crew),
+            still_url: image_url("w300", self.still_path.as_deref()),
+        }
+    }
+}
+
+/// TMDB (API v3) as a `MetadataProvider`. Besides what OMDB has, it knows backdrops, episode stills,
+/// collections and titles in other languages.
+pub struct TmdbProvider {
+    api_key: String,
+    base_url: String,
+    /// e.g. `de-DE`; titles and plots are in this language when TMDB has them
+    language: Option<String>,
+    client: HttpClient,
+    /// TMDB ids of the series looked up so far, by IMDb id or title
+    series_ids: RefCell<HashMap<String, u64>>,
+}
+
+impl TmdbProvider {
+    /// `base_url` is `TMDB_API_URL`, or a mock of it.
+    pub fn new(api_key: &str, language: Option<&str>, base_url: &str) -> Self {
+        TmdbProvider {
+            api_key: api_key.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            language: language.map(String::from),
+            client: HttpClient::new(Some(TMDB_REQUESTS_PER_SECOND), None),
+            series_ids: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn request<T: DeserializeOwned>(&self, path: &str, params: &[(&str, String)]) -> Result<T, TmdbError> {
+        let mut query = vec![("api_key", self.api_key.clone())];
+        if let Some(language) = &self.language {
+            query.push(("language", language.clone()));
+        }
+        query.extend(params.iter().cloned());
+        let resp = match self.client.get(&format!("{}{}", self.base_url, path), &query) {
+            Ok(resp) => resp,
+            Err(HttpError::QuotaExhausted(_)) => return Err(TmdbError::RateLimited),
+            Err(HttpError::Request(e)) => return Err(e.into()),
+        };
+        match resp.status().as_u16() {
+            401 => return Err(TmdbError::InvalidKey),
+            404 => return Err(TmdbError::NotFound(path.to_string())),
+            429 => return Err(TmdbError::RateLimited),
+            status if !resp.status().is_success() => return Err(TmdbError::Decode(format!("HTTP {} for {}", status, path))),
+            _ => {}
+        }
+        serde_json::from_str(&resp.text()?).map_err(|e| TmdbError::Decode(e.to_string()))
+    }
+
+    fn find(&self, imdb_id: &str) -> Result<TmdbFind, TmdbError> {
+        self.request(&format!("/find/{}", imdb_id), &[("external_source", "imdb_id".to_string())])
+    }
+
+    /// The TMDB id of the first search result.
+    fn search(&self, kind: &str, query: &TitleQuery, year_param: &str) -> Result<u64, TmdbError> {
+        let mut params = vec![("query", query.title.to_string())];
+        if let Some(year) = query.year {
+            params.push((year_param, year.to_string()));
+        }
+        let found: TmdbSearch = self.request(&format!("/search/{}", kind), &params)?;
+        found.results.first().map(|r| r.id).ok_or_else(|| TmdbError::NotFound(query.title.to_string()))
+    }
+
+    fn movie_id(&self, query: &TitleQuery) -> Result<u64, TmdbError> {
+        match query.imdb_id {
+            Some(imdb_id) => self.find(imdb_id)?.movie_results.first().map(|r| r.id).ok_or_else(|| TmdbError::NotFound(imdb_id.to_string())),
+            None => self.search("movie", query, "year"),
+        }
+    }
+
+    fn series_id(&self, query: &TitleQuery) -> Result<u64, TmdbError> {
+        let key = query.imdb_id.unwrap_or(query.title).to_string();
+        if let Some(id) = self.series_ids.borrow().get(&key) {
+            return Ok(*id);
+        }
+        let id = match query.imdb_id {
+            Some(imdb_id) => self.find(imdb_id)?.tv_results.first().map(|r| r.id).ok_or_else(|| TmdbError::NotFound(imdb_id.to_string()))?,
+            None => self.search("tv", query, "first_air_date_year")?,
+        };
+        self.series_ids.borrow_mut().insert(key, id);
+        Ok(id)
+    }
+}
+
+impl MetadataProvider for TmdbProvider {
+    fn name(&self) -> &'static str {
+        "TMDB"
+    }
+
+    fn movie(&self, query: &TitleQuery) -> Result<MovieMetadata, Box<dyn Error>> {
+        let id = self.movie_id(query)?;
+        let movie: TmdbMovie = self.request(&format!("/movie/{}", id), &[("append_to_response", "credits".to_string())])?;
+        Ok(MovieMetadata {
+            imdb_id: non_empty(movie.imdb_id),
+            localized_title: localized(self.language.as_ref(), movie.title.clone(), &movie.original_title),
+            title: movie.title,
+            year: movie.release_date.as_deref().and_then(parse_year),
+            director: directors(&movie.credits.crew),
+            plot: non_empty(movie.overview),
+            genre: joined(movie.genres.iter().map(|g| g.name.as_str())),
+            runtime: movie.runtime.filter(|r| *r > 0).map(|r| format!("{} min", r)),
+            released: non_empty(movie.release_date),
+            actors: joined(movie.credits.cast.iter().take(TMDB_ACTORS).map(|a| a.name.as_str())),
+            language: joined(movie.spoken_languages.iter().map(|l| l.english_name.as_str())),
+            country: joined(movie.production_countries.iter().map(|c| c.name.as_str())),
+            poster_url: image_url("w500", movie.poster_path.as_deref()),
+            backdrop_url: image_url("w1280", movie.backdrop_path.as_deref()),
+            collection: movie.belongs_to_collection.map(|c| c.name),
+            ..Default::default()
+        })
+    }
+
+    fn series(&self, query: &TitleQuery) -> Result<SeriesMetadata, Box<dyn Error>> {
+        let id = self.series_id(query)?;
+        let series: TmdbSeries = self.request(&format!("/tv/{}", id), &[("append_to_response", "credits,external_ids".to_string())])?;
+        Ok(SeriesMetadata {
+            imdb_id: non_empty(series.external_ids.imdb_id),
+            localized_title: localized(self.language.as_ref(), series.name.clone(), &series.original_name),
+            title: series.name,
+            year: series.first_air_date.as_deref().and_then(parse_year),
+            director: joined(series.created_by.iter().map(|c| c.name.as_str())),
+            plot: non_empty(series.overview),
+            genre: joined(series.genres.iter().map(|g| g.name.as_str())),
+            runtime: series.episode_run_time.first().map(|r| format!("{} min", r)),
+            released: non_empty(series.first_air_date),
+            actors: joined(series.credits.cast.iter().take(TMDB_ACTORS).map(|a| a.name.as_str())),
+            language: joined(series.spoken_languages.iter().map(|l| l.english_name.as_str())),
+            country: joined(series.origin_country.iter().map(String::as_str)),
+            total_seasons: series.number_of_seasons,
+            poster_url: image_url("w500", series.poster_path.as_deref()),
+            backdrop_url: image_url("w1280", series.backdrop_path.as_deref()),
+            ..Default::default()
+        })
+    }
+
+    fn season(&self, series: &TitleQuery, season: u8) -> Result<SeasonMetadata, Box<dyn Error>> {
+        let id = self.series_id(series)?;
+        let listing: TmdbSeason = self.request(&format!("/tv/{}/season/{}", id, season), &[])?;
+        Ok(SeasonMetadata {
+            total_seasons: None,
+            episodes: listing.episodes.into_iter().map(TmdbEpisode::into_metadata).collect(),
+        })
+    }
+
+    fn episode(&self, series: &TitleQuery, season: u8, episode: u8, imdb_id: Option<&str>) -> Result<EpisodeMetadata, Box<dyn Error>> {
+        if let Some(imdb_id) = imdb_id {
+            if let Some(found) = self.find(imdb_id)?.tv_episode_results.into_iter().next() {
+                return Ok(EpisodeMetadata { imdb_id: Some(imdb_id.to_string()), ..found.into_metadata() });
+            }
+        }
+        let id = self.series_id(series)?;
+        let found: TmdbEpisode = self.request(&format!("/tv/{}/season/{}/episode/{}", id, season, episode), &[("append_to_response", "external_ids".to_string())])?;
+        Ok(found.into_metadata())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serves canned TMDB answers by path on a local port, and 404 for anything else.
+    fn mock_tmdb(routes: &'static [(&'static str, &'static str)]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).is_ok_and(|n| n > 2) {
+                    header.clear();
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default().split('?').next().unwrap_or_default().to_string();
+                let (status, body) = match routes.iter().find(|(route, _)| *route == path) {
+                    Some((_, body)) => ("200 OK", *body),
+                    None => ("404 Not Found", r#"{"status_code":34}"#),
+                };
+                let mut stream = &stream;
+                write!(stream, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_tmdb_provider() {
+        let url = mock_tmdb(&[
+            ("/find/tt0087182", r#"{"movie_results":[{"id":841}],"tv_results":[]}"#),
+            ("/movie/841", r#"{"title":"Der Wüstenplanet","original_title":"Dune","release_date":"1984-12-14","runtime":137,"overview":"",
+                "genres":[{"id":878,"name":"Science Fiction"}],"poster_path":"/poster.jpg","backdrop_path":"/backdrop.jpg",
+                "belongs_to_collection":{"id":1,"name":"Dune Collection"},"imdb_id":"tt0087182",
+                "credits":{"cast":[{"name":"Kyle MacLachlan"}],"crew":[{"name":"David Lynch","job":"Director"},{"name":"Someone","job":"Editor"}]}}"#),
+            ("/find/tt0245429", r#"{"movie_results":[{"id":129}],"tv_results":[]}"#),
+            ("/movie/129", r#"{"title":"Spirited Away","original_title":"千と千尋の神隠し","release_date":"2001-07-20"}"#),
+            ("/search/tv", r#"{"results":[{"id":1399}]}"#),
+            ("/tv/1399", r#"{"name":"Game of Thrones","original_name":"Game of Thrones","number_of_seasons":8,
+                "external_ids":{"imdb_id":"tt0944947"}}"#),
+            ("/tv/1399/season/1", r#"{"episodes":[{"episode_number":1,"name":"Winter Is Coming","air_date":"2011-04-17",
+                "overview":"A lord is summoned.","still_path":"/still.jpg","crew":[{"name":"Tim Van Patten","job":"Director"}]}]}"#),
+        ]);
+        let tmdb = TmdbProvider::new("key", Some("de-DE"), &url);
+
+        let movie = tmdb.movie(&TitleQuery { title: "Dune", year: Some(1984), imdb_id: Some("tt0087182") }).unwrap();
+        assert_eq!(movie.title, "Der Wüstenplanet");
+        assert_eq!(movie.localized_title.as_deref(), Some("Der Wüstenplanet"));
+        assert_eq!((movie.year, movie.runtime.as_deref()), (Some(1984), Some("137 min")));
+        assert_eq!(movie.plot, None);
+        assert_eq!(movie.director.as_deref(), Some("David Lynch"));
+        assert_eq!(movie.collection.as_deref(), Some("Dune Collection"));
+        assert_eq!(movie.backdrop_url.unwrap().as_str(), "https://image.tmdb.org/t/p/w1280/backdrop.jpg");
+
+        // Without a language, the title is TMDB's English one, not the original
+        let english = TmdbProvider::new("key", None, &url);
+        let movie = english.movie(&TitleQuery { title: "Spirited Away", year: None, imdb_id: Some("tt0245429") }).unwrap();
+        assert_eq!((movie.title.as_str(), movie.localized_title), ("Spirited Away", None));
+
+        let series = TitleQuery { title: "Game of Thrones", year: None, imdb_id: None };
+        // No translation: the title isn't repeated as a localized one
+        let metadata = tmdb.series(&series).unwrap();
+        assert_eq!((metadata.title.as_str(), metadata.localized_title), ("Game of Thrones", None));
+        assert_eq!((metadata.imdb_id.as_deref(), metadata.total_seasons), (Some("tt0944947"), Some(8)));
+        let season = tmdb.season(&series, 1).unwrap();
+        let episode = season.episode(1).unwrap();
+        assert_eq!(episode.title.as_deref(), Some("Winter Is Coming"));
+        assert_eq!(episode.director.as_deref(), Some("Tim Van Patten"));
+        assert_eq!(episode.still_url.as_ref().unwrap().as_str(), "https://image.tmdb.org/t/p/w300/still.jpg");
+
+        let err = tmdb.season(&series, 9).unwrap_err();
+        assert!(crate::provider::is_not_found(&*err));
+        let err = tmdb.movie(&TitleQuery { title: "Nothing", year: None, imdb_id: Some("tt0000000") }).unwrap_err();
+        assert!(crate::provider::is_not_found(&*err));
+    }
+}
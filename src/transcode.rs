@@ -0,0 +1,291 @@
+use crate::probe::MediaProbe;
+use serde_derive::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+/// URL prefix of the webserver endpoint that streams files through ffmpeg.
+pub const TRANSCODE_MOUNT: &str = "transcode";
+
+/// Containers a Cast device plays without help, by file extension.
+pub const CASTABLE_CONTAINERS: &[&str] = &["mp4", "m4v", "webm"];
+/// Video codecs the default Cast receiver decodes.
+const CASTABLE_VIDEO_CODECS: &[&str] = &["h264", "vp8", "vp9"];
+/// Audio codecs the default Cast receiver decodes.
+const CASTABLE_AUDIO_CODECS: &[&str] = &["aac", "mp3", "opus", "vorbis", "flac"];
+/// Video codecs that can be copied as is into fragmented MP4 and still cast.
+const MP4_VIDEO_COPY_CODECS: &[&str] = &["h264"];
+/// Audio codecs that can be copied as is into fragmented MP4 and still cast.
+const MP4_AUDIO_COPY_CODECS: &[&str] = &["aac", "mp3"];
+
+/// Locations of the ffmpeg tools.
+#[derive(Clone, Debug)]
+pub struct Ffmpeg {
+    pub ffmpeg: PathBuf,
+    pub ffprobe: PathBuf,
+}
+
+impl Default for Ffmpeg {
+    fn default() -> Self {
+        Ffmpeg { ffmpeg: PathBuf::from("ffmpeg"), ffprobe: PathBuf::from("ffprobe") }
+    }
+}
+
+/// Codecs of the first video and audio streams of a file.
+#[derive(Debug, PartialEq, Default)]
+pub struct StreamCodecs {
+    pub video: Option<String>,
+    pub audio: Option<String>,
+}
+
+/// What to do with one stream when converting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamAction {
+    Copy,
+    Transcode,
+}
+
+/// How a file has to be delivered to a Cast device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamPlan {
+    /// The file can be cast as is.
+    Direct,
+    /// Rewrap into fragmented MP4, re-encoding only the streams the Cast device can't decode.
+    /// Copying both streams is a lossless remux.
+    Convert { video: StreamAction, audio: StreamAction },
+}
+
+impl StreamPlan {
+    pub fn is_direct(&self) -> bool {
+        *self == StreamPlan::Direct
+    }
+}
+
+fn has_castable_container(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| CASTABLE_CONTAINERS.contains(&ext.to_lowercase().as_str()))
+}
+
+fn codec_in(codec: &Option<String>, codecs: &[&str]) -> bool {
+    codec.as_deref().is_some_and(|c| codecs.contains(&c))
+}
+
+/// Decides how to deliver `path` given its probed codecs.
+///
+/// Without probe data only the container is known, so anything outside
+/// `CASTABLE_CONTAINERS` is fully transcoded.
+pub fn plan_stream(path: &Path, codecs: Option<&StreamCodecs>) -> StreamPlan {
+    let codecs = match codecs {
+        Some(c) => c,
+        None if has_castable_container(path) => return StreamPlan::Direct,
+        None => return StreamPlan::Convert { video: StreamAction::Transcode, audio: StreamAction::Transcode },
+    };
+
+    let audio_castable = codecs.audio.is_none() || codec_in(&codecs.audio, CASTABLE_AUDIO_CODECS);
+    if has_castable_container(path) && codec_in(&codecs.video, CASTABLE_VIDEO_CODECS) && audio_castable {
+        return StreamPlan::Direct;
+    }
+
+    let action = |copyable: bool| if copyable { StreamAction::Copy } else { StreamAction::Transcode };
+    StreamPlan::Convert {
+        video: action(codec_in(&codecs.video, MP4_VIDEO_COPY_CODECS)),
+        audio: action(codecs.audio.is_none() || codec_in(&codecs.audio, MP4_AUDIO_COPY_CODECS)),
+    }
+}
+
+/// Whether a Cast device can play a file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Castability {
+    /// Castable as is.
+    Direct,
+    /// Castable through the webserver's transcode or HLS endpoint.
+    Transcode,
+    /// Not a playable media file.
+    Unplayable,
+}
+
+/// What the Cast button loads for a file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CastTarget {
+    pub url: String,
+    /// MIME type sent in the Cast load request.
+    pub content_type: String,
+    pub castability: Castability,
+}
+
+/// MIME type of a media file, by extension.
+pub fn media_mime(path: &Path) -> String {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        // mime_guess says video/x-m4v, which Cast receivers don't know
+        Some("m4v") => "video/mp4".to_string(),
+        _ => mime_guess::from_path(path).first_or_octet_stream().to_string(),
+    }
+}
+
+/// Decides whether `path` can be cast, from its extension and, when available, its probe.
+pub fn castability(path: &Path, probe: Option<&MediaProbe>) -> Castability {
+    let playable = match probe {
+        Some(probe) => probe.video.is_some() || !probe.audio_tracks.is_empty(),
+        None => {
+            let mime = media_mime(path);
+            mime.starts_with("video/") || mime.starts_with("audio/")
+        }
+    };
+    if !playable {
+        Castability::Unplayable
+    } else if plan_stream(path, probe.map(|p| p.codecs()).as_ref()).is_direct() {
+        Castability::Direct
+    } else {
+        Castability::Transcode
+    }
+}
+
+/// ffmpeg arguments that write `input` as fragmented MP4 to stdout, starting at `start_secs`.
+pub fn ffmpeg_args(input: &Path, video: StreamAction, audio: StreamAction, start_secs: Option<f64>) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["-nostdin".into(), "-v".into(), "error".into()];
+    if let Some(start) = start_secs.filter(|s| s.is_finite() && *s > 0.0) {
+        // Seeking before -i is fast and, when copying, snaps to the previous keyframe
+        args.extend(["-ss".into(), format!("{:.3}", start).into()]);
+    }
+    args.extend(["-i".into(), input.as_os_str().to_owned()]);
+    args.extend(["-map", "0:v:0", "-map", "0:a:0?"].map(OsString::from));
+    match video {
+        StreamAction::Copy => args.extend(["-c:v", "copy"].map(OsString::from)),
+        StreamAction::Transcode => args.extend(
+            ["-c:v", "libx264", "-preset", "veryfast", "-crf", "21", "-pix_fmt", "yuv420p", "-profile:v", "high", "-level", "4.1"]
+                .map(OsString::from),
+        ),
+    }
+    match audio {
+        StreamAction::Copy => args.extend(["-c:a", "copy"].map(OsString::from)),
+        StreamAction::Transcode => args.extend(["-c:a", "aac", "-b:a", "192k", "-ac", "2"].map(OsString::from)),
+    }
+    args.extend(["-movflags", "frag_keyframe+empty_moov+default_base_moof", "-f", "mp4", "pipe:1"].map(OsString::from));
+    args
+}
+
+/// A running ffmpeg whose stdout is the response body.
+///
+/// Dropping it (e.g. when the client disconnects) kills ffmpeg.
+pub struct FfmpegStream {
+    child: Child,
+    stdout: ChildStdout,
+}
+
+impl Read for FfmpegStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdout.read(buf)
+    }
+}
+
+impl Drop for FfmpegStream {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Ffmpeg {
+    /// Plans delivery of `path`, falling back to the container alone if ffprobe isn't usable.
+    pub fn plan(&self, path: &Path) -> StreamPlan {
+        match self.probe(path) {
+            Ok(probe) => plan_stream(path, Some(&probe.codecs())),
+            Err(e) => {
+                // A missing ffprobe is a supported setup, not worth a warning per file
+                if e.kind() == io::ErrorKind::NotFound {
+                    log::debug!(target: "cli", "ffprobe not available, judging {:?} by extension only", path);
+                } else {
+                    log::warn!(target: "cli", "Could not probe {:?}, judging by extension only: {}", path, e);
+                }
+                plan_stream(path, None)
+            }
+        }
+    }
+
+    /// Starts ffmpeg streaming `input` as fragmented MP4.
+    pub fn stream(&self, input: &Path, video: StreamAction, audio: StreamAction, start_secs: Option<f64>) -> io::Result<FfmpegStream> {
+        let mut child = Command::new(&self.ffmpeg)
+            .args(ffmpeg_args(input, video, audio, start_secs))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("ffmpeg stdout is piped");
+        Ok(FfmpegStream { child, stdout })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codecs(video: &str, audio: Option<&str>) -> StreamCodecs {
+        StreamCodecs { video: Some(video.into()), audio: audio.map(String::from) }
+    }
+
+    #[test]
+    fn test_plan_stream() {
+        use StreamAction::*;
+        let mp4 = Path::new("Movie (2001).mp4");
+        let mkv = Path::new("Movie (2001).MKV");
+        let avi = Path::new("Movie (2001).avi");
+
+        assert_eq!(plan_stream(mp4, Some(&codecs("h264", Some("aac")))), StreamPlan::Direct);
+        assert_eq!(plan_stream(Path::new("a.webm"), Some(&codecs("vp9", Some("opus")))), StreamPlan::Direct);
+        assert_eq!(plan_stream(mp4, Some(&codecs("h264", None))), StreamPlan::Direct);
+        // Castable codecs in the wrong container only need a remux
+        assert_eq!(plan_stream(mkv, Some(&codecs("h264", Some("aac")))), StreamPlan::Convert { video: Copy, audio: Copy });
+        // AC3/DTS audio is re-encoded, the video kept
+        assert_eq!(plan_stream(mkv, Some(&codecs("h264", Some("ac3")))), StreamPlan::Convert { video: Copy, audio: Transcode });
+        assert_eq!(plan_stream(mp4, Some(&codecs("h264", Some("dts")))), StreamPlan::Convert { video: Copy, audio: Transcode });
+        assert_eq!(plan_stream(mp4, Some(&codecs("hevc", Some("aac")))), StreamPlan::Convert { video: Transcode, audio: Copy });
+        assert_eq!(plan_stream(avi, Some(&codecs("mpeg4", Some("mp3")))), StreamPlan::Convert { video: Transcode, audio: Copy });
+        // Without probe data only the container counts
+        assert_eq!(plan_stream(mp4, None), StreamPlan::Direct);
+        assert_eq!(plan_stream(avi, None), StreamPlan::Convert { video: Transcode, audio: Transcode });
+    }
+
+    #[test]
+    fn test_castability() {
+        use crate::probe::{AudioTrack, VideoTrack};
+        let probe = |video: Option<&str>, audio: Option<&str>| MediaProbe {
+            video: video.map(|codec| VideoTrack { codec: codec.into(), ..Default::default() }),
+            audio_tracks: audio.into_iter().map(|codec| AudioTrack { codec: codec.into(), ..Default::default() }).collect(),
+            ..Default::default()
+        };
+        let mp4 = Path::new("Movie (2001).mp4");
+        let mkv = Path::new("Movie (2001).mkv");
+
+        assert_eq!(castability(mp4, Some(&probe(Some("h264"), Some("aac")))), Castability::Direct);
+        assert_eq!(castability(mp4, Some(&probe(Some("hevc"), Some("aac")))), Castability::Transcode);
+        assert_eq!(castability(mkv, Some(&probe(Some("h264"), Some("aac")))), Castability::Transcode);
+        assert_eq!(castability(mkv, Some(&probe(None, None))), Castability::Unplayable);
+        assert_eq!(castability(mp4, None), Castability::Direct);
+        assert_eq!(castability(mkv, None), Castability::Transcode);
+        assert_eq!(castability(Path::new("notes.txt"), None), Castability::Unplayable);
+
+        assert_eq!(media_mime(mp4), "video/mp4");
+        assert_eq!(media_mime(Path::new("a.M4V")), "video/mp4");
+        assert_eq!(media_mime(Path::new("a.webm")), "video/webm");
+        assert_eq!(media_mime(mkv), "video/x-matroska");
+    }
+
+    #[test]
+    fn test_ffmpeg_args() {
+        let args = ffmpeg_args(Path::new("/media/a.mkv"), StreamAction::Copy, StreamAction::Transcode, Some(90.5));
+        let args: Vec<&str> = args.iter().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(args, vec![
+            "-nostdin", "-v", "error", "-ss", "90.500", "-i", "/media/a.mkv", "-map", "0:v:0", "-map", "0:a:0?",
+            "-c:v", "copy", "-c:a", "aac", "-b:a", "192k", "-ac", "2",
+            "-movflags", "frag_keyframe+empty_moov+default_base_moof", "-f", "mp4", "pipe:1",
+        ]);
+        let args = ffmpeg_args(Path::new("a.avi"), StreamAction::Transcode, StreamAction::Copy, None);
+        assert!(!args.contains(&OsString::from("-ss")));
+        assert!(args.contains(&OsString::from("libx264")));
+        let args = ffmpeg_args(Path::new("a.avi"), StreamAction::Copy, StreamAction::Copy, Some(f64::INFINITY));
+        assert!(!args.contains(&OsString::from("-ss")));
+    }
+}
@@ -2,11 +2,20 @@ use crate::media::MediaInfoEquiv; // Keep this for the trait
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{Serialize, Deserialize}; // Add Deserialize
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 use std::fs; // Added for directory reading
 use std::io; // Added for io::Error
 use url::Url;
 use crate::cache::MediaCache; // Import MediaCache
+use crate::probe::MediaProbe;
+use crate::subtitles::TextTrack;
+use crate::transcode::CastTarget;
+use crate::scan::{SkipReason, VideoScanner};
+use crate::nfo::{self, Nfo};
+use crate::overrides::Override;
+use crate::artwork::Artwork;
+use crate::provider::{self, EpisodeMetadata, MetadataProvider, SeasonMetadata, SeriesMetadata, TitleQuery};
 
 /// Holds information parsed directly from a TV Series folder path.
 #[derive(Serialize, Debug, PartialEq, Clone)]
@@ -15,6 +24,8 @@ pub struct TvSeriesMediaInfo {
     pub name: String,
     /// Optional year derived from the folder name.
     pub year: Option<u16>,
+    /// IMDb id hinted in the folder name, e.g. `Show (2005) [tt0412142]`.
+    pub imdb_id: Option<String>,
     /// Path to the series folder.
     pub path: PathBuf,
     /// Episodes belonging to this series.
@@ -29,6 +40,10 @@ pub struct TvSeriesMediaInfo {
     pub poster_url: Option<String>,
     pub imdb_rating: Option<String>,
     pub total_seasons: Option<String>,
+    /// Posters and fanart found in the series folder
+    pub artwork: Artwork,
+    /// Aired episodes per season against the ones on disk, from season listings
+    pub coverage: Vec<SeasonCoverage>,
 }
 
 /// Holds information parsed directly from a TV Episode video file path.
@@ -52,8 +67,16 @@ pub struct TvEpisodeMediaInfo {
     pub air_date: Option<String>,
     /// Episode director from OMDB.
     pub director: Option<String>,
+    /// A frame of the episode, from TMDB.
+    pub still_url: Option<String>,
     /// Play link to the episode file (relative or absolute URI)
     pub media_ref: Option<String>,
+    /// What the Cast button loads; points at the transcode endpoint when needed
+    pub cast: Option<CastTarget>,
+    /// ffprobe details of the episode file
+    pub probe: Option<MediaProbe>,
+    /// Sidecar subtitles converted to WebVTT
+    pub subtitles: Vec<TextTrack>,
 }
 
 impl MediaInfoEquiv for TvEpisodeMediaInfo {
@@ -62,6 +85,31 @@ impl MediaInfoEquiv for TvEpisodeMediaInfo {
     }
 }
 
+impl TvSeriesMediaInfo {
+    /// Overwrites fields of the series, and of its metadata if any, with the ones set in an overrides file.
+    pub fn apply_override(&mut self, info: Option<&mut TvSeriesInfo>, item_override: &Override) {
+        let poster_url = item_override.poster.as_deref().and_then(|p| Url::parse(p).ok());
+        if let Some(info) = info {
+            nfo::set(&mut info.name, item_override.title.clone());
+            nfo::set(&mut info.plot, item_override.plot.clone().map(Some));
+            nfo::set(&mut info.genre, item_override.genre.clone().map(Some));
+            nfo::set(&mut info.poster_url, poster_url.clone().map(Some));
+        }
+        nfo::set(&mut self.name, item_override.title.clone());
+        nfo::set(&mut self.plot, item_override.plot.clone().map(Some));
+        nfo::set(&mut self.genre, item_override.genre.clone().map(Some));
+        nfo::set(&mut self.poster_url, item_override.poster.clone().filter(|_| poster_url.is_some()).map(Some));
+    }
+}
+
+impl TvEpisodeMediaInfo {
+    /// Overwrites fields with the ones set in an overrides file.
+    pub fn apply_override(&mut self, item_override: &Override) {
+        nfo::set(&mut self.title, item_override.title.clone().map(Some));
+        nfo::set(&mut self.plot, item_override.plot.clone().map(Some));
+    }
+}
+
 // ---- OMDB-enriched Struct Definitions ----
 // These structs hold data after enrichment from OMDB
 
@@ -69,45 +117,32 @@ impl MediaInfoEquiv for TvEpisodeMediaInfo {
 pub struct TvSeriesInfo {
     pub name: String,
     pub year: Option<u16>,
-    pub director: String,
+    pub director: Option<String>,
     pub info_url: Url,
-    pub poster_url: Url,
-    pub language: String,
-    pub country: String,
-    pub plot: String,
-    pub genre: String,
-    pub runtime: String,
-    pub released: String,
-    pub rated: String,
-    pub actors: String,
-    pub imdb_rating: String,
-    pub total_seasons: String,
-    pub rotten_tomatoes_rating: Option<String>,
-}
-
-#[derive(Serialize, Deserialize, Debug, PartialEq)] // Add Deserialize, Not Cloned as per original, add if necessary
-pub struct TvEpisodeInfo {
-    pub series_name: String,
-    pub season: u8,
-    pub episode: u8,
-    pub episode_title: String,
-    pub year: Option<u16>, // Year of the episode/series
-    pub path: PathBuf,     // Path to the media file
-    pub series_info: Option<TvSeriesInfo>, // Link to the overall series info
-    pub imdb_rating: Option<String>,
+    pub poster_url: Option<Url>,
+    pub language: Option<String>,
+    pub country: Option<String>,
     pub plot: Option<String>,
-}
-
-impl MediaInfoEquiv for TvEpisodeInfo {
-    fn path(&self) -> &Path {
-        &self.path
-    }
+    pub genre: Option<String>,
+    pub runtime: Option<String>,
+    pub released: Option<String>,
+    pub rated: Option<String>,
+    pub actors: Option<String>,
+    pub imdb_rating: Option<String>,
+    pub total_seasons: Option<String>,
+    pub rotten_tomatoes_rating: Option<String>,
+    /// Title in the language asked of TMDB, shown when it differs from `name`
+    #[serde(default)]
+    pub localized_title: Option<String>,
+    /// Provider backdrop, shown when there's no local fanart
+    #[serde(default)]
+    pub backdrop_url: Option<Url>,
 }
 
 // ---- Structs for HTML Template Data (using OMDB-enriched info) ----
 
-// The `TvSeriesInfo` and `TvEpisodeInfo` structs defined above
-// will be primary sources for these template structs.
+// The `TvSeriesInfo` struct defined above
+// will be the primary source for these template structs.
 
 /// Data structure for rendering a full TV series page.
 #[derive(Serialize, Debug)]
@@ -117,13 +152,51 @@ pub struct SeriesPageTemplateData {
     /// List of seasons, each containing its episodes.
     pub seasons: Vec<SeasonTemplateData>,
     pub name: String, // Series name as detected from folder structure
+    /// Exported poster, or the metadata provider's.
+    pub poster_url: Option<String>,
+    /// Resized variants of the exported poster.
+    pub poster_srcset: Option<String>,
+    /// Local fanart shown behind the page header.
+    pub fanart_url: Option<String>,
 }
 
 /// Data structure for a single season within a series page template.
 #[derive(Serialize, Deserialize, Debug)] // Add Deserialize
 pub struct SeasonTemplateData {
     pub season_number: u8,
+    /// Thumbnail of the local season poster, if any.
+    #[serde(default)]
+    pub poster_url: Option<String>,
     pub episodes: Vec<EpisodeTemplateData>,
+    /// Aired episodes on disk and aired in all, when the season listing is known.
+    #[serde(default)]
+    pub coverage: Option<(usize, usize)>,
+}
+
+/// Which aired episodes of a season have a file.
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct SeasonCoverage {
+    pub season_number: u8,
+    /// Aired episodes that have a file.
+    pub on_disk: usize,
+    pub aired: usize,
+    /// Aired episodes without a file.
+    pub missing: Vec<EpisodeMetadata>,
+}
+
+impl SeasonCoverage {
+    /// Compares a season listing with the episode numbers on disk. Episodes with no air date, or one
+    /// after `today` (`YYYY-MM-DD`), don't count as aired.
+    pub fn new(season_number: u8, listing: &SeasonMetadata, on_disk: &BTreeSet<u8>, today: &str) -> SeasonCoverage {
+        let aired: Vec<&EpisodeMetadata> = listing.episodes.iter()
+            .filter(|e| e.released.as_deref().is_some_and(|released| released <= today))
+            .collect();
+        let missing: Vec<EpisodeMetadata> = aired.iter()
+            .filter(|e| !on_disk.contains(&e.episode))
+            .map(|e| (*e).clone())
+            .collect();
+        SeasonCoverage { season_number, on_disk: aired.len() - missing.len(), aired: aired.len(), missing }
+    }
 }
 
 /// Data structure for a single episode within a season.
@@ -143,6 +216,127 @@ pub struct EpisodeTemplateData {
     pub director: Option<String>,
     /// Generated URL/path to the media file for playback.
     pub media_ref: String,
+    /// What the Cast button loads, with its MIME type and castability verdict.
+    #[serde(default)]
+    pub cast: Option<CastTarget>,
+    /// ffprobe details of the episode file.
+    #[serde(default)]
+    pub probe: Option<MediaProbe>,
+    /// Sidecar subtitles converted to WebVTT.
+    #[serde(default)]
+    pub subtitles: Vec<TextTrack>,
+    /// A frame of the episode, from TMDB.
+    #[serde(default)]
+    pub still_url: Option<String>,
+    /// An aired episode without a file, shown greyed out.
+    #[serde(default)]
+    pub missing: bool,
+}
+
+impl TvSeriesInfo {
+    /// Builds the series info from a `tvshow.nfo` alone, without a metadata provider.
+    pub fn from_nfo(nfo: &Nfo, series_name: &str) -> TvSeriesInfo {
+        let mut series_info = TvSeriesInfo {
+            name: series_name.to_string(),
+            year: None,
+            director: None,
+            info_url: Url::parse_with_params("https://www.imdb.com/find", [("q", series_name)]).unwrap(),
+            poster_url: None,
+            language: None,
+            country: None,
+            plot: None,
+            genre: None,
+            runtime: None,
+            released: None,
+            rated: None,
+            actors: None,
+            imdb_rating: None,
+            total_seasons: None,
+            rotten_tomatoes_rating: None,
+            localized_title: None,
+            backdrop_url: None,
+        };
+        series_info.apply_nfo(nfo);
+        series_info
+    }
+
+    /// Builds the series info from what the metadata providers found.
+    fn from_metadata(metadata: SeriesMetadata) -> TvSeriesInfo {
+        let search_url = Url::parse_with_params("https://www.imdb.com/find", [("q", &metadata.title)]).unwrap();
+        TvSeriesInfo {
+            info_url: metadata.imdb_id.as_deref().and_then(crate::media::imdb_url).unwrap_or(search_url),
+            localized_title: metadata.localized_title.filter(|t| *t != metadata.title),
+            name: metadata.title,
+            year: metadata.year,
+            director: metadata.director,
+            poster_url: metadata.poster_url,
+            language: metadata.language,
+            country: metadata.country,
+            plot: metadata.plot,
+            genre: metadata.genre,
+            runtime: metadata.runtime,
+            released: metadata.released,
+            rated: metadata.rated,
+            actors: metadata.actors,
+            imdb_rating: metadata.imdb_rating,
+            total_seasons: metadata.total_seasons.map(|t| t.to_string()),
+            rotten_tomatoes_rating: metadata.rotten_tomatoes_rating,
+            backdrop_url: metadata.backdrop_url,
+        }
+    }
+
+    /// Overrides fields with the ones the NFO has.
+    pub fn apply_nfo(&mut self, nfo: &Nfo) {
+        nfo::set(&mut self.name, nfo.title.clone());
+        nfo::set(&mut self.year, nfo.year.map(Some));
+        nfo::set(&mut self.director, nfo.director().map(Some));
+        nfo::set(&mut self.info_url, nfo.imdb_url());
+        nfo::set(&mut self.poster_url, nfo.poster_url.clone().map(Some));
+        nfo::set(&mut self.country, nfo.country().map(Some));
+        nfo::set(&mut self.plot, nfo.plot.clone().map(Some));
+        nfo::set(&mut self.genre, nfo.genre().map(Some));
+        nfo::set(&mut self.runtime, nfo.runtime().map(Some));
+        nfo::set(&mut self.released, nfo.premiered.clone().map(Some));
+        nfo::set(&mut self.rated, nfo.mpaa.clone().map(Some));
+        nfo::set(&mut self.actors, nfo.actor_names().map(Some));
+        nfo::set(&mut self.imdb_rating, nfo.rating.clone().map(Some));
+    }
+}
+
+impl EpisodeTemplateData {
+    /// Builds the episode data from an episode NFO alone, without a metadata provider.
+    pub fn from_nfo(nfo: &Nfo, episode: u8) -> EpisodeTemplateData {
+        let mut episode_data = EpisodeTemplateData::from_metadata(&EpisodeMetadata { episode, ..Default::default() });
+        episode_data.apply_nfo(nfo);
+        episode_data
+    }
+
+    /// Builds the episode data from what the metadata providers found, e.g. its entry in a season listing.
+    pub fn from_metadata(metadata: &EpisodeMetadata) -> EpisodeTemplateData {
+        EpisodeTemplateData {
+            title: metadata.title.clone().unwrap_or_else(|| format!("Episode {}", metadata.episode)),
+            episode_number: metadata.episode,
+            plot: metadata.plot.clone(),
+            imdb_rating: metadata.imdb_rating.clone(),
+            aired_date: metadata.released.clone(),
+            director: metadata.director.clone(),
+            media_ref: String::new(),
+            cast: None,
+            probe: None,
+            subtitles: Vec::new(),
+            still_url: metadata.still_url.as_ref().map(Url::to_string),
+            missing: false,
+        }
+    }
+
+    /// Overrides fields with the ones the NFO has.
+    pub fn apply_nfo(&mut self, nfo: &Nfo) {
+        nfo::set(&mut self.title, nfo.title.clone());
+        nfo::set(&mut self.plot, nfo.plot.clone().map(Some));
+        nfo::set(&mut self.imdb_rating, nfo.rating.clone().map(Some));
+        nfo::set(&mut self.aired_date, nfo.premiered.clone().map(Some));
+        nfo::set(&mut self.director, nfo.director().map(Some));
+    }
 }
 
 lazy_static! {
@@ -215,7 +409,11 @@ pub fn parse_tv_episode_path(
                 imdb_rating: None,
                 air_date: None,
                 director: None,
+                still_url: None,
                 media_ref: None,
+                cast: None,
+                probe: None,
+                subtitles: Vec::new(),
             });
         }
     }
@@ -223,19 +421,23 @@ pub fn parse_tv_episode_path(
 }
 
 pub fn parse_series_folder_name(folder_path: &std::path::Path) -> (Option<String>, Option<u8>, Option<u16>) {
-    let folder_name = folder_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let (folder_name, _imdb_id) = crate::media::strip_imdb_hint(folder_path.file_name().and_then(|n| n.to_str()).unwrap_or(""));
+    let folder_name = folder_name.as_str();
 
     // Regex to find a year pattern (YYYY) within a string.
     let year_finder_re = Regex::new(r"(?:\(|\[|\b)(\d{4})(?:\)|\]|\b)").unwrap();
     // Regex to help clean a name by removing a trailing year pattern.
     let name_cleaner_re = Regex::new(r"^(.*?)(?:[._ ]*(?:\(|\[)?\d{4}(?:\)|\])?)?[._ ]*$").unwrap();
+    // Regexes used to decide whether a year belongs to the series name.
+    let paren_year_re = Regex::new(r"\(\d{4}\)").unwrap();
+    let year_before_sxx_re = Regex::new(r"(?i)(\d{4})[._ ]*S\d{1,2}$").unwrap();
 
     let mut parsed_year: Option<u16> = None;
     // First, try to find a year anywhere in the folder name.
     if let Some(caps) = year_finder_re.captures(folder_name) {
         if let Ok(y) = caps.get(1).unwrap().as_str().parse::<u16>() {
             // Basic sanity check for a plausible year.
-            if y >= 1900 && y < 2050 {
+            if (1900..2050).contains(&y) {
                 parsed_year = Some(y);
             }
         }
@@ -264,7 +466,7 @@ pub fn parse_series_folder_name(folder_path: &std::path::Path) -> (Option<String
                 if parsed_year.is_none() {
                     if let Some(ycaps) = year_finder_re.captures(&series_name_str) {
                          if let Ok(y) = ycaps.get(1).unwrap().as_str().parse::<u16>() {
-                            if y >= 1900 && y < 2050 {
+                            if (1900..2050).contains(&y) {
                                 parsed_year = Some(y);
                             }
                         }
@@ -273,11 +475,9 @@ pub fn parse_series_folder_name(folder_path: &std::path::Path) -> (Option<String
                 // --- FIX: Only strip trailing year if it is not part of the main name (not in parens, not before Sxx/Season) ---
                 if parsed_year.is_some() {
                     // Only strip if not in parens and not immediately before Sxx/Season
-                    let paren_year_re = Regex::new(r"\(\d{4}\)").unwrap();
                     // Check if the year is in parens in the name
                     let year_in_parens = paren_year_re.is_match(&series_name_str);
                     // Only keep year if it's directly before Sxx (e.g. Series.Name.2023.S01), not before 'season'
-                    let year_before_sxx_re = Regex::new(r"(?i)(\d{4})[._ ]*S\d{1,2}$").unwrap();
                     let year_before_sxx = year_before_sxx_re.is_match(folder_name);
                     if !year_in_parens && !year_before_sxx {
                         if let Some(clean_caps) = name_cleaner_re.captures(&series_name_str) {
@@ -311,27 +511,35 @@ pub fn parse_series_folder_name(folder_path: &std::path::Path) -> (Option<String
 }
 
 
-// Helper function to check for common video file extensions
-fn is_video_file(path: &Path) -> bool {
-    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-        matches!(ext.to_lowercase().as_str(), "mkv" | "mp4" | "avi" | "mov" | "wmv" | "flv" | "webm")
-    } else {
-        false
-    }
-}
-
-// Helper function to scan files within a given folder and collect episode information
+// Helper function to scan a folder and its subfolders and collect episode information
 fn collect_episodes_from_folder(
     folder_path: &Path,
     series_name: &str, // Ensure this is the clean series name, not including season/year if parsed separately
     season_from_folder: Option<u8>, // Contextual season number from folder structure
+    depth: usize, // Depth of folder_path below the TV library folder
+    scanner: &VideoScanner,
     episodes_list: &mut Vec<TvEpisodeMediaInfo>,
 ) -> Result<(), io::Error> {
     for file_entry in fs::read_dir(folder_path)? {
         let file_entry = file_entry?;
         let file_path = file_entry.path();
 
-        if file_path.is_file() && is_video_file(&file_path) {
+        if file_path.is_dir() {
+            if depth + 1 > scanner.max_depth {
+                log::info!(target: "cli", "Skipped {:?}: {}", file_path, SkipReason::TooDeep(scanner.max_depth));
+                continue;
+            }
+            // A season subfolder (e.g. "Season 1", "S02") sets the season; others (e.g. "Extras") inherit it
+            let (_name_from_sub_dir, season_from_sub_dir, _year_from_sub_dir) = parse_series_folder_name(&file_path);
+            collect_episodes_from_folder(
+                &file_path,
+                series_name,
+                season_from_sub_dir.or(season_from_folder),
+                depth + 1,
+                scanner,
+                episodes_list,
+            )?;
+        } else if file_path.is_file() && scanner.accept(&file_path, depth + 1) {
             if let Some(episode_info) = parse_tv_episode_path(
                 &file_path,
                 Some(series_name.to_string()),
@@ -340,7 +548,6 @@ fn collect_episodes_from_folder(
                 episodes_list.push(episode_info);
             }
         }
-        // Not recursing into subdirectories from here; scan_tv_directory handles structure.
     }
     Ok(())
 }
@@ -350,7 +557,7 @@ fn collect_episodes_from_folder(
 /// It navigates through series folders and season subfolders, using the
 /// parsing functions to extract metadata for each episode and associate
 /// it with its parent series.
-pub fn scan_tv_directory(tv_base_path: &Path) -> Result<Vec<TvSeriesMediaInfo>, io::Error> { // Changed return type
+pub fn scan_tv_directory(tv_base_path: &Path, scanner: &VideoScanner) -> Result<Vec<TvSeriesMediaInfo>, io::Error> { // Changed return type
     let mut all_series_info: Vec<TvSeriesMediaInfo> = Vec::new();
 
     for series_entry in fs::read_dir(tv_base_path)? {
@@ -369,62 +576,17 @@ pub fn scan_tv_directory(tv_base_path: &Path) -> Result<Vec<TvSeriesMediaInfo>,
         if let Some(current_series_name_str) = parsed_series_name {
             let mut current_series_episodes: Vec<TvEpisodeMediaInfo> = Vec::new();
 
-            // Case 1: The main series directory itself specifies a season (e.g., "My Show Season 1")
-            // In this case, parsed_series_name_str is "My Show", and parsed_season_from_series_folder is Some(1)
-            if let Some(main_dir_season_num) = parsed_season_from_series_folder {
-                collect_episodes_from_folder(
-                    &series_folder_path, // Scan files directly within this "Series Season X" directory
-                    &current_series_name_str,
-                    Some(main_dir_season_num), // Season context from this folder
-                    &mut current_series_episodes,
-                )?;
-            } else {
-                // Case 2: The main series directory is just "My Show".
-                // Look for season subfolders OR episode files directly within it.
-                for item_entry in fs::read_dir(&series_folder_path)? {
-                    let item_entry = item_entry?;
-                    let item_path = item_entry.path();
-
-                    if item_path.is_dir() {
-                        // Check if this subdirectory is a season folder (e.g., "Season 1", "S02")
-                        // or another type of subfolder (e.g. "Extras")
-                        let (_name_from_season_sub_dir, season_from_sub_dir, _year_from_sub_dir) =
-                            parse_series_folder_name(&item_path);
-
-                        if let Some(season_num) = season_from_sub_dir {
-                            // It's a season subfolder
-                            collect_episodes_from_folder(
-                                &item_path, // Scan within this season subfolder
-                                &current_series_name_str, // Inherit series name
-                                Some(season_num),         // Season context from this subfolder
-                                &mut current_series_episodes,
-                            )?;
-                        } else {
-                            // It's a subdirectory but not recognized as a season folder (e.g., "Extras", "Specials").
-                            // Scan it for episodes, relying on filenames for season/episode info.
-                            // The series name is still current_series_name_str.
-                            // No season context is provided by this sub-folder itself.
-                            collect_episodes_from_folder(
-                                &item_path,
-                                &current_series_name_str,
-                                None, 
-                                &mut current_series_episodes,
-                            )?;
-                        }
-                    } else if item_path.is_file() && is_video_file(&item_path) {
-                        // It's an episode file directly in the series folder (e.g., "My Show/My.Show.S01E01.mkv")
-                        // No season context from the immediate parent folder (series_folder_path).
-                        if let Some(episode_info) = parse_tv_episode_path(
-                            &item_path,
-                            Some(current_series_name_str.clone()),
-                            None, // No season context from this level of folder
-                        ) {
-                            current_series_episodes.push(episode_info);
-                        }
-                    }
-                }
-            }
-            
+            // The main series directory may itself specify a season (e.g., "My Show Season 1"),
+            // otherwise season subfolders or the episode file names do.
+            collect_episodes_from_folder(
+                &series_folder_path,
+                &current_series_name_str,
+                parsed_season_from_series_folder,
+                1,
+                scanner,
+                &mut current_series_episodes,
+            )?;
+
             // After collecting all episodes for current_series_name_str,
             // create and add the TvSeriesMediaInfo if episodes were found.
             if !current_series_episodes.is_empty() {
@@ -434,6 +596,7 @@ pub fn scan_tv_directory(tv_base_path: &Path) -> Result<Vec<TvSeriesMediaInfo>,
                 all_series_info.push(TvSeriesMediaInfo {
                     name: current_series_name_str.clone(),
                     year: parsed_year_from_series_folder,
+                    imdb_id: series_folder_path.file_name().and_then(|n| n.to_str()).and_then(|n| crate::media::strip_imdb_hint(n).1),
                     path: series_folder_path.to_path_buf(),
                     episodes: current_series_episodes,
                     poster_url: None, // Do not use placeholder, will be set from OMDB elsewhere if available
@@ -445,6 +608,8 @@ pub fn scan_tv_directory(tv_base_path: &Path) -> Result<Vec<TvSeriesMediaInfo>,
                     country: None,
                     imdb_rating: None,
                     total_seasons: None,
+                    artwork: Artwork::default(),
+                    coverage: Vec::new(),
                 });
             }
         }
@@ -456,148 +621,222 @@ pub fn scan_tv_directory(tv_base_path: &Path) -> Result<Vec<TvSeriesMediaInfo>,
 }
 
 pub fn get_series_info(
-    omdb_api_key: &str,
+    provider: Option<&dyn MetadataProvider>, // Without one, only NFO files and the cache are used
     series_name: &str,
+    series_nfo: Option<&Nfo>, // tvshow.nfo of the series folder
+    imdb_id: Option<&str>, // Pinned by the NFO or the folder name
     cache: &Option<MediaCache>,
 ) -> Result<TvSeriesInfo, Box<dyn std::error::Error>> {
-    // Try to get from cache first
+    if let Some(nfo) = series_nfo.filter(|nfo| nfo.is_complete()) {
+        log::info!(target: "cli", "Using NFO for TV series: {}", series_name);
+        return Ok(TvSeriesInfo::from_nfo(nfo, series_name));
+    }
+    let pinned_url = imdb_id.and_then(crate::media::imdb_url);
+
+    // Try to get from cache first; an entry from before an IMDb id was pinned to another title doesn't count
     if let Some(media_cache) = cache {
-        if let Some(cached_series_info) = media_cache.get_tv_series_by_name(series_name).map_err(|e| e.to_string())? {
-            log::info!(target: "cli", "Cache hit for TV series: {}", series_name);
-            return Ok(cached_series_info);
+        if let Some(mut cached_series_info) = media_cache.get_tv_series_by_name(series_name).map_err(|e| e.to_string())? {
+            if pinned_url.as_ref().is_none_or(|url| *url == cached_series_info.info_url) {
+                log::info!(target: "cli", "Cache hit for TV series: {}", series_name);
+                if let Some(nfo) = series_nfo {
+                    cached_series_info.apply_nfo(nfo);
+                }
+                return Ok(cached_series_info);
+            }
         }
     }
 
-    log::info!(target: "cli", "Cache miss for TV series: {}. Fetching from OMDB.", series_name);
-    let r = crate::media::omdb_get_metadata(omdb_api_key, crate::media::OmdbType::Series, series_name, None)?;
-
-    match r {
-        crate::media::OmdbResponse::Series { .. } => {
-            let info_url = r.imdb_url();
-            if let crate::media::OmdbResponse::Series {
-                title,
-                year, // This is a String, e.g., "2008-2013" or "2008-"
-                director,
-                poster,
-                language,
-                country,
-                plot,
-                genre,
-                runtime,
-                released,
-                rated,
-                actors,
-                imdb_rating,
-                total_seasons,
-                ratings,
-                ..
-            } = r
-            {
-                let rotten_tomatoes_rating = ratings.iter()
-                    .find(|r_item| r_item.source == "Rotten Tomatoes")
-                    .map(|r_item| r_item.value.to_string());
-
-                // Safely parse the year string (e.g., "2008-2013" or "2008-") to get the start year.
-                let parsed_year: Option<u16> = year
-                    .split(|c: char| !c.is_digit(10))
-                    .next()
-                    .and_then(|s| s.parse::<u16>().ok());
-
-                let series_info = TvSeriesInfo {
-                    name: title,
-                    year: parsed_year, 
-                    director,
-                    info_url,
-                    poster_url: Url::parse(&poster)?,
-                    language,
-                    country,
-                    plot,
-                    genre,
-                    runtime,
-                    released,
-                    rated,
-                    actors,
-                    imdb_rating,
-                    total_seasons,
-                    rotten_tomatoes_rating,
-                };
+    let Some(provider) = provider else {
+        return match series_nfo {
+            Some(nfo) => Ok(TvSeriesInfo::from_nfo(nfo, series_name)),
+            None => Err(format!("No metadata provider to look up TV series: {}", series_name).into()),
+        };
+    };
+    log::info!(target: "cli", "Cache miss for TV series: {}. Fetching from {}.", series_name, provider.name());
+    let query = format!("series:{}:{}", series_name, imdb_id.unwrap_or_default());
+    let title = series_nfo.and_then(|n| n.title.as_deref()).unwrap_or(series_name);
+    let metadata = crate::cache::unless_not_found(cache, &query, || {
+        provider.series(&TitleQuery { title, year: None, imdb_id })
+    })?;
+    let mut series_info = TvSeriesInfo::from_metadata(metadata);
+
+    // Store in cache
+    if let Some(media_cache) = cache {
+        if let Err(e) = media_cache.store_tv_series(&series_info) {
+            log::error!("Failed to store TV series '{}' in cache: {}", series_info.name, e);
+        } else {
+            log::info!(target: "cli", "Stored TV series '{}' in cache", series_info.name);
+        }
+    }
+    if let Some(nfo) = series_nfo {
+        series_info.apply_nfo(nfo);
+    }
+    Ok(series_info)
+}
 
-                // Store in cache
-                if let Some(media_cache) = cache {
-                    if let Err(e) = media_cache.store_tv_series(&series_info) {
-                        log::error!("Failed to store TV series '{}' in cache: {}", series_info.name, e);
-                    } else {
-                        log::info!(target: "cli", "Stored TV series '{}' in cache", series_info.name);
+/// The listing of a season, from the cache unless it's older than `SEASON_REFRESH_SECS` and may
+/// be outdated: `episode` is missing from it, or without an `episode`, some of it hasn't aired yet.
+/// It's cached by the series' IMDb id when known, so same-named series don't share listings.
+pub fn get_season_listing(
+    provider: &dyn MetadataProvider,
+    series_name: &str,
+    series_imdb_id: Option<&str>,
+    season: u8,
+    episode: Option<u8>,
+    cache: &Option<MediaCache>,
+) -> Result<SeasonMetadata, Box<dyn std::error::Error>> {
+    let series_key = series_imdb_id.unwrap_or(series_name);
+    if let Some(media_cache) = cache {
+        match media_cache.get_tv_season(series_key, season) {
+            Ok(Some((listing, fetched_at))) => {
+                let listed = match episode {
+                    Some(e) => listing.episode(e).is_some(),
+                    None => {
+                        let today = today();
+                        listing.episodes.iter().all(|e| e.released.as_deref().is_some_and(|released| released <= today.as_str()))
                     }
+                };
+                if listed || crate::cache::now_secs() - fetched_at < crate::cache::SEASON_REFRESH_SECS {
+                    return Ok(listing);
                 }
-                Ok(series_info)
-            } else {
-                unreachable!("Should be OmdbResponse::Series variant");
             }
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to read season listing '{}' S{:02} from cache: {}", series_name, season, e),
+        }
+    }
+
+    log::info!(target: "cli", "Fetching season listing from {}: {} S{:02}", provider.name(), series_name, season);
+    let query = format!("season:{}:{}", series_key, season);
+    let listing = crate::cache::unless_not_found(cache, &query, || {
+        provider.season(&TitleQuery { title: series_name, year: None, imdb_id: series_imdb_id }, season)
+    })?;
+    if let Some(media_cache) = cache {
+        if let Err(e) = media_cache.store_tv_season(series_key, season, &listing) {
+            log::error!("Failed to store season listing '{}' S{:02} in cache: {}", series_name, season, e);
         }
-        _ => Err(format!("Unexpected OMDB response type for series: {}", series_name).into()),
     }
+    Ok(listing)
 }
 
+/// Episode details from its NFO, the cache, or the metadata providers. They're asked for the season
+/// listing, which serves all its episodes with one request; only with `episode_details`, or for episodes
+/// the listing lacks, each episode is looked up on its own for its plot and director.
+#[allow(clippy::too_many_arguments)]
 pub fn get_episode_info(
-    omdb_api_key: &str,
+    provider: Option<&dyn MetadataProvider>, // Without one, only NFO files and the cache are used
     series_name: &str,
+    series_imdb_id: Option<&str>, // From tvshow.nfo or the folder name; pins the lookup to that series
+    episode_path: &Path,
     season: u8,
     episode: u8,
+    imdb_id: Option<&str>, // Pinned by an override; wins over the episode NFO's
+    episode_details: bool,
     cache: &Option<MediaCache>,
 ) -> Result<EpisodeTemplateData, Box<dyn std::error::Error>> {
+    let episode_nfo = nfo::find_episode_nfo(episode_path, season, episode);
+    if let Some(nfo) = episode_nfo.as_ref().filter(|nfo| nfo.is_complete()) {
+        log::info!(target: "cli", "Using NFO for TV episode: {} S{:02}E{:02}", series_name, season, episode);
+        return Ok(EpisodeTemplateData::from_nfo(nfo, episode));
+    }
+    let apply_nfo = |mut episode_data: EpisodeTemplateData| {
+        if let Some(nfo) = &episode_nfo {
+            episode_data.apply_nfo(nfo);
+        }
+        episode_data
+    };
+
     // Try to get from cache first
     if let Some(media_cache) = cache {
         if let Some(cached_episode_info) = media_cache.get_tv_episode(series_name, season, episode).map_err(|e| e.to_string())? {
             log::info!(target: "cli", "Cache hit for TV episode: {} S{:02}E{:02}", series_name, season, episode);
-            return Ok(cached_episode_info);
+            return Ok(apply_nfo(cached_episode_info));
         }
     }
 
-    log::info!(target: "cli", "Cache miss for TV episode: {} S{:02}E{:02}. Fetching from OMDB.", series_name, season, episode);
-    // Use the new omdb_get_episode_metadata function
-    let r = crate::media::omdb_get_episode_metadata(
-        omdb_api_key,
-        series_name,
-        season,
-        episode,
-    )?;
-
-    // The response `r` is already confirmed to be OmdbResponse::Episode by omdb_get_episode_metadata or it would have errored.
-    if let crate::media::OmdbResponse::Episode {
-        title,
-        // year, // Episode year might differ or be part of a range, handle carefully
-        plot,
-        imdb_rating,
-        released, // This is air date for episodes
-        director, // If available for episode
-        ..
-    } = r
-    {
-        let episode_data = EpisodeTemplateData {
-            title,
-            episode_number: episode,
-            plot: Some(plot),
-            imdb_rating: Some(imdb_rating),
-            aired_date: Some(released), // OMDB 'Released' is air date for episodes
-            director: Some(director), // director is String, so Some(director) is Option<String>
-            media_ref: String::new(), // This will be populated later by the caller
+    let Some(provider) = provider else {
+        return match &episode_nfo {
+            Some(nfo) => Ok(EpisodeTemplateData::from_nfo(nfo, episode)),
+            None => Err(format!("No metadata provider to look up TV episode: {} S{:02}E{:02}", series_name, season, episode).into()),
         };
+    };
+    let episode_imdb_id = imdb_id.or_else(|| episode_nfo.as_ref().and_then(|n| n.imdb_id.as_deref()));
+    if !episode_details && episode_imdb_id.is_none() {
+        let listing = get_season_listing(provider, series_name, series_imdb_id, season, Some(episode), cache)?;
+        if let Some(listed) = listing.episode(episode) {
+            // Not stored as an episode, so that turning on episode details looks it up later
+            return Ok(apply_nfo(EpisodeTemplateData::from_metadata(listed)));
+        }
+    }
+    log::info!(target: "cli", "Cache miss for TV episode: {} S{:02}E{:02}. Fetching from {}.", series_name, season, episode, provider.name());
+    let query = format!("episode:{}:{}:{}", episode_imdb_id.or(series_imdb_id).unwrap_or(series_name), season, episode);
+    let series = TitleQuery { title: series_name, year: None, imdb_id: series_imdb_id };
+    let metadata = crate::cache::unless_not_found(cache, &query, || provider.episode(&series, season, episode, episode_imdb_id))?;
+    let episode_data = EpisodeTemplateData::from_metadata(&metadata);
+
+    // Store in cache
+    if let Some(media_cache) = cache {
+        if let Err(e) = media_cache.store_tv_episode(series_name, season, episode, &episode_data) {
+            log::error!("Failed to store TV episode '{}' S{:02}E{:02} in cache: {}", series_name, season, episode, e);
+        } else {
+            log::info!(target: "cli", "Stored TV episode '{}' S{:02}E{:02} in cache", series_name, season, episode);
+        }
+    }
+    Ok(apply_nfo(episode_data))
+}
 
-        // Store in cache
-        if let Some(media_cache) = cache {
-            // Corrected call to store_tv_episode
-            if let Err(e) = media_cache.store_tv_episode(series_name, season, episode, &episode_data) {
-                log::error!("Failed to store TV episode '{}' S{:02}E{:02} in cache: {}", series_name, season, episode, e);
-            } else {
-                log::info!(target: "cli", "Stored TV episode '{}' S{:02}E{:02} in cache", series_name, season, episode);
+/// Today's UTC date as `YYYY-MM-DD`, the form of air dates in season listings.
+pub fn today() -> String {
+    let days = crate::cache::now_secs().div_euclid(24 * 60 * 60);
+    // Civil date from days since 1970-01-01, after Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Aired episodes against the ones on disk for seasons 1 to `totalSeasons` (of the series or its
+/// season listings) and any season on disk.
+/// Without a metadata provider only cached season listings are used; seasons without a listing are left out.
+pub fn get_series_coverage(
+    provider: Option<&dyn MetadataProvider>,
+    series_name: &str,
+    series_imdb_id: Option<&str>,
+    total_seasons: Option<&str>,
+    episodes: &[TvEpisodeMediaInfo],
+    cache: &Option<MediaCache>,
+) -> Result<Vec<SeasonCoverage>, Box<dyn std::error::Error>> {
+    let total_seasons: u8 = total_seasons.and_then(|t| t.trim().parse().ok()).unwrap_or(0);
+    let mut seasons: BTreeSet<u8> = (1..=total_seasons).chain(episodes.iter().map(|e| e.season).filter(|s| *s > 0)).collect();
+    let today = today();
+    let mut coverage = Vec::new();
+    while let Some(season) = seasons.pop_first() {
+        let listing = match provider {
+            Some(provider) => get_season_listing(provider, series_name, series_imdb_id, season, None, cache),
+            None => match cache.as_ref().map(|c| c.get_tv_season(series_imdb_id.unwrap_or(series_name), season)) {
+                Some(Ok(Some((listing, _)))) => Ok(listing),
+                _ => continue,
+            },
+        };
+        match listing {
+            Ok(listing) => {
+                // Listings know the season count too, e.g. when the series info came from an NFO
+                if let Some(next_season) = season.checked_add(1) {
+                    seasons.extend(next_season..=listing.total_seasons.unwrap_or(0));
+                }
+                let on_disk: BTreeSet<u8> = episodes.iter().filter(|e| e.season == season).map(|e| e.episode).collect();
+                coverage.push(SeasonCoverage::new(season, &listing, &on_disk, &today));
             }
+            Err(e) if provider::is_fatal(&*e) => return Err(e),
+            Err(e) => log::info!(target: "cli", "No season listing for {} S{:02}: {}", series_name, season, e),
         }
-        Ok(episode_data)
-    } else {
-        // This case should ideally not be reached if omdb_get_episode_metadata works as expected (errors out if not an episode)
-        Err(format!("OMDB response was not an episode for: {} S{:02}E{:02}", series_name, season, episode).into())
     }
+    Ok(coverage)
 }
 
 // ---- Tests ----
@@ -607,7 +846,141 @@ mod tests {
     use super::*;
     use std::path::Path;
     use std::fs; // Ensure fs is imported for test setup
-    use tempfile; // Import tempfile crate
+
+    #[test]
+    fn test_season_coverage() {
+        let listed = |episode: u8, released: Option<&str>| EpisodeMetadata {
+            episode,
+            title: Some(format!("Episode {}", episode)),
+            released: released.map(String::from),
+            imdb_id: Some(format!("tt000000{}", episode)),
+            ..Default::default()
+        };
+        let listing = SeasonMetadata {
+            total_seasons: Some(1),
+            episodes: vec![
+                listed(1, Some("2020-01-01")),
+                listed(2, Some("2020-01-08")),
+                listed(3, Some("2020-01-15")),
+                listed(4, Some("2099-01-01")),
+                listed(5, None),
+            ],
+        };
+        let coverage = SeasonCoverage::new(1, &listing, &BTreeSet::from([1, 3, 4]), "2020-06-01");
+        assert_eq!((coverage.on_disk, coverage.aired), (2, 3));
+        assert_eq!(coverage.missing, vec![listed(2, Some("2020-01-08"))]);
+        assert_eq!(today().len(), "2020-06-01".len());
+    }
+
+    #[test]
+    fn test_series_override() {
+        let mut series = TvSeriesMediaInfo {
+            name: "Show".to_string(),
+            year: None,
+            imdb_id: None,
+            path: PathBuf::from("tv/Show"),
+            episodes: Vec::new(),
+            released: None,
+            genre: None,
+            plot: Some("From the folder".to_string()),
+            actors: None,
+            language: None,
+            country: None,
+            poster_url: None,
+            imdb_rating: None,
+            total_seasons: None,
+            artwork: Artwork::default(),
+            coverage: Vec::new(),
+        };
+        let mut info = TvSeriesInfo::from_nfo(&Nfo { plot: Some("From the NFO".to_string()), ..Default::default() }, "Show");
+        let item_override = Override {
+            title: Some("The Show".to_string()),
+            plot: Some("Fixed".to_string()),
+            poster: Some("https://example.com/poster.jpg".to_string()),
+            ..Default::default()
+        };
+        series.apply_override(Some(&mut info), &item_override);
+        assert_eq!((series.name.as_str(), series.plot.as_deref()), ("The Show", Some("Fixed")));
+        assert_eq!((info.name.as_str(), info.plot.as_deref()), ("The Show", Some("Fixed")));
+        assert_eq!(series.poster_url.as_deref(), Some("https://example.com/poster.jpg"));
+        assert_eq!(info.poster_url.unwrap().as_str(), "https://example.com/poster.jpg");
+        // A local poster path isn't a poster URL
+        series.apply_override(None, &Override { poster: Some("posters/show.jpg".to_string()), ..Default::default() });
+        assert_eq!(series.poster_url.as_deref(), Some("https://example.com/poster.jpg"));
+    }
+
+    #[test]
+    fn test_series_coverage_last_season() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Some(MediaCache::new(&dir.path().join("cache.sqlite")).unwrap());
+        let listing = SeasonMetadata {
+            total_seasons: Some(u8::MAX),
+            episodes: vec![EpisodeMetadata { episode: 1, released: Some("2020-01-01".to_string()), ..Default::default() }],
+        };
+        cache.as_ref().unwrap().store_tv_season("Show", u8::MAX, &listing).unwrap();
+        let episode = TvEpisodeMediaInfo {
+            series_name: "Show".to_string(),
+            season: u8::MAX,
+            episode: 2,
+            path: PathBuf::new(),
+            title: None,
+            plot: None,
+            imdb_rating: None,
+            air_date: None,
+            director: None,
+            still_url: None,
+            media_ref: None,
+            cast: None,
+            probe: None,
+            subtitles: Vec::new(),
+        };
+        // Season 255 listing the last season doesn't wrap around to season 0
+        let coverage = get_series_coverage(None, "Show", None, None, &[episode], &cache).unwrap();
+        assert_eq!(coverage.len(), 1);
+        assert_eq!((coverage[0].season_number, coverage[0].on_disk, coverage[0].aired), (u8::MAX, 0, 1));
+    }
+
+    /// Lists one episode titled after the IMDb id it's asked for.
+    struct ListingStub;
+
+    impl MetadataProvider for ListingStub {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        fn movie(&self, _: &TitleQuery) -> Result<crate::provider::MovieMetadata, Box<dyn std::error::Error>> {
+            Err("no movies".into())
+        }
+
+        fn series(&self, _: &TitleQuery) -> Result<SeriesMetadata, Box<dyn std::error::Error>> {
+            Err("no series".into())
+        }
+
+        fn season(&self, series: &TitleQuery, _: u8) -> Result<SeasonMetadata, Box<dyn std::error::Error>> {
+            Ok(SeasonMetadata {
+                total_seasons: Some(1),
+                episodes: vec![EpisodeMetadata { episode: 1, title: series.imdb_id.map(String::from), ..Default::default() }],
+            })
+        }
+
+        fn episode(&self, _: &TitleQuery, _: u8, _: u8, _: Option<&str>) -> Result<EpisodeMetadata, Box<dyn std::error::Error>> {
+            Err("no episodes".into())
+        }
+    }
+
+    #[test]
+    fn test_season_listing_by_imdb_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Some(MediaCache::new(&dir.path().join("cache.sqlite")).unwrap());
+        // Two series named "The Office" keep their own listings
+        for imdb_id in ["tt0290978", "tt0386676"] {
+            let listing = get_season_listing(&ListingStub, "The Office", Some(imdb_id), 1, Some(1), &cache).unwrap();
+            assert_eq!(listing.episodes[0].title.as_deref(), Some(imdb_id));
+        }
+        let cached = cache.as_ref().unwrap().get_tv_season("tt0290978", 1).unwrap().unwrap().0;
+        assert_eq!(cached.episodes[0].title.as_deref(), Some("tt0290978"));
+        assert!(cache.as_ref().unwrap().get_tv_season("The Office", 1).unwrap().is_none());
+    }
 
     fn assert_parsed_episode(
         path_str: &str,
@@ -643,6 +1016,9 @@ mod tests {
         assert_eq!(parse_series_folder_name(Path::new("Series Name")), (Some("Series Name".to_string()), None, None));
         assert_eq!(parse_series_folder_name(Path::new("Series Name (2020)")), (Some("Series Name".to_string()), None, Some(2020)));
          assert_eq!(parse_series_folder_name(Path::new("tales.from.the.loop.2020.season.01")), (Some("tales from the loop".to_string()), Some(1), Some(2020)));
+        // IMDb id hints aren't part of the name
+        assert_eq!(parse_series_folder_name(Path::new("Series Name (2020) [tt0412142]")), (Some("Series Name".to_string()), None, Some(2020)));
+        assert_eq!(parse_series_folder_name(Path::new("Series Name {imdb-tt0412142}")), (Some("Series Name".to_string()), None, None));
     }
 
     #[test]
@@ -704,7 +1080,11 @@ mod tests {
             imdb_rating: None,
             air_date: None,
             director: None,
+            still_url: None,
             media_ref: None,
+            cast: None,
+            probe: None,
+            subtitles: Vec::new(),
         };
 
         // Series 1: Standard structure
@@ -794,6 +1174,7 @@ mod tests {
             TvSeriesMediaInfo {
                 name: "Series Four".to_string(),
                 year: Some(2021),
+                imdb_id: None,
                 path: series4_path.clone(),
                 episodes: vec![s4e1],
                 released: None,
@@ -805,10 +1186,13 @@ mod tests {
                 poster_url: None,
                 imdb_rating: None,
                 total_seasons: None,
+                artwork: Artwork::default(),
+                coverage: Vec::new(),
             },
             TvSeriesMediaInfo {
                 name: "Series One".to_string(),
                 year: Some(2020),
+                imdb_id: None,
                 path: series1_path.clone(),
                 episodes: vec![s1e1, s1e2, s1s2e1],
                 released: None,
@@ -820,10 +1204,13 @@ mod tests {
                 poster_url: None,
                 imdb_rating: None,
                 total_seasons: None,
+                artwork: Artwork::default(),
+                coverage: Vec::new(),
             },
             TvSeriesMediaInfo {
                 name: "Series Three".to_string(),
                 year: None,
+                imdb_id: None,
                 path: series3_path.clone(),
                 episodes: vec![s3e2, s3e1],
                 released: None,
@@ -835,10 +1222,13 @@ mod tests {
                 poster_url: None,
                 imdb_rating: None,
                 total_seasons: None,
+                artwork: Artwork::default(),
+                coverage: Vec::new(),
             },
             TvSeriesMediaInfo {
                 name: "Series Two".to_string(),
                 year: None,
+                imdb_id: None,
                 path: series2_path.clone(),
                 episodes: vec![s2e1],
                 released: None,
@@ -850,10 +1240,12 @@ mod tests {
                 poster_url: None,
                 imdb_rating: None,
                 total_seasons: None,
+                artwork: Artwork::default(),
+                coverage: Vec::new(),
             },
         ];
 
-        let result = scan_tv_directory(base_path).unwrap();
+        let result = scan_tv_directory(base_path, &VideoScanner::default()).unwrap();
         
         let mut expected_sorted_series_info = expected_series_info;
         for series in expected_sorted_series_info.iter_mut() {
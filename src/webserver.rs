@@ -0,0 +1,980 @@
+use crate::auth::{Access, AuthConfig, DEFAULT_SIGNED_URL_TTL, TOKEN_COOKIE};
+use crate::hls::{self, SegmentCache, Variant, HLS_MOUNT};
+use crate::subtitles::SUBTITLES_DIR;
+use crate::tls::TlsIdentity;
+use crate::transcode::{Ffmpeg, StreamPlan, TRANSCODE_MOUNT};
+use rouille::{Request, Response, ResponseBody};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Address bound when no `--bind` option is given.
+pub const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1";
+
+/// Byte range selected by a request's `Range` header.
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    /// No (usable) range was requested; serve the whole file.
+    Full,
+    /// Inclusive start and end offsets of the requested range.
+    Partial(u64, u64),
+    /// The range cannot be satisfied for a file of this length (416).
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header against a file of `file_len` bytes.
+///
+/// Only a single `bytes=` range is supported. Malformed headers and multi-range
+/// requests are ignored, as RFC 9110 allows, and the whole file is served instead.
+pub fn parse_range_header(header: Option<&str>, file_len: u64) -> ByteRange {
+    let spec = match header.and_then(|h| h.trim().strip_prefix("bytes=")) {
+        Some(spec) if !spec.contains(',') => spec.trim(),
+        _ => return ByteRange::Full,
+    };
+    let (start, end) = match spec.split_once('-') {
+        Some(parts) => parts,
+        None => return ByteRange::Full,
+    };
+
+    if start.is_empty() {
+        // Suffix range: the last N bytes of the file
+        return match end.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if file_len == 0 => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Partial(file_len.saturating_sub(suffix), file_len - 1),
+            Err(_) => ByteRange::Full,
+        };
+    }
+
+    let start = match start.parse::<u64>() {
+        Ok(s) => s,
+        Err(_) => return ByteRange::Full,
+    };
+    let end = if end.is_empty() {
+        None
+    } else {
+        match end.parse::<u64>() {
+            Ok(e) if e >= start => Some(e),
+            _ => return ByteRange::Full,
+        }
+    };
+
+    if start >= file_len {
+        return ByteRange::Unsatisfiable;
+    }
+    let last = file_len - 1;
+    ByteRange::Partial(start, end.map_or(last, |e| e.min(last)))
+}
+
+/// Serves a file from disk, honouring `Range` requests.
+///
+/// `HEAD` gets the same status and headers as `GET`; tiny_http drops the body for us.
+pub fn serve_file(request: &Request, path: &Path, mime: &str) -> Response {
+    if request.method() != "GET" && request.method() != "HEAD" {
+        return Response::text("Method not allowed")
+            .with_status_code(405)
+            .with_unique_header("Allow", "GET, HEAD");
+    }
+
+    let mut file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            log::warn!(target: "cli", "Failed to open {:?}: {}", path, e);
+            return Response::html("Not found").with_status_code(404);
+        }
+    };
+    let file_len = match file.metadata() {
+        Ok(m) => m.len(),
+        Err(e) => {
+            log::warn!(target: "cli", "Failed to stat {:?}: {}", path, e);
+            return Response::text("Internal server error").with_status_code(500);
+        }
+    };
+
+    match parse_range_header(request.header("Range"), file_len) {
+        ByteRange::Full => Response {
+            status_code: 200,
+            headers: vec![("Content-Type".into(), mime.to_owned().into())],
+            data: ResponseBody::from_reader_and_size(file, file_len as usize),
+            upgrade: None,
+        }
+        .with_unique_header("Accept-Ranges", "bytes"),
+        ByteRange::Partial(start, end) => {
+            if let Err(e) = file.seek(SeekFrom::Start(start)) {
+                log::warn!(target: "cli", "Failed to seek {:?} to {}: {}", path, start, e);
+                return Response::text("Internal server error").with_status_code(500);
+            }
+            let length = end - start + 1;
+            Response {
+                status_code: 206,
+                headers: vec![("Content-Type".into(), mime.to_owned().into())],
+                data: ResponseBody::from_reader_and_size(file.take(length), length as usize),
+                upgrade: None,
+            }
+            .with_unique_header("Accept-Ranges", "bytes")
+            .with_unique_header("Content-Range", format!("bytes {}-{}/{}", start, end, file_len))
+        }
+        ByteRange::Unsatisfiable => Response::text("Requested range not satisfiable")
+            .with_status_code(416)
+            .with_unique_header("Accept-Ranges", "bytes")
+            .with_unique_header("Content-Range", format!("bytes */{}", file_len)),
+    }
+}
+
+/// Parses a `--bind` value into a socket address.
+///
+/// Accepts a bare IPv4/IPv6 address (`0.0.0.0`, `::`, `[::1]`), in which case `default_port`
+/// is used, or a full socket address (`192.168.1.10:8000`, `[::]:8000`).
+pub fn parse_bind_address(spec: &str, default_port: u16) -> Result<SocketAddr, String> {
+    let spec = spec.trim();
+    if let Ok(addr) = spec.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    let ip_str = spec.strip_prefix('[').and_then(|s| s.strip_suffix(']')).unwrap_or(spec);
+    ip_str.parse::<IpAddr>()
+        .map(|ip| SocketAddr::new(ip, default_port))
+        .map_err(|_| format!("Invalid bind address '{}': expected an IPv4/IPv6 address, optionally with a port", spec))
+}
+
+/// Best-effort detection of the addresses other devices on the LAN can reach us on.
+///
+/// Connecting a UDP socket does not send any packets; it only asks the OS which local
+/// address it would route through.
+fn detect_lan_ips() -> Vec<IpAddr> {
+    let probes: [(SocketAddr, &str); 2] = [
+        (SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0), "8.8.8.8:80"),
+        (SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0), "[2001:4860:4860::8888]:80"),
+    ];
+    probes.iter()
+        .filter_map(|(local, remote)| {
+            let socket = UdpSocket::bind(local).ok()?;
+            socket.connect(remote).ok()?;
+            socket.local_addr().ok().map(|a| a.ip())
+        })
+        .filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+        .collect()
+}
+
+/// URLs a listener bound to `addr` can be reached on, for the startup log.
+fn listen_urls(scheme: &str, addr: &SocketAddr, lan_ips: &[IpAddr], base_path: &str) -> Vec<String> {
+    let ips: Vec<IpAddr> = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => std::iter::once(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .chain(lan_ips.iter().copied().filter(|ip| ip.is_ipv4()))
+            .collect(),
+        // An IPv6 wildcard socket is dual-stack on most systems, so list both families
+        IpAddr::V6(ip) if ip.is_unspecified() => std::iter::once(IpAddr::V6(Ipv6Addr::LOCALHOST))
+            .chain(lan_ips.iter().copied())
+            .collect(),
+        ip => vec![ip],
+    };
+    ips.into_iter()
+        .map(|ip| format!("{}://{}{}/", scheme, SocketAddr::new(ip, addr.port()), base_path))
+        .collect()
+}
+
+/// Whether symlinks found under a served folder may be followed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymlinkPolicy {
+    /// Refuse any path that goes through a symlink below the folder root.
+    Never,
+    /// Follow symlinks as long as the target stays inside the folder root.
+    WithinRoot,
+    /// Follow symlinks anywhere, e.g. a library spread over several disks.
+    Follow,
+}
+
+impl std::str::FromStr for SymlinkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(SymlinkPolicy::Never),
+            "within-root" => Ok(SymlinkPolicy::WithinRoot),
+            "follow" => Ok(SymlinkPolicy::Follow),
+            _ => Err(format!("Unknown symlink policy '{}'", s)),
+        }
+    }
+}
+
+/// Why a request path could not be mapped to a file under a served folder.
+#[derive(Debug, PartialEq)]
+pub enum ResolveError {
+    /// Nothing servable at that path (404).
+    NotFound,
+    /// The path escapes the folder root or breaks the symlink policy (403).
+    Forbidden,
+}
+
+/// Maps a decoded request path onto a file inside `root`.
+///
+/// `..` segments are rejected outright rather than normalised, and the resolved file is
+/// checked against the canonical root according to `policy`.
+pub fn resolve_under_root(root: &Path, rel_path: &str, policy: SymlinkPolicy) -> Result<PathBuf, ResolveError> {
+    let mut relative = PathBuf::new();
+    for segment in rel_path.split(['/', '\\']) {
+        match segment {
+            "" | "." => continue,
+            ".." => return Err(ResolveError::Forbidden),
+            s => {
+                // Each segment must be a plain name; this rejects NUL bytes and Windows drive prefixes
+                let mut components = Path::new(s).components();
+                match (components.next(), components.next()) {
+                    (Some(Component::Normal(_)), None) if !s.contains('\0') => relative.push(s),
+                    _ => return Err(ResolveError::Forbidden),
+                }
+            }
+        }
+    }
+
+    let canonical_root = root.canonicalize().map_err(|_| ResolveError::NotFound)?;
+    let candidate = canonical_root.join(&relative);
+    if candidate.symlink_metadata().is_err() {
+        return Err(ResolveError::NotFound);
+    }
+
+    if policy == SymlinkPolicy::Never {
+        let mut prefix = canonical_root.clone();
+        for component in relative.components() {
+            prefix.push(component);
+            let is_symlink = prefix.symlink_metadata().map(|m| m.file_type().is_symlink()).unwrap_or(false);
+            if is_symlink {
+                return Err(ResolveError::Forbidden);
+            }
+        }
+    }
+
+    // Dangling symlinks fail to canonicalize; they can't be served either way
+    let resolved = candidate.canonicalize().map_err(|_| ResolveError::NotFound)?;
+    if policy != SymlinkPolicy::Follow && !resolved.starts_with(&canonical_root) {
+        return Err(ResolveError::Forbidden);
+    }
+    if !resolved.is_file() {
+        return Err(ResolveError::NotFound);
+    }
+    Ok(resolved)
+}
+
+/// Everything the request handler needs to know about what it serves.
+pub struct WebServerConfig {
+    /// Folder with the generated static site.
+    pub html_dir: PathBuf,
+    /// Movie folders as (path, mount) pairs.
+    pub movies_folders: Vec<(String, String)>,
+    /// TV folders as (path, mount) pairs.
+    pub tv_folders: Vec<(String, String)>,
+    /// URL path prefix everything is served under, taken from `--base-url`.
+    pub base_path: String,
+    pub symlink_policy: SymlinkPolicy,
+    pub auth: AuthConfig,
+    pub ffmpeg: Ffmpeg,
+    pub hls_cache: SegmentCache,
+    /// Whether the server runs with TLS, so the token cookie is only sent over HTTPS.
+    pub secure: bool,
+}
+
+impl WebServerConfig {
+    pub fn new(html_dir: String, tv_folders: Vec<(String, String)>, movies_folders: Vec<(String, String)>, base_url: Option<url::Url>, symlink_policy: SymlinkPolicy) -> Self {
+        // Determine the base path from base_url
+        let base_path = if let Some(ref url) = base_url {
+            url.path().trim_end_matches('/').to_string()
+        } else {
+            String::new()
+        };
+        WebServerConfig {
+            html_dir: PathBuf::from(html_dir),
+            movies_folders,
+            tv_folders,
+            base_path,
+            symlink_policy,
+            auth: AuthConfig::new(None, None, None, DEFAULT_SIGNED_URL_TTL),
+            ffmpeg: Ffmpeg::default(),
+            hls_cache: SegmentCache::new(std::env::temp_dir().join("nascast-hls"), hls::DEFAULT_CACHE_BYTES),
+            secure: false,
+        }
+    }
+
+    pub fn with_hls_cache(mut self, hls_cache: SegmentCache) -> Self {
+        self.hls_cache = hls_cache;
+        self
+    }
+
+    pub fn with_ffmpeg(mut self, ffmpeg: Ffmpeg) -> Self {
+        self.ffmpeg = ffmpeg;
+        self
+    }
+
+    pub fn with_auth(mut self, auth: AuthConfig) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    fn is_media_path(&self, request_path: &str) -> bool {
+        let request_path = strip_mount(request_path, TRANSCODE_MOUNT).unwrap_or(request_path);
+        self.movies_folders.iter().chain(self.tv_folders.iter())
+            .any(|(_, mount)| strip_mount(request_path, mount).is_some())
+    }
+
+    /// The part of a media request path a URL signature covers. For HLS that's the whole
+    /// `/hls/<id>` folder, so one signature also opens the variant playlists and segments.
+    fn signing_scope<'a>(&self, request_path: &'a str) -> Option<&'a str> {
+        if let Some(rest) = strip_mount(request_path, HLS_MOUNT) {
+            let id = rest.trim_start_matches('/').split('/').next().unwrap_or_default();
+            let media_path = hls::parse_media_id(id)?;
+            return self.is_media_path(&media_path).then(|| &request_path[..HLS_MOUNT.len() + 2 + id.len()]);
+        }
+        // Subtitle tracks are loaded by the Cast receiver as well
+        if strip_mount(request_path, SUBTITLES_DIR).is_some() {
+            return Some(request_path);
+        }
+        self.is_media_path(request_path).then_some(request_path)
+    }
+
+    /// Resolves a `/<mount>/<path>` request path to a file in one of the movie or TV folders.
+    fn resolve_media_path(&self, request_path: &str) -> Result<PathBuf, ResolveError> {
+        for (folder, mount) in self.movies_folders.iter().chain(self.tv_folders.iter()) {
+            if let Some(rel_path) = strip_mount(request_path, mount) {
+                match resolve_under_root(Path::new(folder), rel_path, self.symlink_policy) {
+                    Err(ResolveError::NotFound) => continue,
+                    result => return result,
+                }
+            }
+        }
+        Err(ResolveError::NotFound)
+    }
+}
+
+/// Strips `/{mount}` from the front of `request_path`, only matching whole path segments.
+fn strip_mount<'a>(request_path: &'a str, mount: &str) -> Option<&'a str> {
+    let rest = request_path.strip_prefix('/')?.strip_prefix(mount)?;
+    if rest.is_empty() || rest.starts_with('/') {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+fn forbidden(request_path: &str) -> Response {
+    log::warn!(target: "cli", "Refusing path outside of the served folders: {}", request_path);
+    Response::text("Forbidden").with_status_code(403)
+}
+
+fn serve_guessing_mime(request: &Request, file_path: &Path) -> Response {
+    // Use mime_guess to determine content type
+    let mime = mime_guess::from_path(file_path).first_or_octet_stream().to_string();
+    serve_file(request, file_path, &mime)
+}
+
+/// Serves a file under one of the configured roots, mapping resolve errors onto HTTP statuses.
+fn serve_from_root(request: &Request, root: &Path, rel_path: &str, policy: SymlinkPolicy) -> Option<Response> {
+    match resolve_under_root(root, rel_path, policy) {
+        Ok(file_path) => Some(serve_guessing_mime(request, &file_path)),
+        Err(ResolveError::Forbidden) => Some(forbidden(rel_path)),
+        Err(ResolveError::NotFound) => None,
+    }
+}
+
+/// Streams a media file through ffmpeg as fragmented MP4 so a Cast device can play it.
+///
+/// Files that are castable as they are get served directly. The output has no known length,
+/// so ranges aren't supported; `?start=<seconds>` seeks instead. Other starts than finite, non-negative
+/// numbers are rejected with a 400.
+fn serve_transcoded(request: &Request, file_path: &Path, config: &WebServerConfig) -> Response {
+    let (video, audio) = match config.ffmpeg.plan(file_path) {
+        StreamPlan::Direct => return serve_guessing_mime(request, file_path),
+        StreamPlan::Convert { video, audio } => (video, audio),
+    };
+    let response = |data| Response {
+        status_code: 200,
+        headers: vec![("Content-Type".into(), "video/mp4".into())],
+        data,
+        upgrade: None,
+    }.with_unique_header("Accept-Ranges", "none");
+
+    match request.method() {
+        "HEAD" => response(ResponseBody::empty()),
+        "GET" => {
+            let start_secs = match request.get_param("start").map(|s| s.parse::<f64>()) {
+                None => None,
+                Some(Ok(start)) if start.is_finite() && start >= 0.0 => Some(start),
+                Some(_) => return Response::text("Invalid start").with_status_code(400),
+            };
+            log::info!(target: "cli", "Streaming {:?} through ffmpeg (video: {:?}, audio: {:?})", file_path, video, audio);
+            match config.ffmpeg.stream(file_path, video, audio, start_secs) {
+                Ok(stream) => response(ResponseBody::from_reader(stream)),
+                Err(e) => {
+                    log::error!(target: "cli", "Failed to start ffmpeg for {:?}: {}", file_path, e);
+                    Response::text("Failed to start ffmpeg").with_status_code(500)
+                }
+            }
+        }
+        _ => Response::text("Method not allowed").with_status_code(405).with_unique_header("Allow", "GET, HEAD"),
+    }
+}
+
+/// Serves `/hls/<id>/...`: the master playlist, one playlist per variant and segments encoded
+/// on first request.
+///
+/// A signature on the master playlist request is carried over to every URI in the playlists.
+fn serve_hls(request: &Request, hls_path: &str, config: &WebServerConfig) -> Response {
+    let not_found = || Response::html("Not found").with_status_code(404);
+    let (id, file) = match hls_path.trim_start_matches('/').split_once('/') {
+        Some(parts) => parts,
+        None => return not_found(),
+    };
+    let media_path = match hls::parse_media_id(id) {
+        Some(media_path) => media_path,
+        None => return not_found(),
+    };
+    let source_path = match config.resolve_media_path(&media_path) {
+        Ok(path) => path,
+        Err(ResolveError::Forbidden) => return forbidden(&media_path),
+        Err(ResolveError::NotFound) => return not_found(),
+    };
+    let query = match (request.get_param("expires"), request.get_param("sig")) {
+        (Some(expires), Some(sig)) => format!("?expires={}&sig={}", urlencoding::encode(&expires), urlencoding::encode(&sig)),
+        _ => String::new(),
+    };
+    let source = || config.ffmpeg.probe(&source_path)
+        .and_then(|probe| hls::SourceInfo::from_probe(&probe)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "no duration")))
+        .map_err(|e| {
+            log::error!(target: "cli", "Failed to probe {:?} for HLS: {}", source_path, e);
+            Response::text("Failed to probe media").with_status_code(500)
+        });
+    let playlist = |body: String| Response::from_data("application/vnd.apple.mpegurl", body);
+
+    let response = if file == "master.m3u8" {
+        source().map(|source| playlist(hls::master_playlist(&source, &query)))
+    } else if let Some(variant) = file.strip_suffix(".m3u8").and_then(Variant::by_name) {
+        source().map(|source| playlist(hls::media_playlist(&source, variant, &query)))
+    } else if let Some((variant, index)) = file.strip_suffix(".ts").and_then(|f| f.split_once('/'))
+        .and_then(|(variant, index)| Some((Variant::by_name(variant)?, index.parse::<u32>().ok()?))) {
+        source().and_then(|source| {
+            // Segments past the end would only encode nothing
+            if index >= source.segment_count() {
+                return Err(not_found());
+            }
+            let segment = config.hls_cache.get_or_create(&source_path, variant, index, |output| {
+                log::info!(target: "cli", "Encoding HLS segment {} ({}) of {:?}", index, variant.name, source_path);
+                config.ffmpeg.encode_segment(&source_path, variant, index, output)
+            });
+            match segment {
+                Ok(segment_path) => Ok(serve_file(request, &segment_path, "video/mp2t")),
+                Err(e) => {
+                    log::error!(target: "cli", "Failed to produce HLS segment {} of {:?}: {}", index, source_path, e);
+                    Err(Response::text("Failed to encode segment").with_status_code(500))
+                }
+            }
+        })
+    } else {
+        Err(not_found())
+    };
+    // The Cast receiver fetches playlists and segments with XHR
+    response.unwrap_or_else(|e| e).with_unique_header("Access-Control-Allow-Origin", "*")
+}
+
+fn unauthorized(config: &WebServerConfig) -> Response {
+    let response = Response::text("Unauthorized").with_status_code(401);
+    if config.auth.basic.is_some() {
+        response.with_unique_header("WWW-Authenticate", "Basic realm=\"NASCast\"")
+    } else {
+        response
+    }
+}
+
+/// Answers `/sign?path=<media path>` with a signed URL a Cast device can fetch without
+/// credentials. The path is sent decoded, as `Request::url` has it, so a `%xx` in a file name stays literal.
+fn sign_media_path(request: &Request, config: &WebServerConfig) -> Response {
+    let decoded_path = match request.get_param("path") {
+        Some(p) => p,
+        None => return Response::text("Missing path").with_status_code(400),
+    };
+    let media_path = decoded_path.strip_prefix(config.base_path.as_str()).unwrap_or("");
+    let scope = match config.signing_scope(media_path) {
+        Some(scope) => format!("{}{}", config.base_path, scope),
+        None => return Response::text("Not a media path").with_status_code(400),
+    };
+    let encoded_path = decoded_path.split('/').map(|segment| urlencoding::encode(segment)).collect::<Vec<_>>().join("/");
+    let url = if config.auth.is_enabled() {
+        format!("{}?{}", encoded_path, config.auth.sign_path(&scope, SystemTime::now()))
+    } else {
+        encoded_path
+    };
+    Response::json(&serde_json::json!({ "url": url }))
+}
+
+pub fn handle_request(request: &Request, config: &WebServerConfig) -> Response {
+    log::info!(target: "cli", "{} {}", request.method(), request.url());
+
+    let base_path = &config.base_path;
+
+    // Strip base path from request URL if it exists
+    let request_url = request.url();
+    let request_path = if !base_path.is_empty() && request_url.starts_with(base_path.as_str()) {
+        &request_url[base_path.len()..]
+    } else if base_path.is_empty() {
+        &request_url
+    } else {
+        // Request doesn't match base path, return 404
+        return Response::html("Not found").with_status_code(404);
+    };
+
+    // Media URLs signed by /sign are good without credentials; that's how the Cast device gets in
+    let signed = config.auth.is_enabled()
+        && config.signing_scope(request_path).is_some_and(|scope| config.auth.verify_signed(
+            &format!("{}{}", base_path, scope), request.get_param("expires").as_deref(), request.get_param("sig").as_deref(), SystemTime::now()));
+    let access = if signed { Access::Authorized } else { config.auth.check(request) };
+
+    match access {
+        Access::Denied => unauthorized(config),
+        Access::Authorized => route_request(request, request_path, config),
+        Access::TokenLogin => {
+            let cookie_path = if base_path.is_empty() { "/" } else { base_path.as_str() };
+            route_request(request, request_path, config).with_additional_header(
+                "Set-Cookie",
+                format!("{}={}; Path={}; HttpOnly; SameSite=Lax{}", TOKEN_COOKIE, config.auth.token.as_deref().unwrap_or_default(), cookie_path,
+                    if config.secure { "; Secure" } else { "" }),
+            )
+        }
+    }
+}
+
+fn route_request(request: &Request, request_path: &str, config: &WebServerConfig) -> Response {
+    let html_dir_path = &config.html_dir;
+
+    if request_path == "/sign" {
+        return sign_media_path(request, config);
+    }
+
+    if let Some(hls_path) = strip_mount(request_path, HLS_MOUNT) {
+        return serve_hls(request, hls_path, config);
+    }
+
+    // Media files streamed through ffmpeg: /transcode/<mount>/<path>
+    if let Some(media_path) = strip_mount(request_path, TRANSCODE_MOUNT) {
+        return match config.resolve_media_path(media_path) {
+            Ok(file_path) => serve_transcoded(request, &file_path, config),
+            Err(ResolveError::Forbidden) => forbidden(request_path),
+            Err(ResolveError::NotFound) => Response::html("Not found").with_status_code(404),
+        };
+    }
+
+    // First try to handle media folders, then TV folders
+    match config.resolve_media_path(request_path) {
+        Ok(file_path) => return serve_guessing_mime(request, &file_path),
+        Err(ResolveError::Forbidden) => return forbidden(request_path),
+        Err(ResolveError::NotFound) => {}
+    }
+
+    // Finally serve the static HTML files using the stripped request path
+    match request_path {
+        "/" => {
+            let index_path = html_dir_path.join("index.html");
+            if index_path.exists() {
+                serve_file(request, &index_path, "text/html")
+            } else {
+                Response::text("Index file not found").with_status_code(404)
+            }
+        },
+        "/media.css" => {
+            serve_file(request, &html_dir_path.join("media.css"), "text/css")
+        },
+        "/media.js" => {
+            serve_file(request, &html_dir_path.join("media.js"), "application/javascript")
+        },
+        "/search.js" => {
+            serve_file(request, &html_dir_path.join("search.js"), "application/javascript")
+        },
+        "/search-index.json" => {
+            serve_file(request, &html_dir_path.join("search-index.json"), "application/json")
+        },
+        "/movies.html" => {
+            serve_file(request, &html_dir_path.join("movies.html"), "text/html")
+        },
+        "/tv.html" => {
+            serve_file(request, &html_dir_path.join("tv.html"), "text/html")
+        },
+        // Fallback for other HTML files (media pages) and subtitles
+        _ => {
+            let response = serve_from_root(request, html_dir_path, request_path, config.symlink_policy).unwrap_or_else(|| {
+                log::info!(target: "cli", "404 for path: {}", request_path);
+                Response::html("Not found").with_status_code(404)
+            });
+            if strip_mount(request_path, SUBTITLES_DIR).is_some() {
+                // The Cast receiver fetches text tracks with XHR
+                response.with_unique_header("Access-Control-Allow-Origin", "*")
+            } else {
+                response
+            }
+        }
+    }
+}
+
+/// Names a generated self-signed certificate should cover for these listeners.
+pub fn certificate_names(bind_addresses: &[SocketAddr]) -> Vec<String> {
+    let mut names = vec!["localhost".to_string(), Ipv4Addr::LOCALHOST.to_string(), Ipv6Addr::LOCALHOST.to_string()];
+    let ips = bind_addresses.iter().map(|a| a.ip()).filter(|ip| !ip.is_unspecified()).chain(detect_lan_ips());
+    for ip in ips {
+        if !names.contains(&ip.to_string()) {
+            names.push(ip.to_string());
+        }
+    }
+    names
+}
+
+// Function to start the web server
+pub fn start_webserver(config: WebServerConfig, bind_addresses: Vec<SocketAddr>, tls: Option<TlsIdentity>) -> std::io::Result<()> {
+    log::info!(target: "cli", "Starting web server on {:?} serving files from {:?}", bind_addresses, config.html_dir);
+
+    let log_base_path = config.base_path.clone();
+    let config = Arc::new(config.with_secure(tls.is_some()));
+
+    // Bind every address up front so a bad address fails before anything is served
+    let mut servers = Vec::new();
+    for address in bind_addresses {
+        let config = config.clone();
+        let handler = move |request: &Request| handle_request(request, &config);
+        let server = match &tls {
+            Some(identity) => rouille::Server::new_ssl(address, handler, identity.certificate_chain.clone(), identity.private_key.clone()),
+            None => rouille::Server::new(address, handler),
+        }.map_err(|e| std::io::Error::other(format!("Failed to bind {}: {}", address, e)))?;
+        servers.push(server);
+    }
+
+    let lan_ips = detect_lan_ips();
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    for server in &servers {
+        for url in listen_urls(scheme, &server.server_addr(), &lan_ips, &log_base_path) {
+            log::info!(target: "cli", "Serving at {}", url);
+        }
+    }
+
+    // Each listener runs forever on its own thread
+    let threads: Vec<_> = servers.into_iter()
+        .map(|server| std::thread::spawn(move || server.run()))
+        .collect();
+    for thread in threads {
+        thread.join().map_err(|_| std::io::Error::other("Web server thread panicked"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn response_header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+        response.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_ref())
+    }
+
+    fn response_body(response: Response) -> Vec<u8> {
+        let (mut reader, _) = response.data.into_reader_and_size();
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).unwrap();
+        body
+    }
+
+    #[test]
+    fn test_parse_range_header() {
+        assert_eq!(parse_range_header(None, 100), ByteRange::Full);
+        assert_eq!(parse_range_header(Some("bytes=0-9"), 100), ByteRange::Partial(0, 9));
+        assert_eq!(parse_range_header(Some("bytes=90-"), 100), ByteRange::Partial(90, 99));
+        assert_eq!(parse_range_header(Some("bytes=90-500"), 100), ByteRange::Partial(90, 99));
+        assert_eq!(parse_range_header(Some("bytes=-10"), 100), ByteRange::Partial(90, 99));
+        assert_eq!(parse_range_header(Some("bytes=-500"), 100), ByteRange::Partial(0, 99));
+        assert_eq!(parse_range_header(Some("bytes=100-"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range_header(Some("bytes=-0"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range_header(Some("bytes=0-"), 0), ByteRange::Unsatisfiable);
+        // Malformed or unsupported ranges are ignored
+        assert_eq!(parse_range_header(Some("bytes=9-0"), 100), ByteRange::Full);
+        assert_eq!(parse_range_header(Some("bytes=a-b"), 100), ByteRange::Full);
+        assert_eq!(parse_range_header(Some("items=0-9"), 100), ByteRange::Full);
+        assert_eq!(parse_range_header(Some("bytes=0-9,20-29"), 100), ByteRange::Full);
+    }
+
+    #[test]
+    fn test_parse_bind_address() {
+        assert_eq!(parse_bind_address("0.0.0.0", 8000), Ok("0.0.0.0:8000".parse().unwrap()));
+        assert_eq!(parse_bind_address("192.168.1.10:9000", 8000), Ok("192.168.1.10:9000".parse().unwrap()));
+        assert_eq!(parse_bind_address("::", 8000), Ok("[::]:8000".parse().unwrap()));
+        assert_eq!(parse_bind_address("[::1]", 8000), Ok("[::1]:8000".parse().unwrap()));
+        assert_eq!(parse_bind_address("[fe80::1]:9000", 8000), Ok("[fe80::1]:9000".parse().unwrap()));
+        assert!(parse_bind_address("nas.local", 8000).is_err());
+    }
+
+    #[test]
+    fn test_listen_urls() {
+        let lan_ips: Vec<IpAddr> = vec!["192.168.1.10".parse().unwrap(), "fd00::10".parse().unwrap()];
+        assert_eq!(listen_urls("http", &"0.0.0.0:8000".parse().unwrap(), &lan_ips, ""),
+            vec!["http://127.0.0.1:8000/", "http://192.168.1.10:8000/"]);
+        assert_eq!(listen_urls("http", &"[::]:8000".parse().unwrap(), &lan_ips, "/media"),
+            vec!["http://[::1]:8000/media/", "http://192.168.1.10:8000/media/", "http://[fd00::10]:8000/media/"]);
+        assert_eq!(listen_urls("https", &"10.0.0.2:443".parse().unwrap(), &lan_ips, ""), vec!["https://10.0.0.2:443/"]);
+    }
+
+    struct Library {
+        _dir: tempfile::TempDir,
+        config: WebServerConfig,
+    }
+
+    /// Builds a library with a movie and a TV mount and a secret file next to them.
+    fn hostile_library(symlink_policy: SymlinkPolicy) -> Library {
+        let dir = tempfile::Builder::new().prefix("test_webserver").tempdir().unwrap();
+        let base = dir.path();
+        for folder in ["Movies/Some Movie (2001)", "TV/Show/Season 1", "pub", "Elsewhere"] {
+            std::fs::create_dir_all(base.join(folder)).unwrap();
+        }
+        std::fs::write(base.join("Movies/Some Movie (2001)/movie.mp4"), b"movie").unwrap();
+        std::fs::write(base.join("TV/Show/Season 1/S01E01.mkv"), b"episode").unwrap();
+        std::fs::write(base.join("pub/index.html"), b"index").unwrap();
+        std::fs::create_dir_all(base.join("pub/subtitles")).unwrap();
+        std::fs::write(base.join("pub/subtitles/1.vtt"), b"WEBVTT\n").unwrap();
+        std::fs::write(base.join("secret.txt"), b"secret").unwrap();
+        std::fs::write(base.join("Elsewhere/other.mp4"), b"other").unwrap();
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(base.join("secret.txt"), base.join("Movies/leak.mp4")).unwrap();
+            std::os::unix::fs::symlink(base.join("Elsewhere"), base.join("TV/Elsewhere")).unwrap();
+            std::os::unix::fs::symlink(base.join("TV/Show/Season 1/S01E01.mkv"), base.join("TV/Show/latest.mkv")).unwrap();
+        }
+
+        let folder = |name: &str| base.join(name).to_str().unwrap().to_string();
+        let config = WebServerConfig::new(
+            folder("pub"),
+            vec![(folder("TV"), "tv".to_string())],
+            vec![(folder("Movies"), "movies".to_string())],
+            None,
+            symlink_policy,
+        );
+        Library { _dir: dir, config }
+    }
+
+    fn get_status(config: &WebServerConfig, url: &str) -> u16 {
+        handle_request(&Request::fake_http("GET", url, vec![], vec![]), config).status_code
+    }
+
+    #[test]
+    fn test_hostile_urls() {
+        let library = hostile_library(SymlinkPolicy::WithinRoot);
+        let config = &library.config;
+
+        assert_eq!(get_status(config, "/movies/Some%20Movie%20(2001)/movie.mp4"), 200);
+        assert_eq!(get_status(config, "/tv/Show/Season%201/S01E01.mkv"), 200);
+        assert_eq!(get_status(config, "/"), 200);
+
+        for url in [
+            "/movies/../secret.txt",
+            "/movies/%2e%2e/secret.txt",
+            "/movies/%2E%2E%2Fsecret.txt",
+            "/movies/Some%20Movie%20(2001)/../../secret.txt",
+            "/movies/..%5csecret.txt",
+            "/tv/../secret.txt",
+            "/tv/%2e%2e/%2e%2e/etc/passwd",
+            "/tv/Show/..%2f..%2fsecret.txt",
+            "/../secret.txt",
+            "/%2e%2e/secret.txt",
+        ] {
+            assert_eq!(get_status(config, url), 403, "{}", url);
+        }
+
+        // Double-encoding only yields a literal "%2e%2e" name, which doesn't exist
+        assert_eq!(get_status(config, "/movies/%252e%252e/secret.txt"), 404);
+        assert_eq!(get_status(config, "/movies/missing.mp4"), 404);
+        // A mount only matches whole path segments
+        assert_eq!(get_status(config, "/moviesSome%20Movie%20(2001)/movie.mp4"), 404);
+    }
+
+    #[test]
+    fn test_auth_and_signed_urls() {
+        let library = hostile_library(SymlinkPolicy::WithinRoot);
+        let config = library.config.with_auth(AuthConfig::new(
+            Some(("admin".into(), "hunter2".into())), None, Some("key".into()), DEFAULT_SIGNED_URL_TTL));
+        let basic = ("Authorization".to_string(), "Basic YWRtaW46aHVudGVyMg==".to_string());
+        let media_url = "/movies/Some%20Movie%20%282001%29/movie.mp4";
+        let sign_url = |path: &str| format!("/sign?path={}", urlencoding::encode(path));
+
+        let response = handle_request(&Request::fake_http("GET", "/", vec![], vec![]), &config);
+        assert_eq!(response.status_code, 401);
+        assert_eq!(response_header(&response, "WWW-Authenticate"), Some("Basic realm=\"NASCast\""));
+        assert_eq!(get_status(&config, "/search-index.json"), 401);
+        assert_eq!(get_status(&config, media_url), 401);
+        assert_eq!(handle_request(&Request::fake_http("GET", "/", vec![basic.clone()], vec![]), &config).status_code, 200);
+
+        // Signing requires credentials; the signed URL then works without them
+        assert_eq!(get_status(&config, &sign_url("/movies/Some Movie (2001)/movie.mp4")), 401);
+        let response = handle_request(&Request::fake_http("GET", sign_url("/movies/Some Movie (2001)/movie.mp4"), vec![basic.clone()], vec![]), &config);
+        assert_eq!(response.status_code, 200);
+        let body: serde_json::Value = serde_json::from_slice(&response_body(response)).unwrap();
+        let signed_url = body["url"].as_str().unwrap().to_string();
+        assert!(signed_url.starts_with(&format!("{}?expires=", media_url)));
+        assert_eq!(get_status(&config, &signed_url), 200);
+
+        // The signature only covers the signed media file, and never the pages
+        let query = signed_url.split_once('?').unwrap().1;
+        assert_eq!(get_status(&config, &format!("/tv/Show/Season%201/S01E01.mkv?{}", query)), 401);
+        assert_eq!(get_status(&config, &format!("/?{}", query)), 401);
+        let bad_sig = signed_url.replace("sig=", "sig=00");
+        assert_eq!(get_status(&config, &bad_sig), 401);
+
+        // A `%xx` in a file name is signed as it is, not decoded once more
+        std::fs::write(Path::new(&config.movies_folders[0].0).join("Some Movie (2001)/100%41.mp4"), b"movie").unwrap();
+        let response = handle_request(&Request::fake_http("GET", sign_url("/movies/Some Movie (2001)/100%41.mp4"), vec![basic.clone()], vec![]), &config);
+        let body: serde_json::Value = serde_json::from_slice(&response_body(response)).unwrap();
+        let signed_url = body["url"].as_str().unwrap();
+        assert!(signed_url.starts_with("/movies/Some%20Movie%20%282001%29/100%2541.mp4?expires="));
+        assert_eq!(get_status(&config, signed_url), 200);
+
+        // Subtitle tracks can be signed for the Cast receiver too, and allow cross-origin reads
+        let response = handle_request(&Request::fake_http("GET", "/sign?path=%2Fsubtitles%2F1.vtt", vec![basic.clone()], vec![]), &config);
+        let body: serde_json::Value = serde_json::from_slice(&response_body(response)).unwrap();
+        let response = handle_request(&Request::fake_http("GET", body["url"].as_str().unwrap(), vec![], vec![]), &config);
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response_header(&response, "Content-Type"), Some("text/vtt"));
+        assert_eq!(response_header(&response, "Access-Control-Allow-Origin"), Some("*"));
+    }
+
+    #[test]
+    fn test_token_login_sets_cookie() {
+        let library = hostile_library(SymlinkPolicy::WithinRoot);
+        let config = library.config.with_auth(AuthConfig::new(None, Some("s3cret".into()), None, DEFAULT_SIGNED_URL_TTL));
+
+        let response = handle_request(&Request::fake_http("GET", "/?token=s3cret", vec![], vec![]), &config);
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response_header(&response, "Set-Cookie"), Some("nascast_token=s3cret; Path=/; HttpOnly; SameSite=Lax"));
+        let cookie = ("Cookie".to_string(), "nascast_token=s3cret".to_string());
+        assert_eq!(handle_request(&Request::fake_http("GET", "/tv/Show/Season%201/S01E01.mkv", vec![cookie], vec![]), &config).status_code, 200);
+        assert_eq!(get_status(&config, "/?token=wrong"), 401);
+
+        // Behind TLS the cookie is kept off plain HTTP
+        let config = config.with_secure(true);
+        let response = handle_request(&Request::fake_http("GET", "/?token=s3cret", vec![], vec![]), &config);
+        assert_eq!(response_header(&response, "Set-Cookie"), Some("nascast_token=s3cret; Path=/; HttpOnly; SameSite=Lax; Secure"));
+    }
+
+    #[test]
+    fn test_transcode_endpoint() {
+        let library = hostile_library(SymlinkPolicy::WithinRoot);
+        // Point at a missing ffprobe so the plan is made from the extension alone
+        let config = library.config.with_ffmpeg(Ffmpeg { ffmpeg: "/nonexistent/ffmpeg".into(), ffprobe: "/nonexistent/ffprobe".into() });
+
+        // Castable files are served as they are
+        let response = handle_request(&Request::fake_http("GET", "/transcode/movies/Some%20Movie%20(2001)/movie.mp4", vec![], vec![]), &config);
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response_body(response), b"movie");
+
+        let response = handle_request(&Request::fake_http("HEAD", "/transcode/tv/Show/Season%201/S01E01.mkv", vec![], vec![]), &config);
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response_header(&response, "Content-Type"), Some("video/mp4"));
+        assert_eq!(get_status(&config, "/transcode/tv/Show/Season%201/S01E01.mkv"), 500);
+        assert_eq!(get_status(&config, "/transcode/tv/Show/Season%201/S01E01.mkv?start=90.5"), 500);
+        for start in ["inf", "NaN", "-5", "soon"] {
+            assert_eq!(get_status(&config, &format!("/transcode/tv/Show/Season%201/S01E01.mkv?start={}", start)), 400);
+        }
+
+        assert_eq!(get_status(&config, "/transcode/tv/../secret.txt"), 403);
+        assert_eq!(get_status(&config, "/transcode/movies/%2e%2e/secret.txt"), 403);
+        assert_eq!(get_status(&config, "/transcode/tv/missing.mkv"), 404);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hls_endpoint() {
+        use std::os::unix::fs::PermissionsExt;
+        let library = hostile_library(SymlinkPolicy::WithinRoot);
+        let cache_dir = library._dir.path().join("hls");
+        // Probes every file as 12 seconds long, i.e. two segments
+        let ffprobe = library._dir.path().join("ffprobe");
+        std::fs::write(&ffprobe, "#!/bin/sh\necho '{\"format\":{\"duration\":\"12.0\"},\"streams\":[]}'\n").unwrap();
+        std::fs::set_permissions(&ffprobe, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let config = library.config
+            .with_ffmpeg(Ffmpeg { ffmpeg: "/nonexistent/ffmpeg".into(), ffprobe })
+            .with_hls_cache(SegmentCache::new(cache_dir, hls::DEFAULT_CACHE_BYTES))
+            .with_auth(AuthConfig::new(None, Some("s3cret".into()), Some("key".into()), DEFAULT_SIGNED_URL_TTL));
+        let id = hls::media_id("/tv/Show/Season 1/S01E01.mkv");
+        let source = config.resolve_media_path("/tv/Show/Season 1/S01E01.mkv").unwrap();
+        config.hls_cache.get_or_create(&source, &hls::VARIANTS[1], 0, |path| std::fs::write(path, b"segment")).unwrap();
+
+        // One signature for the master playlist opens the whole folder
+        let master = format!("/hls/{}/master.m3u8", id);
+        let signed = handle_request(&Request::fake_http("GET", format!("/sign?path={}", master), vec![("Authorization".into(), "Bearer s3cret".into())], vec![]), &config);
+        let signed: serde_json::Value = serde_json::from_slice(&response_body(signed)).unwrap();
+        let query = signed["url"].as_str().unwrap().split_once('?').unwrap().1.to_string();
+        let response = handle_request(&Request::fake_http("GET", format!("/hls/{}/720p/0.ts?{}", id, query), vec![], vec![]), &config);
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response_header(&response, "Content-Type"), Some("video/mp2t"));
+        assert_eq!(response_header(&response, "Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(response_body(response), b"segment");
+        assert_eq!(get_status(&config, &format!("/hls/{}/720p/0.ts", id)), 401);
+        let other = hls::media_id("/movies/Some Movie (2001)/movie.mp4");
+        assert_eq!(get_status(&config, &format!("/hls/{}/720p/0.ts?{}", other, query)), 401);
+
+        let authorized = |url: String| handle_request(&Request::fake_http("GET", url, vec![("Authorization".into(), "Bearer s3cret".into())], vec![]), &config).status_code;
+        assert_eq!(authorized(master), 200);
+        // Uncached segments need ffmpeg, and ones past the end don't exist
+        assert_eq!(authorized(format!("/hls/{}/720p/1.ts", id)), 500);
+        assert_eq!(authorized(format!("/hls/{}/720p/2.ts", id)), 404);
+        assert_eq!(authorized(format!("/hls/{}/1080p/999999.ts", id)), 404);
+        assert_eq!(authorized(format!("/hls/{}/4k.m3u8", id)), 404);
+        assert_eq!(authorized(format!("/hls/{}/master.m3u8", hls::media_id("/tv/missing.mkv"))), 404);
+        assert_eq!(authorized("/hls/not-hex/master.m3u8".into()), 404);
+        assert_eq!(authorized(format!("/hls/{}/master.m3u8", hls::media_id("/tv/../secret.txt"))), 403);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policies() {
+        let within = hostile_library(SymlinkPolicy::WithinRoot);
+        assert_eq!(get_status(&within.config, "/movies/leak.mp4"), 403);
+        assert_eq!(get_status(&within.config, "/tv/Elsewhere/other.mp4"), 403);
+        assert_eq!(get_status(&within.config, "/tv/Show/latest.mkv"), 200);
+
+        let never = hostile_library(SymlinkPolicy::Never);
+        assert_eq!(get_status(&never.config, "/movies/leak.mp4"), 403);
+        assert_eq!(get_status(&never.config, "/tv/Show/latest.mkv"), 403);
+        assert_eq!(get_status(&never.config, "/tv/Show/Season%201/S01E01.mkv"), 200);
+
+        let follow = hostile_library(SymlinkPolicy::Follow);
+        assert_eq!(get_status(&follow.config, "/tv/Elsewhere/other.mp4"), 200);
+        assert_eq!(get_status(&follow.config, "/movies/../secret.txt"), 403);
+    }
+
+    #[test]
+    fn test_serve_file_ranges() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"0123456789").unwrap();
+
+        let request = Request::fake_http("GET", "/movies/a.mp4", vec![], vec![]);
+        let response = serve_file(&request, file.path(), "video/mp4");
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response_header(&response, "Accept-Ranges"), Some("bytes"));
+        assert_eq!(response_body(response), b"0123456789");
+
+        let request = Request::fake_http("GET", "/movies/a.mp4", vec![("Range".into(), "bytes=2-5".into())], vec![]);
+        let response = serve_file(&request, file.path(), "video/mp4");
+        assert_eq!(response.status_code, 206);
+        assert_eq!(response_header(&response, "Content-Range"), Some("bytes 2-5/10"));
+        assert_eq!(response_body(response), b"2345");
+
+        let request = Request::fake_http("HEAD", "/movies/a.mp4", vec![("Range".into(), "bytes=-3".into())], vec![]);
+        let response = serve_file(&request, file.path(), "video/mp4");
+        assert_eq!(response.status_code, 206);
+        assert_eq!(response_header(&response, "Content-Range"), Some("bytes 7-9/10"));
+
+        let request = Request::fake_http("GET", "/movies/a.mp4", vec![("Range".into(), "bytes=10-".into())], vec![]);
+        let response = serve_file(&request, file.path(), "video/mp4");
+        assert_eq!(response.status_code, 416);
+        assert_eq!(response_header(&response, "Content-Range"), Some("bytes */10"));
+
+        let request = Request::fake_http("POST", "/movies/a.mp4", vec![], vec![]);
+        assert_eq!(serve_file(&request, file.path(), "video/mp4").status_code, 405);
+    }
+}
@@ -1,10 +1,43 @@
-const contentType = 'video/mp4';
+// Cast devices can't send our credentials, so ask the nascast webserver for a signed URL.
+// Other webservers have no sign endpoint; the plain URL is used then.
+async function castableURL(mediaURL) {
+    const absoluteURL = new URL(mediaURL, window.location.href);
+    try {
+        const response = await fetch('sign?path=' + encodeURIComponent(decodeURIComponent(absoluteURL.pathname)));
+        if (response.ok) {
+            const signed = await response.json();
+            return new URL(signed.url, absoluteURL).href;
+        }
+    } catch(error) {
+        console.log('Could not sign media URL: ' + error);
+    }
+    return absoluteURL.href;
+}
 
-async function playMedia(mediaURL) {
+// WebVTT subtitles from the page, as Cast text tracks
+async function castTextTracks(textTracks) {
+    return Promise.all(textTracks.map(async function(textTrack, i) {
+        const track = new chrome.cast.media.Track(i + 1, chrome.cast.media.TrackType.TEXT);
+        track.trackContentId = await castableURL(textTrack.url);
+        track.trackContentType = 'text/vtt';
+        track.subtype = chrome.cast.media.TextTrackType.SUBTITLES;
+        track.name = textTrack.label;
+        track.language = textTrack.language;
+        return track;
+    }));
+}
+
+// contentType comes from the page generator, which knows each file's type
+async function playMedia(mediaURL, contentType, textTracks) {
     const castSession = cast.framework.CastContext.getInstance().getCurrentSession();
-    const mediaInfo = new chrome.cast.media.MediaInfo(mediaURL, contentType);
+    const mediaInfo = new chrome.cast.media.MediaInfo(await castableURL(mediaURL), contentType || 'video/mp4');
+    textTracks = textTracks || [];
+    mediaInfo.tracks = await castTextTracks(textTracks);
+    mediaInfo.textTrackStyle = new chrome.cast.media.TextTrackStyle();
     const request = new chrome.cast.media.LoadRequest(mediaInfo);
     request.autoplay = true;
+    // Forced subtitles are on from the start
+    request.activeTrackIds = mediaInfo.tracks.filter((track, i) => textTracks[i].forced).map(track => track.trackId);
 
     console.log('Playing media: ' + mediaURL);
     try {
@@ -20,13 +53,43 @@ async function playMedia(mediaURL) {
     
 const cjs = new Castjs();
 
-function init(mediaURL) {    
+function init(mediaURL, contentType, textTracks) {    
     cjs.on('statechange', function(event) {
         console.log('State change')
         switch(event.sessionState) {
             case cast.framework.sessionState.SESSION_STARTED:
-                playMedia(mediaURL);
+                playMedia(mediaURL, contentType, textTracks);
                 break;
         }
     });
-}
\ No newline at end of file
+}
+
+// Starts a Cast session if needed, then loads the media
+function castMedia(mediaURL, contentType, textTracks) {
+    const castContext = cast.framework.CastContext.getInstance();
+    if (castContext.getCurrentSession()) {
+        playMedia(mediaURL, contentType, textTracks);
+    } else {
+        castContext.requestSession().then(
+            () => playMedia(mediaURL, contentType, textTracks),
+            (errorCode) => console.log('Cast session failed: ' + errorCode));
+    }
+}
+
+function textTracksOf(element) {
+    return element.dataset.textTracks ? JSON.parse(element.dataset.textTracks) : [];
+}
+
+// Pages mark what to cast with data-cast-url: the movie page on its cast container,
+// the series page on each episode's cast button
+document.addEventListener('DOMContentLoaded', function() {
+    const castContainer = document.querySelector('.cast-container[data-cast-url]');
+    if (castContainer) {
+        init(castContainer.dataset.castUrl, castContainer.dataset.contentType, textTracksOf(castContainer));
+    }
+    document.querySelectorAll('button[data-cast-url]').forEach(function(button) {
+        button.addEventListener('click', function() {
+            castMedia(button.dataset.castUrl, button.dataset.contentType, textTracksOf(button));
+        });
+    });
+});
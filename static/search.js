@@ -107,7 +107,7 @@ function createSearchResultItem(item) {
     return `
         <a href="${escapeHtml(item.url)}" class="search-result-item">
             <img src="${escapeHtml(item.poster_url)}" alt="${escapeHtml(item.title)}" class="search-result-poster" 
-                 onerror="this.src='https://via.placeholder.com/40x60.png?text=No+Poster'">
+                 onerror="this.src='no-poster.svg'">
             <div class="search-result-info">
                 <div class="search-result-title">${escapeHtml(item.title)}</div>
                 <div class="search-result-meta">${typeLabel}${year}</div>